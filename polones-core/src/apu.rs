@@ -1,15 +1,16 @@
 use crate::cpu::Cpu;
-use crate::nes::Peripherals;
+use crate::nes::{Peripherals, Region};
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
 pub type AudioSample = u16;
 pub const AUDIO_BATCH_SIZE: usize = 1024 * 16;
 
-const LENGTH_COUNTER_TABLE: [u8; 32] = [
+pub(crate) const LENGTH_COUNTER_TABLE: [u8; 32] = [
     10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
     192, 24, 72, 26, 16, 28, 32, 30,
 ];
 
-const PULSE_MIX_TABLE: [u16; 32] = [
+pub(crate) const PULSE_MIX_TABLE: [u16; 32] = [
     0, 749, 1479, 2193, 2889, 3569, 4234, 4884, 5519, 6140, 6748, 7342, 7924, 8493, 9050, 9596,
     10131, 10654, 11168, 11670, 12163, 12647, 13121, 13585, 14041, 14489, 14928, 15359, 15782,
     16197, 16605, 17006,
@@ -20,11 +21,29 @@ const TRIANGLE_SEQUENCE_TABLE: [u8; 32] = [
     13, 14, 15,
 ];
 
-const NOISE_TIMER_TABLE: [u16; 16] = [
+const NOISE_TIMER_TABLE_NTSC: [u16; 16] = [
     4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
 ];
 
-const OTHER_MIX_TABLE: [u16; 204] = [
+const NOISE_TIMER_TABLE_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+const DMC_RATE_TABLE_NTSC: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+const DMC_RATE_TABLE_PAL: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// CPU cycles at which the frame counter clocks the 1st quarter frame, the
+/// 1st half frame, the 3rd quarter frame, the 2nd half frame in 4-step mode
+/// (one cycle after the IRQ) and the 2nd half frame in 5-step mode.
+const FRAME_COUNTER_STEPS_NTSC: [u16; 5] = [7457, 14913, 22371, 29829, 37281];
+const FRAME_COUNTER_STEPS_PAL: [u16; 5] = [8313, 16627, 24939, 33253, 41565];
+
+pub(crate) const OTHER_MIX_TABLE: [u16; 204] = [
     0, 432, 861, 1286, 1707, 2125, 2540, 2952, 3360, 3765, 4167, 4565, 4961, 5353, 5743, 6129,
     6513, 6893, 7271, 7645, 8017, 8386, 8752, 9116, 9477, 9835, 10190, 10543, 10893, 11241, 11586,
     11928, 12268, 12606, 12941, 13274, 13604, 13932, 14258, 14581, 14902, 15221, 15538, 15852,
@@ -114,7 +133,7 @@ impl Pulse {
             ..Self::new_with_complement()
         }
     }
-    fn tick(&mut self) {
+    pub(crate) fn tick(&mut self) {
         if self.timer_divider_counter != 0 {
             self.timer_divider_counter -= 1;
         } else {
@@ -122,7 +141,7 @@ impl Pulse {
             self.sequencer_step = (self.sequencer_step + 1) & 0b111;
         }
     }
-    fn tick_envelope(&mut self) {
+    pub(crate) fn tick_envelope(&mut self) {
         if !self.envelope_start_flag {
             // clock divider
             if self.envelope_divider_counter > 0 {
@@ -142,7 +161,7 @@ impl Pulse {
             self.envelope_divider_counter = self.envelope_divider_period;
         }
     }
-    fn tick_length_counter(&mut self) {
+    pub(crate) fn tick_length_counter(&mut self) {
         if self.length_counter > 0 && !self.length_counter_halt {
             self.length_counter -= 1;
         }
@@ -263,6 +282,12 @@ pub struct Noise {
 
 impl Noise {
     fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_load;
+
         let feedback = ((self.linear_feedback_shift_register >> ((self.mode as u8) * 5) >> 1)
             ^ self.linear_feedback_shift_register)
             & 1;
@@ -334,11 +359,130 @@ impl Default for Noise {
 }
 
 pub struct Dmc {
-    enabled: bool,
-    interrupt: bool,
+    pub irq_enabled: bool,
+    pub loop_flag: bool,
+    pub rate_index: u8, // 4 bits
+    pub timer: u16,
+
+    pub sample_address: u16,
+    pub sample_length: u16,
+    pub current_address: u16,
+    pub bytes_remaining: u16,
+    pub sample_buffer: Option<u8>,
+    /// Set when a byte was requested from the CPU and hasn't arrived yet.
+    pub dma_pending: bool,
+
+    pub shift_register: u8,
+    pub bits_remaining: u8,
+    pub silence: bool,
+    pub output_level: u8, // 7 bits
+
+    pub interrupt: bool,
+}
+
+impl Dmc {
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu, rate_table: &[u16; 16]) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        } else {
+            self.timer = rate_table[self.rate_index as usize] - 1;
+            self.tick_output_unit();
+        }
+
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 && !self.dma_pending {
+            cpu.dmc_dma(self.current_address);
+            self.dma_pending = true;
+        }
+    }
+
+    fn tick_output_unit(&mut self) {
+        if !self.silence {
+            if self.shift_register & 1 == 1 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(byte) => {
+                    self.silence = false;
+                    self.shift_register = byte;
+                }
+                None => {
+                    self.silence = true;
+                }
+            }
+        }
+    }
+
+    /// Accepts the sample byte fetched by the CPU.
+    fn load_sample_byte(&mut self, byte: u8) {
+        self.dma_pending = false;
+        if self.bytes_remaining == 0 {
+            // Channel was disabled while the byte was being fetched.
+            return;
+        }
+
+        self.sample_buffer = Some(byte);
+        self.current_address = match self.current_address {
+            0xFFFF => 0x8000,
+            address => address + 1,
+        };
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else if self.irq_enabled {
+                self.interrupt = true;
+            }
+        }
+    }
+
+    fn volume(&self) -> u8 /* 0-127 */ {
+        self.output_level
+    }
+}
+
+impl Default for Dmc {
+    fn default() -> Self {
+        Self {
+            irq_enabled: false,
+            loop_flag: false,
+            rate_index: 0,
+            timer: DMC_RATE_TABLE_NTSC[0] - 1,
+
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            dma_pending: false,
+
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            output_level: 0,
+
+            interrupt: false,
+        }
+    }
 }
 
 pub struct Apu {
+    pub region: Region,
     pub pulse1: Pulse,
     pub pulse2: Pulse,
     pub triangle: Triangle,
@@ -356,14 +500,12 @@ pub struct Apu {
 impl Apu {
     pub fn new() -> Self {
         Self {
+            region: Region::Ntsc,
             pulse1: Pulse::new_with_complement(),
             pulse2: Pulse::new_without_complement(),
             triangle: Triangle::default(),
             noise: Noise::default(),
-            dmc: Dmc {
-                enabled: false,
-                interrupt: false,
-            },
+            dmc: Dmc::default(),
             cpu_cycle_odd: false,
             frame_counter_mode: false,
             frame_counter_interrupt: false,
@@ -381,7 +523,7 @@ impl Apu {
                     (((self.pulse2.length_counter > 0) as u8) << 1) |
                     (((self.triangle.length_counter > 0) as u8) << 2) |
                     (((self.noise.length_counter > 0) as u8) << 3) |
-                    // (((self.dmc.bytes.len() > 0) as u8) << 4) | // TODO
+                    (((self.dmc.bytes_remaining > 0) as u8) << 4) |
                     ((self.frame_counter_interrupt as u8) << 6) |
                     ((self.dmc.interrupt as u8) << 7);
 
@@ -476,7 +618,7 @@ impl Apu {
             0x400D => {}
             0x400E => {
                 self.noise.mode = value & 0b1000_0000 > 0;
-                self.noise.timer_load = NOISE_TIMER_TABLE[(value & 0b1111) as usize] as u16;
+                self.noise.timer_load = self.noise_timer_table()[(value & 0b1111) as usize] - 1;
             }
             0x400F => {
                 if self.noise.length_counter_enabled {
@@ -484,6 +626,23 @@ impl Apu {
                 }
                 self.noise.envelope_start_flag = true;
             }
+            0x4010 => {
+                self.dmc.irq_enabled = value & 0b1000_0000 > 0;
+                self.dmc.loop_flag = value & 0b0100_0000 > 0;
+                self.dmc.rate_index = value & 0b0000_1111;
+                if !self.dmc.irq_enabled {
+                    self.dmc.interrupt = false;
+                }
+            }
+            0x4011 => {
+                self.dmc.output_level = value & 0b0111_1111;
+            }
+            0x4012 => {
+                self.dmc.sample_address = 0xC000 | (value as u16) << 6;
+            }
+            0x4013 => {
+                self.dmc.sample_length = ((value as u16) << 4) + 1;
+            }
             0x4015 => {
                 self.pulse1.length_counter_enabled = (value & 1) > 0;
                 if !self.pulse1.length_counter_enabled {
@@ -501,9 +660,14 @@ impl Apu {
                 if !self.noise.length_counter_enabled {
                     self.noise.length_counter = 0;
                 }
-                self.dmc.enabled = (value & 16) > 0;
+                if (value & 16) > 0 {
+                    if self.dmc.bytes_remaining == 0 {
+                        self.dmc.restart();
+                    }
+                } else {
+                    self.dmc.bytes_remaining = 0;
+                }
                 self.dmc.interrupt = false;
-                // TODO update other dmc fields
             }
             0x4017 => {
                 self.frame_counter_mode = (value & 0b10000000) > 0;
@@ -519,18 +683,25 @@ impl Apu {
         }
     }
 
-    pub fn tick(&mut self, cpu: &mut Cpu, peripherals: &mut Peripherals) {
+    /// Runs one CPU cycle. `expansion_audio` is the cartridge's audio output,
+    /// added to the mix.
+    pub fn tick(&mut self, cpu: &mut Cpu, peripherals: &mut Peripherals, expansion_audio: i32) {
+        let [quarter_1, half_1, quarter_3, half_2_4_step, half_2_5_step] = match self.region {
+            Region::Ntsc | Region::Dendy => FRAME_COUNTER_STEPS_NTSC,
+            Region::Pal => FRAME_COUNTER_STEPS_PAL,
+        };
+
         if self.frame_counter_mode {
             // 5 step
             match self.frame_counter {
-                7457 => {
+                cycle if cycle == quarter_1 => {
                     self.pulse1.tick_envelope();
                     self.pulse2.tick_envelope();
                     self.triangle.tick_linear_counter();
                     self.noise.tick_envelope();
                     self.frame_counter += 1;
                 }
-                14913 => {
+                cycle if cycle == half_1 => {
                     self.pulse1.tick_envelope();
                     self.pulse1.tick_length_counter();
                     self.pulse1.tick_sweep();
@@ -543,14 +714,14 @@ impl Apu {
                     self.noise.tick_length_counter();
                     self.frame_counter += 1;
                 }
-                22371 => {
+                cycle if cycle == quarter_3 => {
                     self.pulse1.tick_envelope();
                     self.pulse2.tick_envelope();
                     self.triangle.tick_linear_counter();
                     self.noise.tick_envelope();
                     self.frame_counter += 1;
                 }
-                37281 => {
+                cycle if cycle == half_2_5_step => {
                     self.pulse1.tick_envelope();
                     self.pulse1.tick_length_counter();
                     self.pulse1.tick_sweep();
@@ -570,14 +741,14 @@ impl Apu {
         } else {
             // 4 step
             match self.frame_counter {
-                7457 => {
+                cycle if cycle == quarter_1 => {
                     self.pulse1.tick_envelope();
                     self.pulse2.tick_envelope();
                     self.triangle.tick_linear_counter();
                     self.noise.tick_envelope();
                     self.frame_counter += 1;
                 }
-                14913 => {
+                cycle if cycle == half_1 => {
                     self.pulse1.tick_envelope();
                     self.pulse1.tick_length_counter();
                     self.pulse1.tick_sweep();
@@ -590,21 +761,21 @@ impl Apu {
                     self.noise.tick_length_counter();
                     self.frame_counter += 1;
                 }
-                22371 => {
+                cycle if cycle == quarter_3 => {
                     self.pulse1.tick_envelope();
                     self.pulse2.tick_envelope();
                     self.triangle.tick_linear_counter();
                     self.noise.tick_envelope();
                     self.frame_counter += 1;
                 }
-                29828 => {
+                cycle if cycle == half_2_4_step - 1 => {
                     if !self.frame_counter_interrupt_inhibit {
                         cpu.irq();
                         self.frame_counter_interrupt = true;
                     }
                     self.frame_counter += 1;
                 }
-                29829 => {
+                cycle if cycle == half_2_4_step => {
                     self.pulse1.tick_envelope();
                     self.pulse1.tick_length_counter();
                     self.pulse1.tick_sweep();
@@ -627,20 +798,26 @@ impl Apu {
         if !self.cpu_cycle_odd {
             self.pulse1.tick();
             self.pulse2.tick();
-            self.noise.tick();
         }
 
         self.triangle.tick();
+        self.noise.tick();
+        let dmc_rate_table = self.dmc_rate_table();
+        self.dmc.tick(cpu, dmc_rate_table);
+        if self.dmc.interrupt {
+            cpu.irq();
+        }
 
         let pulse1_sample = !self.pulse1.muted() as u8 * self.pulse1.volume();
         let pulse2_sample = !self.pulse2.muted() as u8 * self.pulse2.volume();
         let triangle_sample = self.triangle.volume();
         let noise_sample = self.noise.volume();
-        let dmc_sample = 0;
+        let dmc_sample = self.dmc.volume();
 
         let mix = PULSE_MIX_TABLE[(pulse1_sample + pulse2_sample) as usize]
             + OTHER_MIX_TABLE
                 [3 * triangle_sample as usize + 2 * noise_sample as usize + dmc_sample as usize];
+        let mix = (mix as i32 + expansion_audio).clamp(0, AudioSample::MAX as i32) as AudioSample;
 
         self.samples.push(mix);
 
@@ -654,8 +831,224 @@ impl Apu {
         self.cpu_cycle_odd = !self.cpu_cycle_odd;
     }
 
+    fn noise_timer_table(&self) -> &'static [u16; 16] {
+        match self.region {
+            Region::Ntsc | Region::Dendy => &NOISE_TIMER_TABLE_NTSC,
+            Region::Pal => &NOISE_TIMER_TABLE_PAL,
+        }
+    }
+
+    fn dmc_rate_table(&self) -> &'static [u16; 16] {
+        match self.region {
+            Region::Ntsc | Region::Dendy => &DMC_RATE_TABLE_NTSC,
+            Region::Pal => &DMC_RATE_TABLE_PAL,
+        }
+    }
+
+    /// Called by the CPU when the sample byte requested by the DMC arrives.
+    pub fn load_dmc_sample_byte(&mut self, byte: u8) {
+        self.dmc.load_sample_byte(byte);
+    }
+
     // Removes all generated samples.
     pub fn clear_samples(&mut self) {
         self.samples.clear();
     }
 }
+
+impl SaveState for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.envelope_divider_period);
+        state.write_u8(self.envelope_divider_counter);
+        state.write_bool(self.envelope_start_flag);
+        state.write_u8(self.envelope_decay_level_counter);
+        state.write_bool(self.envelope_loop_flag);
+        state.write_bool(self.envelope_constant_volume_flag);
+        state.write_bool(self.sweep_enabled);
+        state.write_u8(self.sweep_divider_period);
+        state.write_u8(self.sweep_divider_counter);
+        state.write_bool(self.sweep_negate_flag);
+        state.write_u8(self.sweep_shift_count);
+        state.write_bool(self.sweep_reload_flag);
+        state.write_u16(self.timer_divider_period);
+        state.write_u16(self.timer_divider_counter);
+        state.write_u8(self.sequencer_duty);
+        state.write_u8(self.sequencer_step);
+        state.write_u8(self.length_counter);
+        state.write_bool(self.length_counter_halt);
+        state.write_bool(self.length_counter_enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.envelope_divider_period = state.read_u8()? & 0b1111;
+        self.envelope_divider_counter = state.read_u8()? & 0b1111;
+        self.envelope_start_flag = state.read_bool()?;
+        self.envelope_decay_level_counter = state.read_u8()? & 0b1111;
+        self.envelope_loop_flag = state.read_bool()?;
+        self.envelope_constant_volume_flag = state.read_bool()?;
+        self.sweep_enabled = state.read_bool()?;
+        self.sweep_divider_period = state.read_u8()?;
+        self.sweep_divider_counter = state.read_u8()?;
+        self.sweep_negate_flag = state.read_bool()?;
+        self.sweep_shift_count = state.read_u8()? & 0b111;
+        self.sweep_reload_flag = state.read_bool()?;
+        self.timer_divider_period = state.read_u16()?;
+        self.timer_divider_counter = state.read_u16()?;
+        self.sequencer_duty = state.read_u8()? & 0b11;
+        self.sequencer_step = state.read_u8()? & 0b111;
+        self.length_counter = state.read_u8()?;
+        self.length_counter_halt = state.read_bool()?;
+        self.length_counter_enabled = state.read_bool()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Triangle {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u16(self.timer);
+        state.write_u16(self.timer_load);
+        state.write_u8(self.linear_counter);
+        state.write_u8(self.linear_counter_load);
+        state.write_bool(self.linear_counter_reload);
+        state.write_u8(self.length_counter);
+        state.write_bool(self.length_counter_halt);
+        state.write_bool(self.length_counter_enabled);
+        state.write_u8(self.sequencer_step);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.enabled = state.read_bool()?;
+        self.timer = state.read_u16()?;
+        self.timer_load = state.read_u16()?;
+        self.linear_counter = state.read_u8()?;
+        self.linear_counter_load = state.read_u8()?;
+        self.linear_counter_reload = state.read_bool()?;
+        self.length_counter = state.read_u8()?;
+        self.length_counter_halt = state.read_bool()?;
+        self.length_counter_enabled = state.read_bool()?;
+        self.sequencer_step = state.read_u8()? & 0b11111;
+        Ok(())
+    }
+}
+
+impl SaveState for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.envelope_divider_period);
+        state.write_u8(self.envelope_divider_counter);
+        state.write_bool(self.envelope_start_flag);
+        state.write_u8(self.envelope_decay_level_counter);
+        state.write_bool(self.envelope_loop_flag);
+        state.write_bool(self.envelope_constant_volume_flag);
+        state.write_u8(self.length_counter);
+        state.write_bool(self.length_counter_halt);
+        state.write_bool(self.length_counter_enabled);
+        state.write_bool(self.mode);
+        state.write_u16(self.timer);
+        state.write_u16(self.timer_load);
+        state.write_u16(self.linear_feedback_shift_register);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.envelope_divider_period = state.read_u8()? & 0b1111;
+        self.envelope_divider_counter = state.read_u8()? & 0b1111;
+        self.envelope_start_flag = state.read_bool()?;
+        self.envelope_decay_level_counter = state.read_u8()? & 0b1111;
+        self.envelope_loop_flag = state.read_bool()?;
+        self.envelope_constant_volume_flag = state.read_bool()?;
+        self.length_counter = state.read_u8()?;
+        self.length_counter_halt = state.read_bool()?;
+        self.length_counter_enabled = state.read_bool()?;
+        self.mode = state.read_bool()?;
+        self.timer = state.read_u16()?;
+        self.timer_load = state.read_u16()?;
+        self.linear_feedback_shift_register = state.read_u16()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Dmc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.loop_flag);
+        state.write_u8(self.rate_index);
+        state.write_u16(self.timer);
+        state.write_u16(self.sample_address);
+        state.write_u16(self.sample_length);
+        state.write_u16(self.current_address);
+        state.write_u16(self.bytes_remaining);
+        state.write_bool(self.sample_buffer.is_some());
+        state.write_u8(self.sample_buffer.unwrap_or(0));
+        state.write_bool(self.dma_pending);
+        state.write_u8(self.shift_register);
+        state.write_u8(self.bits_remaining);
+        state.write_bool(self.silence);
+        state.write_u8(self.output_level);
+        state.write_bool(self.interrupt);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.irq_enabled = state.read_bool()?;
+        self.loop_flag = state.read_bool()?;
+        self.rate_index = state.read_u8()? & 0b1111;
+        self.timer = state.read_u16()?;
+        self.sample_address = state.read_u16()?;
+        self.sample_length = state.read_u16()?;
+        self.current_address = state.read_u16()?;
+        self.bytes_remaining = state.read_u16()?;
+        let buffer_full = state.read_bool()?;
+        let buffer = state.read_u8()?;
+        self.sample_buffer = buffer_full.then_some(buffer);
+        self.dma_pending = state.read_bool()?;
+        self.shift_register = state.read_u8()?;
+        self.bits_remaining = state.read_u8()?;
+        self.silence = state.read_bool()?;
+        self.output_level = state.read_u8()? & 0b0111_1111;
+        self.interrupt = state.read_bool()?;
+        if !(1..=8).contains(&self.bits_remaining) {
+            return Err("Save state: Invalid DMC bit counter");
+        }
+        Ok(())
+    }
+}
+
+impl SaveState for Apu {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.write_bool(self.cpu_cycle_odd);
+        state.write_bool(self.frame_counter_mode);
+        state.write_bool(self.frame_counter_interrupt);
+        state.write_bool(self.frame_counter_interrupt_inhibit);
+        state.write_u16(self.frame_counter);
+        state.write_u32(self.samples.len() as u32);
+        for sample in &self.samples {
+            state.write_u16(*sample);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.cpu_cycle_odd = state.read_bool()?;
+        self.frame_counter_mode = state.read_bool()?;
+        self.frame_counter_interrupt = state.read_bool()?;
+        self.frame_counter_interrupt_inhibit = state.read_bool()?;
+        self.frame_counter = state.read_u16()?;
+        let samples = state.read_u32()? as usize;
+        if samples >= AUDIO_BATCH_SIZE {
+            return Err("Save state: Too many pending audio samples");
+        }
+        self.samples.clear();
+        for _ in 0..samples {
+            self.samples.push(state.read_u16()?);
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::nes::CpuBus;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
 pub struct Cpu {
     // registers
@@ -26,7 +27,10 @@ pub struct Cpu {
     dma_page: u8,
     dma_byte: u8,
     dma_cycles_left: u16,
+    dmc_dma_address: u16,
+    dmc_dma_cycles_left: u8,
     cycle_odd: bool,
+    halted: bool, // set by JAM opcodes, cleared by reset
 
     pub cycle: u64,
 }
@@ -62,7 +66,10 @@ impl Cpu {
             dma_page: 0,
             dma_byte: 0,
             dma_cycles_left: 0,
+            dmc_dma_address: 0,
+            dmc_dma_cycles_left: 0,
             cycle_odd: false,
+            halted: false,
 
             cycle: 0,
         }
@@ -73,22 +80,22 @@ impl Cpu {
     #[rustfmt::skip]
     const INSTRUCTION_SET: [fn (cpu: &mut Self, cpu_bus: &mut CpuBus); 256] = [
         /* HI   LO    0             1             2             3             4             5             6             7             8             9             A             B             C             D             E             F    */
-        /* 0 */ Self::run_00, Self::run_01, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_05, Self::run_06, Self::run_xx, Self::run_08, Self::run_09, Self::run_0A, Self::run_xx, Self::run_xx, Self::run_0D, Self::run_0E, Self::run_xx,
-        /* 1 */ Self::run_10, Self::run_11, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_15, Self::run_16, Self::run_xx, Self::run_18, Self::run_19, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_1D, Self::run_1E, Self::run_xx,
-        /* 2 */ Self::run_20, Self::run_21, Self::run_xx, Self::run_xx, Self::run_24, Self::run_25, Self::run_26, Self::run_xx, Self::run_28, Self::run_29, Self::run_2A, Self::run_xx, Self::run_2C, Self::run_2D, Self::run_2E, Self::run_xx,
-        /* 3 */ Self::run_30, Self::run_31, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_35, Self::run_36, Self::run_xx, Self::run_38, Self::run_39, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_3D, Self::run_3E, Self::run_xx,
-        /* 4 */ Self::run_40, Self::run_41, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_45, Self::run_46, Self::run_xx, Self::run_48, Self::run_49, Self::run_4A, Self::run_xx, Self::run_4C, Self::run_4D, Self::run_4E, Self::run_xx,
-        /* 5 */ Self::run_50, Self::run_51, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_55, Self::run_56, Self::run_xx, Self::run_58, Self::run_59, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_5D, Self::run_5E, Self::run_xx,
-        /* 6 */ Self::run_60, Self::run_61, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_65, Self::run_66, Self::run_xx, Self::run_68, Self::run_69, Self::run_6A, Self::run_xx, Self::run_6C, Self::run_6D, Self::run_6E, Self::run_xx,
-        /* 7 */ Self::run_70, Self::run_71, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_75, Self::run_76, Self::run_xx, Self::run_78, Self::run_79, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_7D, Self::run_7E, Self::run_xx,
-        /* 8 */ Self::run_xx, Self::run_81, Self::run_xx, Self::run_xx, Self::run_84, Self::run_85, Self::run_86, Self::run_xx, Self::run_88, Self::run_xx, Self::run_8A, Self::run_xx, Self::run_8C, Self::run_8D, Self::run_8E, Self::run_xx,
-        /* 9 */ Self::run_90, Self::run_91, Self::run_xx, Self::run_xx, Self::run_94, Self::run_95, Self::run_96, Self::run_xx, Self::run_98, Self::run_99, Self::run_9A, Self::run_xx, Self::run_xx, Self::run_9D, Self::run_xx, Self::run_xx,
-        /* A */ Self::run_A0, Self::run_A1, Self::run_A2, Self::run_xx, Self::run_A4, Self::run_A5, Self::run_A6, Self::run_xx, Self::run_A8, Self::run_A9, Self::run_AA, Self::run_xx, Self::run_AC, Self::run_AD, Self::run_AE, Self::run_xx,
-        /* B */ Self::run_B0, Self::run_B1, Self::run_xx, Self::run_xx, Self::run_B4, Self::run_B5, Self::run_B6, Self::run_xx, Self::run_B8, Self::run_B9, Self::run_BA, Self::run_xx, Self::run_BC, Self::run_BD, Self::run_BE, Self::run_xx,
-        /* C */ Self::run_C0, Self::run_C1, Self::run_xx, Self::run_xx, Self::run_C4, Self::run_C5, Self::run_C6, Self::run_xx, Self::run_C8, Self::run_C9, Self::run_CA, Self::run_xx, Self::run_CC, Self::run_CD, Self::run_CE, Self::run_xx,
-        /* D */ Self::run_D0, Self::run_D1, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_D5, Self::run_D6, Self::run_xx, Self::run_D8, Self::run_D9, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_DD, Self::run_DE, Self::run_xx,
-        /* E */ Self::run_E0, Self::run_E1, Self::run_xx, Self::run_xx, Self::run_E4, Self::run_E5, Self::run_E6, Self::run_xx, Self::run_E8, Self::run_E9, Self::run_EA, Self::run_xx, Self::run_EC, Self::run_ED, Self::run_EE, Self::run_xx,
-        /* F */ Self::run_F0, Self::run_F1, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_F5, Self::run_F6, Self::run_xx, Self::run_F8, Self::run_F9, Self::run_xx, Self::run_xx, Self::run_xx, Self::run_FD, Self::run_FE, Self::run_xx,
+        /* 0 */ Self::run_00, Self::run_01, Self::run_02, Self::run_03, Self::run_04, Self::run_05, Self::run_06, Self::run_07, Self::run_08, Self::run_09, Self::run_0A, Self::run_0B, Self::run_0C, Self::run_0D, Self::run_0E, Self::run_0F,
+        /* 1 */ Self::run_10, Self::run_11, Self::run_12, Self::run_13, Self::run_14, Self::run_15, Self::run_16, Self::run_17, Self::run_18, Self::run_19, Self::run_1A, Self::run_1B, Self::run_1C, Self::run_1D, Self::run_1E, Self::run_1F,
+        /* 2 */ Self::run_20, Self::run_21, Self::run_22, Self::run_23, Self::run_24, Self::run_25, Self::run_26, Self::run_27, Self::run_28, Self::run_29, Self::run_2A, Self::run_2B, Self::run_2C, Self::run_2D, Self::run_2E, Self::run_2F,
+        /* 3 */ Self::run_30, Self::run_31, Self::run_32, Self::run_33, Self::run_34, Self::run_35, Self::run_36, Self::run_37, Self::run_38, Self::run_39, Self::run_3A, Self::run_3B, Self::run_3C, Self::run_3D, Self::run_3E, Self::run_3F,
+        /* 4 */ Self::run_40, Self::run_41, Self::run_42, Self::run_43, Self::run_44, Self::run_45, Self::run_46, Self::run_47, Self::run_48, Self::run_49, Self::run_4A, Self::run_4B, Self::run_4C, Self::run_4D, Self::run_4E, Self::run_4F,
+        /* 5 */ Self::run_50, Self::run_51, Self::run_52, Self::run_53, Self::run_54, Self::run_55, Self::run_56, Self::run_57, Self::run_58, Self::run_59, Self::run_5A, Self::run_5B, Self::run_5C, Self::run_5D, Self::run_5E, Self::run_5F,
+        /* 6 */ Self::run_60, Self::run_61, Self::run_62, Self::run_63, Self::run_64, Self::run_65, Self::run_66, Self::run_67, Self::run_68, Self::run_69, Self::run_6A, Self::run_6B, Self::run_6C, Self::run_6D, Self::run_6E, Self::run_6F,
+        /* 7 */ Self::run_70, Self::run_71, Self::run_72, Self::run_73, Self::run_74, Self::run_75, Self::run_76, Self::run_77, Self::run_78, Self::run_79, Self::run_7A, Self::run_7B, Self::run_7C, Self::run_7D, Self::run_7E, Self::run_7F,
+        /* 8 */ Self::run_80, Self::run_81, Self::run_82, Self::run_83, Self::run_84, Self::run_85, Self::run_86, Self::run_87, Self::run_88, Self::run_89, Self::run_8A, Self::run_8B, Self::run_8C, Self::run_8D, Self::run_8E, Self::run_8F,
+        /* 9 */ Self::run_90, Self::run_91, Self::run_92, Self::run_93, Self::run_94, Self::run_95, Self::run_96, Self::run_97, Self::run_98, Self::run_99, Self::run_9A, Self::run_9B, Self::run_9C, Self::run_9D, Self::run_9E, Self::run_9F,
+        /* A */ Self::run_A0, Self::run_A1, Self::run_A2, Self::run_A3, Self::run_A4, Self::run_A5, Self::run_A6, Self::run_A7, Self::run_A8, Self::run_A9, Self::run_AA, Self::run_AB, Self::run_AC, Self::run_AD, Self::run_AE, Self::run_AF,
+        /* B */ Self::run_B0, Self::run_B1, Self::run_B2, Self::run_B3, Self::run_B4, Self::run_B5, Self::run_B6, Self::run_B7, Self::run_B8, Self::run_B9, Self::run_BA, Self::run_BB, Self::run_BC, Self::run_BD, Self::run_BE, Self::run_BF,
+        /* C */ Self::run_C0, Self::run_C1, Self::run_C2, Self::run_C3, Self::run_C4, Self::run_C5, Self::run_C6, Self::run_C7, Self::run_C8, Self::run_C9, Self::run_CA, Self::run_CB, Self::run_CC, Self::run_CD, Self::run_CE, Self::run_CF,
+        /* D */ Self::run_D0, Self::run_D1, Self::run_D2, Self::run_D3, Self::run_D4, Self::run_D5, Self::run_D6, Self::run_D7, Self::run_D8, Self::run_D9, Self::run_DA, Self::run_DB, Self::run_DC, Self::run_DD, Self::run_DE, Self::run_DF,
+        /* E */ Self::run_E0, Self::run_E1, Self::run_E2, Self::run_E3, Self::run_E4, Self::run_E5, Self::run_E6, Self::run_E7, Self::run_E8, Self::run_E9, Self::run_EA, Self::run_EB, Self::run_EC, Self::run_ED, Self::run_EE, Self::run_EF,
+        /* F */ Self::run_F0, Self::run_F1, Self::run_F2, Self::run_F3, Self::run_F4, Self::run_F5, Self::run_F6, Self::run_F7, Self::run_F8, Self::run_F9, Self::run_FA, Self::run_FB, Self::run_FC, Self::run_FD, Self::run_FE, Self::run_FF,
     ];
 
     pub fn reset(&mut self, cpu_bus: &mut CpuBus) {
@@ -96,6 +103,13 @@ impl Cpu {
         let high = cpu_bus.read(0xFFFD);
         self.program_counter = ((high as u16) << 8) | low as u16;
         self.sleep_cycles = 8;
+        self.halted = false;
+    }
+
+    /// Returns true if the CPU executed a JAM (KIL) opcode. A jammed CPU stops
+    /// executing instructions and ignores interrupts until reset.
+    pub fn halted(&self) -> bool {
+        self.halted
     }
 
     pub fn nmi(&mut self) {
@@ -114,10 +128,26 @@ impl Cpu {
         self.dma_cycles_left = 512 + 1 + !self.cycle_odd as u16;
     }
 
+    /// Stalls the CPU to fetch a DMC sample byte from `address`.
+    pub fn dmc_dma(&mut self, address: u16) {
+        self.dmc_dma_address = address;
+        self.dmc_dma_cycles_left = 4;
+    }
+
     pub fn tick(&mut self, cpu_bus: &mut CpuBus) {
         self.cycle_odd = !self.cycle_odd;
         self.cycle += 1;
 
+        // the APU keeps fetching DMC samples even if the CPU jammed
+        if self.dmc_dma_cycles_left > 0 {
+            self.run_dmc_dma(cpu_bus);
+            return;
+        }
+
+        if self.halted {
+            return;
+        }
+
         if self.dma_cycles_left > 0 {
             self.run_dma(cpu_bus);
             return;
@@ -217,8 +247,17 @@ impl Cpu {
         self.dma_cycles_left -= 1;
     }
 
+    fn run_dmc_dma(&mut self, cpu_bus: &mut CpuBus) {
+        self.dmc_dma_cycles_left -= 1;
+        if self.dmc_dma_cycles_left == 0 {
+            let byte = cpu_bus.read(self.dmc_dma_address);
+            cpu_bus.apu.load_dmc_sample_byte(byte);
+        }
+    }
+
     pub fn finished_instruction(&self) -> bool {
         self.dma_cycles_left == 0
+            && self.dmc_dma_cycles_left == 0
             && self.sleep_cycles == 0
             && self.nmi_sleep_cycles == 0
             && self.irq_sleep_cycles == 0
@@ -232,6 +271,16 @@ impl Cpu {
         }
     }
 
+    // Reads the operand of a read-modify-write instruction. Like the real
+    // CPU, writes the unmodified byte back before the result is written.
+    fn read_modify_operand_byte(&mut self, cpu_bus: &mut CpuBus) -> u8 {
+        let byte = self.get_operand_byte(cpu_bus);
+        if !self.operand_accumulator {
+            cpu_bus.write(self.operand_address, byte);
+        }
+        byte
+    }
+
     fn set_operand_byte(&mut self, cpu_bus: &mut CpuBus, byte: u8) {
         if self.operand_accumulator {
             self.accumulator = byte;
@@ -408,6 +457,11 @@ impl Cpu {
     // ADC
     fn add_with_carry(&mut self, cpu_bus: &mut CpuBus) {
         let operand_byte = self.get_operand_byte(cpu_bus);
+        self.add_to_accumulator(operand_byte);
+    }
+
+    // Shared by ADC, SBC and their unofficial variants.
+    fn add_to_accumulator(&mut self, operand_byte: u8) {
         let old_accumulator = self.accumulator;
 
         let result =
@@ -437,7 +491,7 @@ impl Cpu {
 
     // ASL
     fn arithmetic_shift_left(&mut self, cpu_bus: &mut CpuBus) {
-        let mut operand_byte = self.get_operand_byte(cpu_bus);
+        let mut operand_byte = self.read_modify_operand_byte(cpu_bus);
         let leftmost_bit = operand_byte & 0b10000000;
         operand_byte <<= 1;
 
@@ -594,7 +648,7 @@ impl Cpu {
 
     // DEC
     fn decrement(&mut self, cpu_bus: &mut CpuBus) {
-        let operand_byte = self.get_operand_byte(cpu_bus).wrapping_sub(1);
+        let operand_byte = self.read_modify_operand_byte(cpu_bus).wrapping_sub(1);
         self.status_register
             .set_negative(operand_byte & 0b10000000 > 0);
         self.status_register.set_zero(operand_byte == 0);
@@ -628,7 +682,7 @@ impl Cpu {
 
     // INC
     fn increment(&mut self, cpu_bus: &mut CpuBus) {
-        let operand_byte = self.get_operand_byte(cpu_bus).wrapping_add(1);
+        let operand_byte = self.read_modify_operand_byte(cpu_bus).wrapping_add(1);
         self.status_register
             .set_negative(operand_byte & 0b10000000 > 0);
         self.status_register.set_zero(operand_byte == 0);
@@ -695,7 +749,7 @@ impl Cpu {
 
     // LSR
     fn logical_shift_right(&mut self, cpu_bus: &mut CpuBus) {
-        let mut operand_byte = self.get_operand_byte(cpu_bus);
+        let mut operand_byte = self.read_modify_operand_byte(cpu_bus);
         let rightmost_bit = operand_byte & 1;
         operand_byte >>= 1;
         self.status_register.set_negative(false);
@@ -751,7 +805,7 @@ impl Cpu {
 
     // ROL
     fn rotate_left(&mut self, cpu_bus: &mut CpuBus) {
-        let mut operand_byte = self.get_operand_byte(cpu_bus);
+        let mut operand_byte = self.read_modify_operand_byte(cpu_bus);
         let leftmost_bit = operand_byte & 0b10000000;
         operand_byte = (operand_byte << 1) | self.status_register.get_carry() as u8;
         self.status_register.set_carry(leftmost_bit > 0);
@@ -763,7 +817,7 @@ impl Cpu {
 
     // ROR
     fn rotate_right(&mut self, cpu_bus: &mut CpuBus) {
-        let mut operand_byte = self.get_operand_byte(cpu_bus);
+        let mut operand_byte = self.read_modify_operand_byte(cpu_bus);
         let rightmost_bit = operand_byte & 1;
         operand_byte = (operand_byte >> 1) | ((self.status_register.get_carry() as u8) << 7);
         self.status_register.set_carry(rightmost_bit > 0);
@@ -798,19 +852,7 @@ impl Cpu {
     fn subtract_with_carry(&mut self, cpu_bus: &mut CpuBus) {
         // invert operand byte and proceed as with addition
         let operand_byte = !self.get_operand_byte(cpu_bus);
-        let old_accumulator = self.accumulator;
-
-        let result =
-            self.accumulator as u16 + operand_byte as u16 + self.status_register.get_carry() as u16;
-        self.accumulator = result as u8;
-
-        self.status_register
-            .set_negative(self.accumulator & 0b10000000 > 0);
-        self.status_register.set_zero(self.accumulator == 0);
-        self.status_register.set_carry(result > 0xFF);
-        self.status_register.set_overflow(
-            (old_accumulator as u16 ^ result) & (operand_byte as u16 ^ result) & 0x0080 > 0,
-        );
+        self.add_to_accumulator(operand_byte);
     }
 
     // SEC
@@ -888,6 +930,201 @@ impl Cpu {
         self.status_register.set_zero(self.accumulator == 0);
     }
 
+    // unofficial operations
+
+    // Writes of SHA, SHX, SHY and TAS. The value is ANDed with the high byte of
+    // the base address plus one. If indexing crossed a page, the value also
+    // replaces the high byte of the target address.
+    fn store_high_and(&mut self, cpu_bus: &mut CpuBus, value: u8, index: u8) {
+        let base_high = (self.operand_address.wrapping_sub(index as u16) >> 8) as u8;
+        let value = value & base_high.wrapping_add(1);
+        let address = if self.crossed_page_boundary {
+            ((value as u16) << 8) | (self.operand_address & 0x00FF)
+        } else {
+            self.operand_address
+        };
+        cpu_bus.write(address, value);
+    }
+
+    // ALR
+    fn and_shift_right(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.get_operand_byte(cpu_bus);
+        let and = self.accumulator & operand_byte;
+        self.accumulator = and >> 1;
+        self.status_register.set_carry(and & 1 > 0);
+        self.status_register.set_negative(false);
+        self.status_register.set_zero(self.accumulator == 0);
+    }
+
+    // ANC
+    fn and_with_carry(&mut self, cpu_bus: &mut CpuBus) {
+        self.and(cpu_bus);
+        self.status_register
+            .set_carry(self.accumulator & 0b10000000 > 0);
+    }
+
+    // ANE (XAA), unstable
+    fn and_x_immediate(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.get_operand_byte(cpu_bus);
+        self.accumulator = (self.accumulator | 0xEE) & self.x_index & operand_byte;
+        self.status_register
+            .set_negative(self.accumulator & 0b10000000 > 0);
+        self.status_register.set_zero(self.accumulator == 0);
+    }
+
+    // ARR
+    fn and_rotate_right(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.get_operand_byte(cpu_bus);
+        let and = self.accumulator & operand_byte;
+        self.accumulator = (and >> 1) | ((self.status_register.get_carry() as u8) << 7);
+        let bit_6 = self.accumulator & 0b01000000 > 0;
+        let bit_5 = self.accumulator & 0b00100000 > 0;
+        self.status_register
+            .set_negative(self.accumulator & 0b10000000 > 0);
+        self.status_register.set_zero(self.accumulator == 0);
+        self.status_register.set_carry(bit_6);
+        self.status_register.set_overflow(bit_6 != bit_5);
+    }
+
+    // AXS (SBX)
+    fn and_x_subtract(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.get_operand_byte(cpu_bus);
+        let and = self.accumulator & self.x_index;
+        self.x_index = and.wrapping_sub(operand_byte);
+        self.status_register.set_carry(and >= operand_byte);
+        self.status_register
+            .set_negative(self.x_index & 0b10000000 > 0);
+        self.status_register.set_zero(self.x_index == 0);
+    }
+
+    // DCP
+    fn decrement_compare(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.read_modify_operand_byte(cpu_bus).wrapping_sub(1);
+        self.set_operand_byte(cpu_bus, operand_byte);
+        let accumulator_minus_operand = self.accumulator.wrapping_sub(operand_byte);
+        self.status_register
+            .set_negative(accumulator_minus_operand & 0b10000000 > 0);
+        self.status_register
+            .set_zero(self.accumulator == operand_byte);
+        self.status_register
+            .set_carry(self.accumulator >= operand_byte);
+    }
+
+    // ISC
+    fn increment_subtract(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.read_modify_operand_byte(cpu_bus).wrapping_add(1);
+        self.set_operand_byte(cpu_bus, operand_byte);
+        self.add_to_accumulator(!operand_byte);
+    }
+
+    // JAM (KIL)
+    fn jam(&mut self, _cpu_bus: &mut CpuBus) {
+        eprintln!(
+            "CPU: Jammed by opcode {:02X} at {:04X}.",
+            self.opcode, self.program_counter
+        );
+        self.halted = true;
+    }
+
+    // LAS
+    fn and_stack_pointer(&mut self, cpu_bus: &mut CpuBus) {
+        let value = self.get_operand_byte(cpu_bus) & self.stack_pointer;
+        self.accumulator = value;
+        self.x_index = value;
+        self.stack_pointer = value;
+        self.status_register.set_negative(value & 0b10000000 > 0);
+        self.status_register.set_zero(value == 0);
+    }
+
+    // LAX
+    fn load_accumulator_and_x(&mut self, cpu_bus: &mut CpuBus) {
+        self.load_accumulator(cpu_bus);
+        self.x_index = self.accumulator;
+    }
+
+    // LXA (LAX immediate), unstable
+    fn load_accumulator_and_x_immediate(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.get_operand_byte(cpu_bus);
+        self.accumulator = (self.accumulator | 0xEE) & operand_byte;
+        self.x_index = self.accumulator;
+        self.status_register
+            .set_negative(self.accumulator & 0b10000000 > 0);
+        self.status_register.set_zero(self.accumulator == 0);
+    }
+
+    // RLA
+    fn rotate_left_and(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.read_modify_operand_byte(cpu_bus);
+        let result = (operand_byte << 1) | self.status_register.get_carry() as u8;
+        self.set_operand_byte(cpu_bus, result);
+        self.status_register
+            .set_carry(operand_byte & 0b10000000 > 0);
+        self.accumulator &= result;
+        self.status_register
+            .set_negative(self.accumulator & 0b10000000 > 0);
+        self.status_register.set_zero(self.accumulator == 0);
+    }
+
+    // RRA
+    fn rotate_right_add(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.read_modify_operand_byte(cpu_bus);
+        let result = (operand_byte >> 1) | ((self.status_register.get_carry() as u8) << 7);
+        self.set_operand_byte(cpu_bus, result);
+        self.status_register.set_carry(operand_byte & 1 > 0);
+        self.add_to_accumulator(result);
+    }
+
+    // SAX
+    fn store_accumulator_and_x(&mut self, cpu_bus: &mut CpuBus) {
+        self.set_operand_byte(cpu_bus, self.accumulator & self.x_index);
+    }
+
+    // SHA (AHX), unstable
+    fn store_accumulator_and_x_high(&mut self, cpu_bus: &mut CpuBus) {
+        self.store_high_and(cpu_bus, self.accumulator & self.x_index, self.y_index);
+    }
+
+    // SHX, unstable
+    fn store_x_high(&mut self, cpu_bus: &mut CpuBus) {
+        self.store_high_and(cpu_bus, self.x_index, self.y_index);
+    }
+
+    // SHY, unstable
+    fn store_y_high(&mut self, cpu_bus: &mut CpuBus) {
+        self.store_high_and(cpu_bus, self.y_index, self.x_index);
+    }
+
+    // SLO
+    fn shift_left_or(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.read_modify_operand_byte(cpu_bus);
+        let result = operand_byte << 1;
+        self.set_operand_byte(cpu_bus, result);
+        self.status_register
+            .set_carry(operand_byte & 0b10000000 > 0);
+        self.accumulator |= result;
+        self.status_register
+            .set_negative(self.accumulator & 0b10000000 > 0);
+        self.status_register.set_zero(self.accumulator == 0);
+    }
+
+    // SRE
+    fn shift_right_exclusive_or(&mut self, cpu_bus: &mut CpuBus) {
+        let operand_byte = self.read_modify_operand_byte(cpu_bus);
+        let result = operand_byte >> 1;
+        self.set_operand_byte(cpu_bus, result);
+        self.status_register.set_carry(operand_byte & 1 > 0);
+        self.accumulator ^= result;
+        self.status_register
+            .set_negative(self.accumulator & 0b10000000 > 0);
+        self.status_register.set_zero(self.accumulator == 0);
+    }
+
+    // TAS (SHS), unstable
+    fn transfer_accumulator_and_x_to_stack_pointer(&mut self, cpu_bus: &mut CpuBus) {
+        self.stack_pointer = self.accumulator & self.x_index;
+        self.store_high_and(cpu_bus, self.stack_pointer, self.y_index);
+    }
+
     // operation codes handlers
 
     fn run_00(&mut self, cpu_bus: &mut CpuBus) {
@@ -938,7 +1175,11 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // 80 - illegal
+    fn run_80(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_90(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_relative(cpu_bus);
@@ -1085,16 +1326,47 @@ impl Cpu {
         self.sleep_cycles += self.crossed_page_boundary as u8;
     }
 
-    // 02 - illegal
-    // 12 - illegal
-    // 22 - illegal
-    // 32 - illegal
-    // 42 - illegal
-    // 52 - illegal
-    // 62 - illegal
-    // 72 - illegal
-    // 82 - illegal
-    // 92 - illegal
+    fn run_02(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_12(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_22(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_32(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_42(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_52(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_62(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_72(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
+
+    fn run_82(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_92(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
 
     fn run_A2(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_immediate(cpu_bus);
@@ -1102,145 +1374,286 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // B2 - illegal
-    // C2 - illegal
-    // D2 - illegal
-    // E2 - illegal
-    // F2 - illegal
-    // 03 - illegal
-    // 13 - illegal
-    // 23 - illegal
-    // 33 - illegal
-    // 43 - illegal
-    // 53 - illegal
-    // 63 - illegal
-    // 73 - illegal
-    // 83 - illegal
-    // 93 - illegal
-    // A3 - illegal
-    // B3 - illegal
-    // C3 - illegal
-    // D3 - illegal
-    // E3 - illegal
-    // F3 - illegal
-    // 04 - illegal
-    // 14 - illegal
+    fn run_B2(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
 
-    fn run_24(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.bit_test(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_C2(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
     }
 
-    // 34 - illegal
-    // 44 - illegal
-    // 54 - illegal
-    // 64 - illegal
-    // 74 - illegal
+    fn run_D2(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
+    }
 
-    fn run_84(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.store_y(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_E2(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
     }
 
-    fn run_94(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.store_y(cpu_bus);
-        self.sleep_cycles += 3;
+    fn run_F2(&mut self, cpu_bus: &mut CpuBus) {
+        self.jam(cpu_bus);
     }
 
-    fn run_A4(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.load_y(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_03(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 7;
     }
 
-    fn run_B4(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.load_y(cpu_bus);
-        self.sleep_cycles += 3;
+    fn run_13(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 7;
     }
 
-    fn run_C4(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.compare_with_y(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_23(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 7;
     }
 
-    // D4 - illegal
+    fn run_33(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 7;
+    }
 
-    fn run_E4(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.compare_with_x(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_43(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 7;
     }
 
-    // F4 - illegal
+    fn run_53(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 7;
+    }
 
-    fn run_05(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.or_with_accumulator(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_63(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 7;
     }
 
-    fn run_15(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.or_with_accumulator(cpu_bus);
-        self.sleep_cycles += 3;
+    fn run_73(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 7;
     }
 
-    fn run_25(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage(cpu_bus);
-        self.and(cpu_bus);
-        self.sleep_cycles += 2;
+    fn run_83(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.store_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 5;
     }
 
-    fn run_35(&mut self, cpu_bus: &mut CpuBus) {
-        self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.and(cpu_bus);
-        self.sleep_cycles += 3;
+    fn run_93(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.store_accumulator_and_x_high(cpu_bus);
+        self.sleep_cycles += 5;
     }
 
-    fn run_45(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_A3(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.load_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_B3(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.load_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 4;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
+
+    fn run_C3(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 7;
+    }
+
+    fn run_D3(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 7;
+    }
+
+    fn run_E3(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_x_indexed_indirect(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 7;
+    }
+
+    fn run_F3(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_indirect_y_indexed(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 7;
+    }
+
+    fn run_04(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage(cpu_bus);
-        self.exclusive_or(cpu_bus);
+        self.no_operation(cpu_bus);
         self.sleep_cycles += 2;
     }
 
-    fn run_55(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_14(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.exclusive_or(cpu_bus);
+        self.no_operation(cpu_bus);
         self.sleep_cycles += 3;
     }
 
-    fn run_65(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_24(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage(cpu_bus);
-        self.add_with_carry(cpu_bus);
+        self.bit_test(cpu_bus);
         self.sleep_cycles += 2;
     }
 
-    fn run_75(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_34(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.add_with_carry(cpu_bus);
+        self.no_operation(cpu_bus);
         self.sleep_cycles += 3;
     }
 
-    fn run_85(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_44(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage(cpu_bus);
-        self.store_accumulator(cpu_bus);
+        self.no_operation(cpu_bus);
         self.sleep_cycles += 2;
     }
 
-    fn run_95(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_54(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage_x_indexed(cpu_bus);
-        self.store_accumulator(cpu_bus);
+        self.no_operation(cpu_bus);
         self.sleep_cycles += 3;
     }
 
-    fn run_A5(&mut self, cpu_bus: &mut CpuBus) {
+    fn run_64(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_zeropage(cpu_bus);
-        self.load_accumulator(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_74(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_84(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.store_y(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_94(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.store_y(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_A4(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.load_y(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_B4(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.load_y(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_C4(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.compare_with_y(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_D4(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_E4(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.compare_with_x(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_F4(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_05(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.or_with_accumulator(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_15(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.or_with_accumulator(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_25(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.and(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_35(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.and(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_45(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.exclusive_or(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_55(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.exclusive_or(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_65(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.add_with_carry(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_75(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.add_with_carry(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_85(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.store_accumulator(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_95(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.store_accumulator(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_A5(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.load_accumulator(cpu_bus);
         self.sleep_cycles += 2;
     }
 
@@ -1370,22 +1783,101 @@ impl Cpu {
         self.sleep_cycles += 5;
     }
 
-    // 07 - illegal
-    // 17 - illegal
-    // 27 - illegal
-    // 37 - illegal
-    // 47 - illegal
-    // 57 - illegal
-    // 67 - illegal
-    // 77 - illegal
-    // 87 - illegal
-    // 97 - illegal
-    // A7 - illegal
-    // B7 - illegal
-    // C7 - illegal
-    // D7 - illegal
-    // E7 - illegal
-    // F7 - illegal
+    fn run_07(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_17(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_27(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_37(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_47(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_57(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_67(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_77(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_87(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.store_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_97(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_y_indexed(cpu_bus);
+        self.store_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_A7(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.load_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 2;
+    }
+
+    fn run_B7(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_y_indexed(cpu_bus);
+        self.load_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_C7(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_D7(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_E7(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_F7(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_zeropage_x_indexed(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 5;
+    }
 
     fn run_08(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_implied(cpu_bus);
@@ -1535,7 +2027,11 @@ impl Cpu {
         self.sleep_cycles += self.crossed_page_boundary as u8;
     }
 
-    // 89 - illegal
+    fn run_89(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_99(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute_y_indexed(cpu_bus);
@@ -1588,7 +2084,11 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // 1A - illegal
+    fn run_1A(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_implied(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_2A(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_implied(cpu_bus);
@@ -1596,7 +2096,11 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // 3A - illegal
+    fn run_3A(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_implied(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_4A(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_implied(cpu_bus);
@@ -1604,7 +2108,11 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // 5A - illegal
+    fn run_5A(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_implied(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_6A(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_implied(cpu_bus);
@@ -1612,7 +2120,11 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // 7A - illegal
+    fn run_7A(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_implied(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_8A(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_implied(cpu_bus);
@@ -1644,7 +2156,11 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // DA - illegal
+    fn run_DA(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_implied(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
 
     fn run_EA(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_implied(cpu_bus);
@@ -1652,25 +2168,121 @@ impl Cpu {
         self.sleep_cycles += 1;
     }
 
-    // FA - illegal
-    // 0B - illegal
-    // 1B - illegal
-    // 2B - illegal
-    // 3B - illegal
-    // 4B - illegal
-    // 5B - illegal
-    // 6B - illegal
-    // 7B - illegal
-    // 8B - illegal
-    // 9B - illegal
-    // AB - illegal
-    // BB - illegal
-    // CB - illegal
-    // DB - illegal
-    // EB - illegal
-    // FB - illegal
-    // 0C - illegal
-    // 1C - illegal
+    fn run_FA(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_implied(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_0B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.and_with_carry(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_1B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_2B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.and_with_carry(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_3B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_4B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.and_shift_right(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_5B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_6B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.and_rotate_right(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_7B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_8B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.and_x_immediate(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_9B(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.transfer_accumulator_and_x_to_stack_pointer(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_AB(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.load_accumulator_and_x_immediate(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_BB(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.and_stack_pointer(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
+
+    fn run_CB(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.and_x_subtract(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_DB(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_EB(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_immediate(cpu_bus);
+        self.subtract_with_carry(cpu_bus);
+        self.sleep_cycles += 1;
+    }
+
+    fn run_FB(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_0C(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_1C(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
 
     fn run_2C(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1678,7 +2290,12 @@ impl Cpu {
         self.sleep_cycles += 3;
     }
 
-    // 3C - illegal
+    fn run_3C(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
 
     fn run_4C(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1686,7 +2303,12 @@ impl Cpu {
         self.sleep_cycles += 2;
     }
 
-    // 5C - illegal
+    fn run_5C(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
 
     fn run_6C(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_indirect(cpu_bus);
@@ -1694,7 +2316,12 @@ impl Cpu {
         self.sleep_cycles += 4;
     }
 
-    // 7C - illegal
+    fn run_7C(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
 
     fn run_8C(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1702,7 +2329,11 @@ impl Cpu {
         self.sleep_cycles += 3;
     }
 
-    // 9C - illegal
+    fn run_9C(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.store_y_high(cpu_bus);
+        self.sleep_cycles += 4;
+    }
 
     fn run_AC(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1723,7 +2354,12 @@ impl Cpu {
         self.sleep_cycles += 3;
     }
 
-    // DC - illegal
+    fn run_DC(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
 
     fn run_EC(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1731,7 +2367,12 @@ impl Cpu {
         self.sleep_cycles += 3;
     }
 
-    // FC - illegal
+    fn run_FC(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.no_operation(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
 
     fn run_0D(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1890,7 +2531,11 @@ impl Cpu {
         self.sleep_cycles += 3;
     }
 
-    // 9E - illegal
+    fn run_9E(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.store_x_high(cpu_bus);
+        self.sleep_cycles += 4;
+    }
 
     fn run_AE(&mut self, cpu_bus: &mut CpuBus) {
         self.address_mode_absolute(cpu_bus);
@@ -1929,32 +2574,158 @@ impl Cpu {
         self.sleep_cycles += 6;
     }
 
-    // 0F - illegal
-    // 1F - illegal
-    // 2F - illegal
-    // 3F - illegal
-    // 4F - illegal
-    // 5F - illegal
-    // 6F - illegal
-    // 7F - illegal
-    // 8F - illegal
-    // 9F - illegal
-    // AF - illegal
-    // BF - illegal
-    // CF - illegal
-    // DF - illegal
-    // EF - illegal
-    // FF - illegal
-
-    fn run_xx(&mut self, cpu_bus: &mut CpuBus) {
-        eprintln!(
-            "CPU: Illegal opcode ({:#02}) at ({:04X}). Running noop.",
-            self.opcode,
-            self.program_counter.wrapping_sub(1)
-        );
-        self.address_mode_implied(cpu_bus);
-        self.no_operation(cpu_bus);
-        self.sleep_cycles += 1;
+    fn run_0F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_1F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.shift_left_or(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_2F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_3F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.rotate_left_and(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_4F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_5F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.shift_right_exclusive_or(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_6F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_7F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.rotate_right_add(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_8F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.store_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_9F(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.store_accumulator_and_x_high(cpu_bus);
+        self.sleep_cycles += 4;
+    }
+
+    fn run_AF(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.load_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 3;
+    }
+
+    fn run_BF(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_y_indexed(cpu_bus);
+        self.load_accumulator_and_x(cpu_bus);
+        self.sleep_cycles += 3;
+        self.sleep_cycles += self.crossed_page_boundary as u8;
+    }
+
+    fn run_CF(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_DF(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.decrement_compare(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+
+    fn run_EF(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 5;
+    }
+
+    fn run_FF(&mut self, cpu_bus: &mut CpuBus) {
+        self.address_mode_absolute_x_indexed(cpu_bus);
+        self.increment_subtract(cpu_bus);
+        self.sleep_cycles += 6;
+    }
+}
+
+impl SaveState for Cpu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.accumulator);
+        state.write_u8(self.x_index);
+        state.write_u8(self.y_index);
+        state.write_u16(self.program_counter);
+        state.write_u8(self.stack_pointer);
+        state.write_u8(self.status_register.0);
+        state.write_u8(self.opcode);
+        state.write_u8(self.sleep_cycles);
+        state.write_u16(self.operand_address);
+        state.write_bool(self.operand_accumulator);
+        state.write_bool(self.crossed_page_boundary);
+        state.write_u8(self.program_counter_offset as u8);
+        state.write_bool(self.nmi_requested);
+        state.write_bool(self.irq_requested);
+        state.write_u8(self.nmi_sleep_cycles);
+        state.write_u8(self.irq_sleep_cycles);
+        state.write_u8(self.dma_page);
+        state.write_u8(self.dma_byte);
+        state.write_u16(self.dma_cycles_left);
+        state.write_u16(self.dmc_dma_address);
+        state.write_u8(self.dmc_dma_cycles_left);
+        state.write_bool(self.cycle_odd);
+        state.write_bool(self.halted);
+        state.write_u64(self.cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.accumulator = state.read_u8()?;
+        self.x_index = state.read_u8()?;
+        self.y_index = state.read_u8()?;
+        self.program_counter = state.read_u16()?;
+        self.stack_pointer = state.read_u8()?;
+        self.status_register = StatusRegister(state.read_u8()?);
+        self.opcode = state.read_u8()?;
+        self.sleep_cycles = state.read_u8()?;
+        self.operand_address = state.read_u16()?;
+        self.operand_accumulator = state.read_bool()?;
+        self.crossed_page_boundary = state.read_bool()?;
+        self.program_counter_offset = u8_to_i8(state.read_u8()?);
+        self.nmi_requested = state.read_bool()?;
+        self.irq_requested = state.read_bool()?;
+        self.nmi_sleep_cycles = state.read_u8()?;
+        self.irq_sleep_cycles = state.read_u8()?;
+        self.dma_page = state.read_u8()?;
+        self.dma_byte = state.read_u8()?;
+        self.dma_cycles_left = state.read_u16()?;
+        self.dmc_dma_address = state.read_u16()?;
+        self.dmc_dma_cycles_left = state.read_u8()?;
+        self.cycle_odd = state.read_bool()?;
+        self.halted = state.read_bool()?;
+        self.cycle = state.read_u64()?;
+        Ok(())
     }
 }
 
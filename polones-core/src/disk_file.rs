@@ -0,0 +1,74 @@
+/// Size of one disk side in the .fds format, which stores the blocks of a side
+/// one after another, without gaps and checksums.
+pub const DISK_SIDE_SIZE: usize = 65500;
+
+/// Famicom Disk System game in the .fds format, with or without the 16-byte
+/// fwNES header.
+pub struct DiskFile {
+    pub name: String,
+    data: Vec<u8>,
+    /// Offset of the first side in data.
+    sides_start: usize,
+    side_count: usize,
+}
+
+impl std::fmt::Debug for DiskFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("DiskFile");
+        s.field("name", &self.name);
+        s.field("data", &("length", self.data.len()));
+        s.field("sides_start", &self.sides_start);
+        s.field("side_count", &self.side_count);
+        s.finish()
+    }
+}
+
+impl DiskFile {
+    pub fn read(name: String, data: Vec<u8>) -> Result<Self, &'static str> {
+        let (sides_start, side_count) = if data.starts_with(b"FDS\x1A") {
+            if data.len() < 16 {
+                return Err("Disk file: Header too short");
+            }
+            (16, data[4] as usize)
+        } else {
+            // Without the header, the file size tells the number of sides.
+            let side_count = data.len() / DISK_SIDE_SIZE;
+            if side_count * DISK_SIDE_SIZE != data.len() {
+                return Err("Disk file: Unexpected size");
+            }
+            (0, side_count)
+        };
+
+        if side_count == 0 {
+            return Err("Disk file: No disk sides");
+        }
+        if data.len() < sides_start + side_count * DISK_SIDE_SIZE {
+            return Err("Disk file: Disk sides missing");
+        }
+
+        let disk = Self {
+            name,
+            data,
+            sides_start,
+            side_count,
+        };
+
+        // Every side starts with the disk info block.
+        for side in 0..side_count {
+            if &disk.side(side)[0..15] != b"\x01*NINTENDO-HVC*" {
+                return Err("Disk file: Disk side without disk info block");
+            }
+        }
+
+        Ok(disk)
+    }
+
+    pub fn side_count(&self) -> usize {
+        self.side_count
+    }
+
+    pub fn side(&self, side: usize) -> &[u8] {
+        let start = self.sides_start + side * DISK_SIDE_SIZE;
+        &self.data[start..start + DISK_SIDE_SIZE]
+    }
+}
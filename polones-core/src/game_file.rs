@@ -1,3 +1,5 @@
+use crate::nes::Region;
+
 pub struct GameFile {
     pub name: String,
     data: Vec<u8>,
@@ -10,6 +12,8 @@ pub struct GameFile {
     pub mirroring_vertical: bool,
     pub battery_present: bool,
     pub four_screen_mode: bool,
+    /// TV system the game was made for, if the header says so.
+    pub region: Option<Region>,
 
     pub prg_ram_size: Option<usize>,
     pub prg_nvram_size: Option<usize>,
@@ -34,6 +38,7 @@ impl std::fmt::Debug for GameFile {
         s.field("mirroring_vertical", &self.mirroring_vertical);
         s.field("battery_present", &self.battery_present);
         s.field("four_screen_mode", &self.four_screen_mode);
+        s.field("region", &self.region);
         s.field("prg_ram_size", &self.prg_ram_size);
         s.field("prg_nvram_size", &self.prg_nvram_size);
         s.field("chr_ram_size", &self.chr_ram_size);
@@ -118,6 +123,7 @@ impl GameFile {
         let mut chr_ram_size: Option<usize> = None;
         let mut chr_nvram_size: Option<usize> = None;
         let mut submapper: Option<u8> = None;
+        let mut region: Option<Region> = None;
 
         // Now we have enough data to decide which format we're dealing with.
         // If it's not NES 2.0, we'll reinterpret byte 9.
@@ -140,7 +146,7 @@ impl GameFile {
             let chr_nvram_size_shift = data[11] >> 4;
             let chr_ram_size_shift = data[11] & 0b00001111;
 
-            let _cpu_ppu_timing_mode = data[12] & 0b00000011;
+            let cpu_ppu_timing_mode = data[12] & 0b00000011;
 
             if chr_nvram_size_shift > 0 && !battery_present {
                 return Err(());
@@ -162,6 +168,15 @@ impl GameFile {
                 None
             };
 
+            region = match cpu_ppu_timing_mode {
+                0 => Some(Region::Ntsc),
+                1 => Some(Region::Pal),
+                // multiple-region games run fine on NTSC consoles
+                2 => Some(Region::Ntsc),
+                3 => Some(Region::Dendy),
+                _ => unreachable!(),
+            };
+
             let _miscellaneous_roms_number = data[14] & 0b00000011;
             let _default_expansion_device = data[15] & 0b00111111;
 
@@ -268,6 +283,7 @@ impl GameFile {
             chr_nvram_size,
             chr_ram_size,
             four_screen_mode,
+            region,
             battery_present,
             mirroring_vertical,
         })
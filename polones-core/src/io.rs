@@ -1,5 +1,6 @@
 use crate::cpu::Cpu;
-use crate::nes::{Peripherals, PortState};
+use crate::nes::{GamepadState, Peripherals, PortState};
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
 pub struct Io {
     latch: u8,
@@ -95,3 +96,51 @@ impl Io {
         self.read_version_increment = 0;
     }
 }
+
+fn save_port_state(port: &PortState, state: &mut StateWriter) {
+    match port {
+        PortState::Unplugged => {
+            state.write_u8(0);
+            state.write_u8(0);
+        }
+        PortState::Gamepad(gamepad) => {
+            state.write_u8(1);
+            state.write_u8(gamepad.to_byte());
+        }
+    }
+}
+
+fn load_port_state(state: &mut StateReader) -> Result<PortState, &'static str> {
+    let kind = state.read_u8()?;
+    let byte = state.read_u8()?;
+    match kind {
+        0 => Ok(PortState::Unplugged),
+        1 => Ok(PortState::Gamepad(GamepadState::from_byte(byte))),
+        _ => Err("Save state: Invalid port state"),
+    }
+}
+
+impl SaveState for Io {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.latch);
+        save_port_state(&self.port_1, state);
+        save_port_state(&self.port_2, state);
+        save_port_state(&self.port_1_latched, state);
+        save_port_state(&self.port_2_latched, state);
+        state.write_u8(self.gamepad_shift_register_1);
+        state.write_u8(self.gamepad_shift_register_2);
+        state.write_u32(self.read_version_increment);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.latch = state.read_u8()?;
+        self.port_1 = load_port_state(state)?;
+        self.port_2 = load_port_state(state)?;
+        self.port_1_latched = load_port_state(state)?;
+        self.port_2_latched = load_port_state(state)?;
+        self.gamepad_shift_register_1 = state.read_u8()?;
+        self.gamepad_shift_register_2 = state.read_u8()?;
+        self.read_version_increment = state.read_u32()?;
+        Ok(())
+    }
+}
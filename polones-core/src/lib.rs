@@ -13,9 +13,13 @@ macro_rules! name_block {
 
 pub mod apu;
 pub mod cpu;
+pub mod disk_file;
 pub mod game_file;
 pub mod io;
 pub mod mapper;
 pub mod nes;
+pub mod nsf_file;
+pub mod palette;
 pub mod ppu;
 pub mod ram;
+pub mod save_state;
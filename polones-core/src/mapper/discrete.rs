@@ -0,0 +1,65 @@
+use crate::game_file::GameFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Returns the index of `address` in `memory` when the `bank_size` window
+/// containing the address shows `bank`. Banks past the end of memory wrap.
+pub fn bank_index(memory: &[u8], bank: usize, bank_size: usize, address: u16) -> usize {
+    (bank * bank_size + (address as usize & (bank_size - 1))) % memory.len()
+}
+
+/// Tells if the board has bus conflicts: on writes to ROM the ROM drives the
+/// data bus too, so the register gets the AND of the written byte and the ROM
+/// byte. Nes 2.0 submapper 1 means no bus conflicts and submapper 2 means bus
+/// conflicts, as on mappers 2, 3, 7 and 34, other games get `default`.
+pub fn bus_conflicts(game: &GameFile, default: bool) -> bool {
+    match game.submapper {
+        Some(1) => false,
+        Some(2) => true,
+        _ => default,
+    }
+}
+
+/// CHR memory of discrete logic boards: the game's CHR-ROM, or 8 KiB of
+/// unbanked CHR-RAM if the game has none.
+pub struct Chr {
+    ram: Option<Ram<{ 8 * 1024 }>>,
+}
+
+impl Chr {
+    pub fn new(game: &GameFile) -> Self {
+        Self {
+            ram: game.chr_rom().is_none().then(Ram::new),
+        }
+    }
+
+    /// Reads a pattern table address, with `bank` in its `bank_size` window.
+    pub fn read(&self, game: &GameFile, bank: usize, bank_size: usize, address: u16) -> u8 {
+        match (&self.ram, game.chr_rom()) {
+            (Some(ram), _) => ram.read(address as usize & 0x1FFF),
+            (None, Some(chr_rom)) => chr_rom[bank_index(chr_rom, bank, bank_size, address)],
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// Writes a pattern table address. Returns false if CHR is ROM.
+    pub fn write(&mut self, address: u16, byte: u8) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.write(address as usize & 0x1FFF, byte);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Chr {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)
+    }
+}
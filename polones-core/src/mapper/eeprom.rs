@@ -0,0 +1,238 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EepromKind {
+    /// X24C01, 128 bytes. Transfers start with the address, bytes are sent
+    /// least significant bit first.
+    X24C01,
+    /// 24C02, 256 bytes. Transfers start with the device address, bytes are
+    /// sent most significant bit first.
+    C24C02,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EepromMode {
+    Idle,
+    DeviceAddress,
+    Address,
+    Read,
+    Write,
+    /// The EEPROM acknowledges the byte it received.
+    SendAck,
+    /// The game acknowledges the byte it read.
+    WaitAck,
+}
+
+/// Serial EEPROM driven by the game over I²C, one SCL and SDA level change at
+/// a time.
+pub struct Eeprom {
+    kind: EepromKind,
+    data: Vec<u8>,
+    mode: EepromMode,
+    /// Mode after the acknowledge bit.
+    next_mode: EepromMode,
+    address: u8,
+    /// Byte being received or sent.
+    shift: u8,
+    bit: u8,
+    acknowledged: bool,
+    output: bool,
+    scl: bool,
+    sda: bool,
+}
+
+impl Eeprom {
+    pub fn new(kind: EepromKind) -> Self {
+        Self {
+            kind,
+            data: match kind {
+                EepromKind::X24C01 => vec![0; 128],
+                EepromKind::C24C02 => vec![0; 256],
+            },
+            mode: EepromMode::Idle,
+            next_mode: EepromMode::Idle,
+            address: 0,
+            shift: 0,
+            bit: 0,
+            acknowledged: false,
+            output: true,
+            scl: false,
+            sda: false,
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// SDA driven by the EEPROM. The line is pulled up when it's not driven.
+    pub fn output(&self) -> bool {
+        self.output
+    }
+
+    /// Mask of the bit transferred now.
+    fn bit_mask(&self) -> u8 {
+        match self.kind {
+            EepromKind::X24C01 => 1 << self.bit,
+            EepromKind::C24C02 => 0x80 >> self.bit,
+        }
+    }
+
+    fn start_byte(&mut self, mode: EepromMode) {
+        self.mode = mode;
+        self.bit = 0;
+        self.shift = 0;
+        self.output = true;
+        if mode == EepromMode::Read {
+            self.shift = self.data[self.address as usize % self.data.len()];
+        }
+    }
+
+    /// Sets the levels of the SCL and SDA lines.
+    pub fn write(&mut self, scl: bool, sda: bool) {
+        if self.scl && scl && self.sda && !sda {
+            // start condition
+            self.start_byte(match self.kind {
+                EepromKind::X24C01 => EepromMode::Address,
+                EepromKind::C24C02 => EepromMode::DeviceAddress,
+            });
+        } else if self.scl && scl && !self.sda && sda {
+            // stop condition
+            self.mode = EepromMode::Idle;
+            self.output = true;
+        } else if !self.scl && scl {
+            self.clock_rise(sda);
+        } else if self.scl && !scl {
+            self.clock_fall();
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    fn clock_rise(&mut self, sda: bool) {
+        match self.mode {
+            EepromMode::DeviceAddress | EepromMode::Address | EepromMode::Write => {
+                if self.bit < 8 {
+                    if sda {
+                        self.shift |= self.bit_mask();
+                    }
+                    self.bit += 1;
+                }
+            }
+            EepromMode::Read => {
+                if self.bit < 8 {
+                    self.output = self.shift & self.bit_mask() != 0;
+                    self.bit += 1;
+                }
+            }
+            EepromMode::SendAck => self.output = false,
+            EepromMode::WaitAck => self.acknowledged = !sda,
+            EepromMode::Idle => {}
+        }
+    }
+
+    fn clock_fall(&mut self) {
+        match self.mode {
+            EepromMode::DeviceAddress if self.bit == 8 => {
+                if self.shift & 0xF0 == 0xA0 {
+                    self.next_mode = match self.shift & 1 {
+                        0 => EepromMode::Address,
+                        _ => EepromMode::Read,
+                    };
+                    self.mode = EepromMode::SendAck;
+                } else {
+                    // another device on the bus
+                    self.mode = EepromMode::Idle;
+                }
+            }
+            EepromMode::Address if self.bit == 8 => {
+                match self.kind {
+                    EepromKind::X24C01 => {
+                        self.address = self.shift & 0x7F;
+                        self.next_mode = match self.shift & 0x80 {
+                            0 => EepromMode::Write,
+                            _ => EepromMode::Read,
+                        };
+                    }
+                    EepromKind::C24C02 => {
+                        self.address = self.shift;
+                        self.next_mode = EepromMode::Write;
+                    }
+                }
+                self.mode = EepromMode::SendAck;
+            }
+            EepromMode::Write if self.bit == 8 => {
+                let length = self.data.len();
+                self.data[self.address as usize % length] = self.shift;
+                self.address = self.address.wrapping_add(1);
+                self.next_mode = EepromMode::Write;
+                self.mode = EepromMode::SendAck;
+            }
+            EepromMode::Read if self.bit == 8 => {
+                self.address = self.address.wrapping_add(1);
+                self.acknowledged = false;
+                self.mode = EepromMode::WaitAck;
+            }
+            EepromMode::SendAck => self.start_byte(self.next_mode),
+            EepromMode::WaitAck if self.acknowledged => self.start_byte(EepromMode::Read),
+            EepromMode::WaitAck => {
+                self.mode = EepromMode::Idle;
+                self.output = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for EepromMode {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(*self as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        *self = match state.read_u8()? {
+            0 => EepromMode::Idle,
+            1 => EepromMode::DeviceAddress,
+            2 => EepromMode::Address,
+            3 => EepromMode::Read,
+            4 => EepromMode::Write,
+            5 => EepromMode::SendAck,
+            6 => EepromMode::WaitAck,
+            _ => return Err("Save state: Invalid EEPROM mode"),
+        };
+        Ok(())
+    }
+}
+
+impl SaveState for Eeprom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.data);
+        self.mode.save_state(state);
+        self.next_mode.save_state(state);
+        state.write_u8(self.address);
+        state.write_u8(self.shift);
+        state.write_u8(self.bit);
+        state.write_bool(self.acknowledged);
+        state.write_bool(self.output);
+        state.write_bool(self.scl);
+        state.write_bool(self.sda);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        state.read_bytes(&mut self.data)?;
+        self.mode.load_state(state)?;
+        self.next_mode.load_state(state)?;
+        self.address = state.read_u8()?;
+        self.shift = state.read_u8()?;
+        self.bit = state.read_u8()?.min(8);
+        self.acknowledged = state.read_bool()?;
+        self.output = state.read_bool()?;
+        self.scl = state.read_bool()?;
+        self.sda = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+//! Sound channel of the Famicom Disk System: a 64-step wavetable with a volume
+//! envelope, pitch-modulated by a second wavetable of frequency offsets.
+
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Mixer level of one step of channel output (0-63).
+const FDS_MIX_STEP: i32 = 360;
+
+/// Master volume 2/2, 2/3, 2/4 and 2/5, scaled so that the full output of
+/// gain 32 at wave level 63 stays 63.
+const MASTER_VOLUMES: [u32; 4] = [36, 24, 17, 14];
+
+/// Modulator counter changes for modulation table entries. Entry 4 resets the
+/// counter.
+const MODULATION_STEPS: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1];
+
+/// Volume and modulation gain unit.
+struct Envelope {
+    gain: u8,
+    speed: u8,
+    increase: bool,
+    disabled: bool,
+    timer: u32,
+}
+
+impl Envelope {
+    fn new() -> Self {
+        Self {
+            gain: 0,
+            speed: 0,
+            increase: false,
+            disabled: true,
+            timer: 0,
+        }
+    }
+
+    /// Writes $4080 or $4084.
+    fn write(&mut self, value: u8, master_speed: u8) {
+        self.disabled = value & 0x80 != 0;
+        self.increase = value & 0x40 != 0;
+        self.speed = value & 0x3F;
+        if self.disabled {
+            self.gain = value & 0x3F;
+        }
+        self.reset_timer(master_speed);
+    }
+
+    fn reset_timer(&mut self, master_speed: u8) {
+        self.timer = 8 * (self.speed as u32 + 1) * master_speed as u32;
+    }
+
+    /// Runs one CPU cycle. Returns true if the gain changed.
+    fn tick(&mut self, master_speed: u8) -> bool {
+        if self.disabled || master_speed == 0 {
+            return false;
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return false;
+        }
+        self.reset_timer(master_speed);
+        if self.increase && self.gain < 32 {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+        true
+    }
+}
+
+impl SaveState for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.gain);
+        state.write_u8(self.speed);
+        state.write_bool(self.increase);
+        state.write_bool(self.disabled);
+        state.write_u32(self.timer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.gain = state.read_u8()?;
+        self.speed = state.read_u8()?;
+        self.increase = state.read_bool()?;
+        self.disabled = state.read_bool()?;
+        self.timer = state.read_u32()?;
+        Ok(())
+    }
+}
+
+pub struct FdsAudio {
+    wave_table: [u8; 64],
+    wave_write_enabled: bool,
+    wave_halted: bool,
+    wave_frequency: u16,
+    wave_accumulator: u16,
+    wave_position: u8,
+    volume: Envelope,
+    envelopes_halted: bool,
+    master_volume: u8,
+    master_speed: u8,
+    modulation_table: [u8; 64],
+    modulation_halted: bool,
+    modulation_frequency: u16,
+    modulation_accumulator: u16,
+    modulation_position: u8,
+    /// 7-bit signed counter bending the wave frequency.
+    modulation_counter: i8,
+    modulation: Envelope,
+    /// Wave frequency offset computed from the counter and modulation gain.
+    pitch_offset: i32,
+    output: u8,
+}
+
+impl FdsAudio {
+    pub fn new() -> Self {
+        Self {
+            wave_table: [0; 64],
+            wave_write_enabled: false,
+            wave_halted: true,
+            wave_frequency: 0,
+            wave_accumulator: 0,
+            wave_position: 0,
+            volume: Envelope::new(),
+            envelopes_halted: false,
+            master_volume: 0,
+            master_speed: 0xE8,
+            modulation_table: [0; 64],
+            modulation_halted: true,
+            modulation_frequency: 0,
+            modulation_accumulator: 0,
+            modulation_position: 0,
+            modulation_counter: 0,
+            modulation: Envelope::new(),
+            pitch_offset: 0,
+            output: 0,
+        }
+    }
+
+    /// Reads $4040-$4097. Unused bits come from open bus.
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            0x4040..=0x407F => self.wave_table[address as usize & 0x3F] | 0x40,
+            0x4090 => self.volume.gain | 0x40,
+            0x4092 => self.modulation.gain | 0x40,
+            _ => 0x40,
+        }
+    }
+
+    /// Writes $4040-$4097.
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4040..=0x407F if self.wave_write_enabled => {
+                self.wave_table[address as usize & 0x3F] = value & 0x3F;
+            }
+            0x4080 => self.volume.write(value, self.master_speed),
+            0x4082 => self.wave_frequency = (self.wave_frequency & 0x0F00) | value as u16,
+            0x4083 => {
+                self.wave_frequency = (self.wave_frequency & 0x00FF) | (value as u16 & 0x0F) << 8;
+                self.wave_halted = value & 0x80 != 0;
+                self.envelopes_halted = value & 0x40 != 0;
+                if self.wave_halted {
+                    self.wave_position = 0;
+                    self.wave_accumulator = 0;
+                }
+                if self.envelopes_halted {
+                    self.volume.reset_timer(self.master_speed);
+                    self.modulation.reset_timer(self.master_speed);
+                }
+            }
+            0x4084 => {
+                self.modulation.write(value, self.master_speed);
+                self.update_pitch_offset();
+            }
+            0x4085 => {
+                self.set_modulation_counter(value);
+                self.update_pitch_offset();
+            }
+            0x4086 => {
+                self.modulation_frequency = (self.modulation_frequency & 0x0F00) | value as u16;
+            }
+            0x4087 => {
+                self.modulation_frequency =
+                    (self.modulation_frequency & 0x00FF) | (value as u16 & 0x0F) << 8;
+                self.modulation_halted = value & 0x80 != 0;
+                if self.modulation_halted {
+                    self.modulation_accumulator = 0;
+                }
+            }
+            // each write fills two entries
+            0x4088 if self.modulation_halted => {
+                let position = self.modulation_position as usize;
+                self.modulation_table[position] = value & 0b111;
+                self.modulation_table[(position + 1) & 0x3F] = value & 0b111;
+                self.modulation_position = (self.modulation_position + 2) & 0x3F;
+            }
+            0x4089 => {
+                self.wave_write_enabled = value & 0x80 != 0;
+                self.master_volume = value & 0b11;
+            }
+            0x408A => self.master_speed = value,
+            _ => {}
+        }
+    }
+
+    /// Sign-extends a 7-bit counter value.
+    fn set_modulation_counter(&mut self, value: u8) {
+        self.modulation_counter = ((value << 1) as i8) >> 1;
+    }
+
+    /// Computes the wave frequency offset the way the hardware does, including
+    /// its rounding.
+    fn update_pitch_offset(&mut self) {
+        let mut offset = self.modulation_counter as i32 * self.modulation.gain as i32;
+        let remainder = offset & 0x0F;
+        offset >>= 4;
+        if remainder > 0 && offset & 0x80 == 0 {
+            offset += if self.modulation_counter < 0 { -1 } else { 2 };
+        }
+        if offset >= 192 {
+            offset -= 256;
+        } else if offset < -64 {
+            offset += 256;
+        }
+        offset *= self.wave_frequency as i32;
+        let remainder = offset & 0x3F;
+        offset >>= 6;
+        if remainder >= 32 {
+            offset += 1;
+        }
+        self.pitch_offset = offset;
+    }
+
+    /// Runs one CPU cycle.
+    pub fn tick(&mut self) {
+        if !self.wave_halted && !self.envelopes_halted {
+            self.volume.tick(self.master_speed);
+            if self.modulation.tick(self.master_speed) {
+                self.update_pitch_offset();
+            }
+        }
+
+        if !self.modulation_halted && self.modulation_frequency > 0 {
+            let (accumulator, overflow) = self
+                .modulation_accumulator
+                .overflowing_add(self.modulation_frequency);
+            self.modulation_accumulator = accumulator;
+            if overflow {
+                let entry = self.modulation_table[self.modulation_position as usize] & 0b111;
+                let counter = match entry {
+                    4 => 0,
+                    _ => self.modulation_counter as i32 + MODULATION_STEPS[entry as usize] as i32,
+                };
+                self.set_modulation_counter(counter as u8);
+                self.modulation_position = (self.modulation_position + 1) & 0x3F;
+                self.update_pitch_offset();
+            }
+        }
+
+        let frequency = self.wave_frequency as i32 + self.pitch_offset;
+        if !self.wave_halted && !self.wave_write_enabled && frequency > 0 {
+            let (accumulator, overflow) = self.wave_accumulator.overflowing_add(frequency as u16);
+            self.wave_accumulator = accumulator;
+            if overflow {
+                self.wave_position = (self.wave_position + 1) & 0x3F;
+            }
+        }
+
+        // the output holds its level while the wavetable is written
+        if !self.wave_write_enabled {
+            let level =
+                self.volume.gain.min(32) as u32 * MASTER_VOLUMES[self.master_volume as usize];
+            let sample = self.wave_table[self.wave_position as usize] as u32;
+            self.output = (sample * level / 1152) as u8;
+        }
+    }
+
+    pub fn output(&self) -> i32 {
+        self.output as i32 * FDS_MIX_STEP
+    }
+}
+
+impl SaveState for FdsAudio {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.wave_table);
+        state.write_bool(self.wave_write_enabled);
+        state.write_bool(self.wave_halted);
+        state.write_u16(self.wave_frequency);
+        state.write_u16(self.wave_accumulator);
+        state.write_u8(self.wave_position);
+        self.volume.save_state(state);
+        state.write_bool(self.envelopes_halted);
+        state.write_u8(self.master_volume);
+        state.write_u8(self.master_speed);
+        state.write_bytes(&self.modulation_table);
+        state.write_bool(self.modulation_halted);
+        state.write_u16(self.modulation_frequency);
+        state.write_u16(self.modulation_accumulator);
+        state.write_u8(self.modulation_position);
+        state.write_u8(self.modulation_counter as u8);
+        self.modulation.save_state(state);
+        state.write_u8(self.output);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        state.read_bytes(&mut self.wave_table)?;
+        self.wave_write_enabled = state.read_bool()?;
+        self.wave_halted = state.read_bool()?;
+        self.wave_frequency = state.read_u16()? & 0x0FFF;
+        self.wave_accumulator = state.read_u16()?;
+        self.wave_position = state.read_u8()? & 0x3F;
+        self.volume.load_state(state)?;
+        self.envelopes_halted = state.read_bool()?;
+        self.master_volume = state.read_u8()? & 0b11;
+        self.master_speed = state.read_u8()?;
+        state.read_bytes(&mut self.modulation_table)?;
+        self.modulation_halted = state.read_bool()?;
+        self.modulation_frequency = state.read_u16()? & 0x0FFF;
+        self.modulation_accumulator = state.read_u16()?;
+        self.modulation_position = state.read_u8()? & 0x3F;
+        self.set_modulation_counter(state.read_u8()?);
+        self.modulation.load_state(state)?;
+        self.output = state.read_u8()?;
+        // the pitch offset is derived from the loaded registers
+        self.update_pitch_offset();
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Steps of the command protocol. Commands start with the unlock sequence:
+/// $AA written to $5555 and $55 written to $2AAA.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FlashState {
+    Read,
+    Unlock1,
+    Unlock2,
+    Program,
+    Erase1,
+    Erase2,
+    Erase3,
+}
+
+/// SST39SF040 flash memory of self-flashable homebrew boards. Games save data
+/// by programming bytes and erasing 4 KiB sectors with command sequences.
+/// Programming and erasing finish immediately.
+pub struct Flash {
+    state: FlashState,
+    /// Reads return the manufacturer and device ID instead of memory.
+    software_id: bool,
+}
+
+impl Flash {
+    pub fn new() -> Self {
+        Self {
+            state: FlashState::Read,
+            software_id: false,
+        }
+    }
+
+    /// Reads the byte at `address` of the chip.
+    pub fn read(&self, memory: &[u8], address: usize) -> u8 {
+        match (self.software_id, address & 1) {
+            (true, 0) => 0xBF,
+            (true, _) => 0xB7,
+            (false, _) => memory[address],
+        }
+    }
+
+    /// Writes to `address` of the chip. Only A0-A14 are decoded for commands.
+    pub fn write(&mut self, memory: &mut [u8], address: usize, value: u8) {
+        let command_address = address & 0x7FFF;
+        self.state = match (self.state, command_address, value) {
+            (FlashState::Program, _, _) => {
+                // programming can only clear bits
+                memory[address] &= value;
+                FlashState::Read
+            }
+            (_, _, 0xF0) => {
+                self.software_id = false;
+                FlashState::Read
+            }
+            (FlashState::Read, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Unlock2, 0x5555, 0x80) => FlashState::Erase1,
+            (FlashState::Unlock2, 0x5555, 0x90) => {
+                self.software_id = true;
+                FlashState::Read
+            }
+            (FlashState::Erase1, 0x5555, 0xAA) => FlashState::Erase2,
+            (FlashState::Erase2, 0x2AAA, 0x55) => FlashState::Erase3,
+            (FlashState::Erase3, 0x5555, 0x10) => {
+                memory.fill(0xFF);
+                FlashState::Read
+            }
+            (FlashState::Erase3, _, 0x30) => {
+                let sector = address & !0x0FFF;
+                memory[sector..sector + 0x1000].fill(0xFF);
+                FlashState::Read
+            }
+            _ => FlashState::Read,
+        };
+    }
+}
+
+impl SaveState for Flash {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.state as u8);
+        state.write_bool(self.software_id);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.state = match state.read_u8()? {
+            0 => FlashState::Read,
+            1 => FlashState::Unlock1,
+            2 => FlashState::Unlock2,
+            3 => FlashState::Program,
+            4 => FlashState::Erase1,
+            5 => FlashState::Erase2,
+            6 => FlashState::Erase3,
+            _ => return Err("Save state: Invalid flash state"),
+        };
+        self.software_id = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 use crate::cpu::Cpu;
 use crate::game_file::GameFile;
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::Mapper;
+use super::{Mapper, Mirroring, NametableSource};
 
 pub struct Mapper000 {
     game: GameFile,
@@ -17,15 +18,13 @@ impl Mapper for Mapper000 {
         if game.chr_rom().is_none() || game.chr_rom().unwrap().len() != 8 * 1024 {
             return Err("Mapper 000: Unexpected chr rom size");
         }
-        if game.prg_ram_size != None
-            && game.prg_ram_size != Some(2 * 1024)
-            && game.prg_ram_size != Some(4 * 1024)
-        {
+        let ram_size = game.prg_ram_size.or(game.prg_nvram_size);
+        if ram_size != None && ram_size != Some(2 * 1024) && ram_size != Some(4 * 1024) {
             return Err("Mapper 000: Unexpected prg ram size");
         }
 
         Ok(Self {
-            ram: game.prg_ram_size.map(|size| (size, Ram::new())),
+            ram: ram_size.map(|size| (size, Ram::new())),
             game,
         })
     }
@@ -87,19 +86,54 @@ impl Mapper for Mapper000 {
         }
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
-        // address bits 11-8
-        // address   vertical   horizontal
-        //    00XX       00XX         00XX
-        //    01XX       01XX         00XX
-        //    10XX       00XX         01XX
-        //    10XX       01XX         01XX
-        if self.game.mirroring_vertical {
-            address & 0b0000_0111_1111_1111
-        } else {
-            (address & 0b0000_0011_1111_1111) | ((address & 0b0000_1000_0000_0000) >> 1)
-        }
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
     }
 
     fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some((size, ram)) if self.game.battery_present => Some(&ram.as_slice()[..*size]),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some((size, ram)) if self.game.battery_present => {
+                Some(&mut ram.as_mut_slice()[..*size])
+            }
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some((size, ram)) => {
+                for (i, byte) in trainer.iter().enumerate() {
+                    ram.write((0x1000 + i) & (*size - 1), *byte);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper000 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.ram.is_some());
+        if let Some((_, ram)) = &self.ram {
+            ram.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        match (&mut self.ram, state.read_bool()?) {
+            (Some((_, ram)), true) => ram.load_state(state),
+            (None, false) => Ok(()),
+            _ => Err("Save state: Component presence mismatch"),
+        }
+    }
 }
@@ -1,10 +1,17 @@
 use crate::cpu::Cpu;
-use crate::game_file::GameFile;
+use crate::game_file::{FileFormat, GameFile};
 use crate::mapper::DebugValue;
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::Mapper;
+use super::{Mapper, Mirroring, NametableSource};
 
+/// MMC1 (mapper 1) and MMC1A (mapper 155). Boards with 8 KiB of CHR use the
+/// upper CHR bank bits for other purposes: SUROM and SXROM select the 256 KiB
+/// half of 512 KiB PRG-ROM with bit 4, SOROM selects one of two 8 KiB RAM banks
+/// with bit 3, SXROM one of four with bits 2-3. RAM banks work only with Nes 2.0
+/// headers; iNES can't tell SOROM and SXROM from 8 KiB boards like SNROM, so
+/// they get a single RAM bank and only its contents are saved.
 pub struct Mapper001 {
     game: GameFile,
     control: u8,
@@ -13,49 +20,113 @@ pub struct Mapper001 {
     chr_bank_0: u8,
     chr_bank_1: u8,
     prg_bank: u8,
-    ram: Ram<{ 32 * 1024 }>, // TODO make ram optional
+    ram: Option<Ram<{ 32 * 1024 }>>,
+    ram_size: usize,
     chr_ram: Ram<{ 8 * 1024 }>,
+    /// MMC1A can't disable RAM.
+    mmc1a: bool,
+    /// SEROM, SHROM and SH1ROM wire 32 KiB of PRG-ROM directly.
+    fixed_prg: bool,
+    /// PPU A12 of the last CHR access, selects the CHR bank register in 4 KiB mode.
+    chr_a12: bool,
+    /// The serial port ignores writes on the cycle after a write.
+    written_this_cycle: bool,
+}
+
+impl Mapper001 {
+    fn battery_ram_range(&self) -> Option<std::ops::Range<usize>> {
+        // iNES does not store the size of battery-backed RAM. Most boards have 8K.
+        // With both kinds of RAM, like on SOROM, the battery-backed bank is last.
+        let size = self.game.prg_nvram_size.unwrap_or(8 * 1024);
+        let size = size.min(self.ram_size);
+        (self.game.battery_present && self.ram.is_some())
+            .then(|| self.ram_size - size..self.ram_size)
+    }
+
+    /// CHR bank register currently in use.
+    fn chr_bank_register(&self) -> u8 {
+        if self.control & 0b10000 > 0 && self.chr_a12 {
+            self.chr_bank_1
+        } else {
+            self.chr_bank_0
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.mmc1a || self.prg_bank & 0b10000 == 0
+    }
+
+    fn ram_index(&self, address: u16) -> usize {
+        let bank = match self.ram_size {
+            0x8000 => (self.chr_bank_register() as usize >> 2) & 0b11,
+            0x4000 => (self.chr_bank_register() as usize >> 3) & 0b1,
+            _ => 0,
+        };
+        bank << 13 | (address as usize & 0x1FFF)
+    }
+
+    fn prg_index(&self, address: u16) -> usize {
+        let prg_rom_len = self.game.prg_rom().len();
+        let outer_bank = (self.chr_bank_register() as usize & 0b10000) << 14;
+        let (outer_bank, inner_len) = match prg_rom_len > 256 * 1024 {
+            true => (outer_bank, 256 * 1024),
+            false => (0, prg_rom_len),
+        };
+        let high = address >= 0xC000;
+        let bank = match ((self.control >> 2) & 0b11, high) {
+            _ if self.fixed_prg => high as usize,
+            (0 | 1, _) => (self.prg_bank as usize & 0b1110) | high as usize,
+            (2, false) => 0,
+            (2, true) | (3, false) => self.prg_bank as usize & 0b1111,
+            (_, _) => (inner_len >> 14) - 1,
+        };
+        outer_bank | ((bank << 14 | (address as usize & 0x3FFF)) & (inner_len - 1))
+    }
 }
 
 impl Mapper for Mapper001 {
     fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        // Only Nes 2.0 can tell us the ram size. For other formats assume 8K.
+        let ram_size = match game.format {
+            FileFormat::Nes20 => {
+                (game.prg_ram_size.unwrap_or(0) + game.prg_nvram_size.unwrap_or(0)).min(32 * 1024)
+            }
+            _ => 8 * 1024,
+        };
+
         Ok(Self {
-            game,
             control: 0b01100,
             load_register: 0,
             load_register_bits: 0,
             chr_bank_0: 0,
             chr_bank_1: 0,
             prg_bank: 0,
-            ram: Ram::new(),
+            ram: (ram_size > 0).then(Ram::new),
+            ram_size,
             chr_ram: Ram::new(),
+            mmc1a: game.mapper == 155,
+            fixed_prg: game.mapper == 1 && game.submapper == Some(5),
+            chr_a12: false,
+            written_this_cycle: false,
+            game,
         })
     }
 
     fn cpu_address_mapped(&self, address: u16) -> bool {
-        (0x6000..=0xFFFF).contains(&address)
+        match address {
+            0x6000..=0x7FFF => self.ram.is_some(),
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
     }
 
     fn cpu_read(&mut self, address: u16) -> u8 {
         match address {
-            0x6000..=0x7FFF => self.ram.read(address as usize - 0x6000),
-            0x8000..=0xBFFF => match (self.control >> 2) & 0b11 {
-                0 | 1 => self.game.prg_rom()
-                    [((self.prg_bank as usize & 0b11110) << 14) | (address as usize & 0x3FFF)],
-                2 => self.game.prg_rom()[address as usize & 0x3FFF],
-                3 => self.game.prg_rom()
-                    [((self.prg_bank as usize) << 14) | (address as usize & 0x3FFF)],
-                _ => unreachable!(),
-            },
-            0xC000..=0xFFFF => match (self.control >> 2) & 0b11 {
-                0 | 1 => self.game.prg_rom()
-                    [((self.prg_bank as usize | 0b00001) << 14) | (address as usize & 0x3FFF)],
-                2 => self.game.prg_rom()
-                    [((self.prg_bank as usize) << 14) | (address as usize & 0x3FFF)],
-                3 => self.game.prg_rom()
-                    [self.game.prg_rom().len() - 0x4000 + (address as usize & 0x3FFF)],
-                _ => unreachable!(),
+            0x6000..=0x7FFF => match &self.ram {
+                Some(ram) if self.ram_enabled() => ram.read(self.ram_index(address)),
+                _ => 0,
             },
+            0x8000..=0xFFFF => self.game.prg_rom()[self.prg_index(address)],
             _ => panic!("Mapper 001: CPU read from {:04X} out of bounds.", address),
         }
     }
@@ -63,9 +134,18 @@ impl Mapper for Mapper001 {
     fn cpu_write(&mut self, address: u16, byte: u8) {
         match address {
             0x6000..=0x7FFF => {
-                self.ram.write(address as usize - 0x6000, byte);
+                let (enabled, index) = (self.ram_enabled(), self.ram_index(address));
+                if let (Some(ram), true) = (&mut self.ram, enabled) {
+                    ram.write(index, byte);
+                }
             }
             0x8000..=0xFFFF => {
+                // read-modify-write instructions write twice in a row, only
+                // the first write counts
+                if std::mem::replace(&mut self.written_this_cycle, true) {
+                    return;
+                }
+
                 if byte & 0b10000000 > 0 {
                     self.load_register = 0;
                     self.load_register_bits = 0;
@@ -96,21 +176,25 @@ impl Mapper for Mapper001 {
     }
 
     fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr_a12 = address & 0x1000 > 0;
         match address {
-            0x0000..=0x1FFF if self.game.chr_rom().is_none() => {
-                self.chr_ram.read(address as usize)
-            }
+            0x0000..=0x1FFF if self.game.chr_rom().is_none() => self.chr_ram.read(address as usize),
             0x0000..=0x0FFF => {
-                self.game.chr_rom().unwrap()[self.lower_chr_bank() | (address as usize & 0x0FFF)]
+                let chr_rom = self.game.chr_rom().unwrap();
+                let index = self.lower_chr_bank() | (address as usize & 0x0FFF);
+                chr_rom[index & (chr_rom.len() - 1)]
             }
             0x1000..=0x1FFF => {
-                self.game.chr_rom().unwrap()[self.upper_chr_bank() | (address as usize & 0x0FFF)]
+                let chr_rom = self.game.chr_rom().unwrap();
+                let index = self.upper_chr_bank() | (address as usize & 0x0FFF);
+                chr_rom[index & (chr_rom.len() - 1)]
             }
             _ => panic!("Mapper 001: PPU read of {:04X} out of bounds.", address),
         }
     }
 
     fn ppu_write(&mut self, address: u16, byte: u8) {
+        self.chr_a12 = address & 0x1000 > 0;
         match address {
             0x0000..=0x1FFF if self.game.chr_rom().is_none() => {
                 self.chr_ram.write(address as usize, byte);
@@ -122,29 +206,56 @@ impl Mapper for Mapper001 {
         }
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
-        match self.control & 0b11 {
-            0 => address & 0b0000_0011_1111_1111,
-            1 => (address & 0b0000_0011_1111_1111) | 0b0000_0100_0000_0000,
-            2 => address & 0b0000_0111_1111_1111,
-            3 => (address & 0b0000_0011_1111_1111) | ((address & 0b0000_1000_0000_0000) >> 1),
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let mirroring = match self.control & 0b11 {
+            0 => Mirroring::SingleScreen(0),
+            1 => Mirroring::SingleScreen(1),
+            2 => Mirroring::Vertical,
+            3 => Mirroring::Horizontal,
             _ => unreachable!(),
-        }
+        };
+        mirroring.nametable_source(address)
     }
 
-    fn tick(&mut self, _cpu: &mut Cpu) {}
+    fn tick(&mut self, _cpu: &mut Cpu) {
+        self.written_this_cycle = false;
+    }
 
     fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
         vec![
-            ("mapper", DebugValue::Dec(1)),
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
             ("control", DebugValue::U8Hex(self.control)),
             ("chr_bank_0", DebugValue::U8Hex(self.chr_bank_0)),
             ("chr_bank_1", DebugValue::U8Hex(self.chr_bank_1)),
             ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            ("ram_size", DebugValue::Dec(self.ram_size as u64)),
             ("load_register", DebugValue::U8Hex(self.load_register)),
-            ("load_register_bits", DebugValue::Dec(self.load_register_bits as u64)),
+            (
+                "load_register_bits",
+                DebugValue::Dec(self.load_register_bits as u64),
+            ),
         ]
     }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        let range = self.battery_ram_range()?;
+        Some(&self.ram.as_ref()?.as_slice()[range])
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        let range = self.battery_ram_range()?;
+        Some(&mut self.ram.as_mut()?.as_mut_slice()[range])
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 impl Mapper001 {
@@ -164,3 +275,32 @@ impl Mapper001 {
         }
     }
 }
+
+impl SaveState for Mapper001 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.control);
+        state.write_u8(self.load_register);
+        state.write_u8(self.load_register_bits);
+        state.write_u8(self.chr_bank_0);
+        state.write_u8(self.chr_bank_1);
+        state.write_u8(self.prg_bank);
+        self.ram.save_state(state);
+        self.chr_ram.save_state(state);
+        state.write_bool(self.chr_a12);
+        state.write_bool(self.written_this_cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.control = state.read_u8()?;
+        self.load_register = state.read_u8()?;
+        self.load_register_bits = state.read_u8()?;
+        self.chr_bank_0 = state.read_u8()?;
+        self.chr_bank_1 = state.read_u8()?;
+        self.prg_bank = state.read_u8()?;
+        self.ram.load_state(state)?;
+        self.chr_ram.load_state(state)?;
+        self.chr_a12 = state.read_bool()?;
+        self.written_this_cycle = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,13 +1,16 @@
 use crate::cpu::Cpu;
 use crate::game_file::GameFile;
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::Mapper;
+use super::discrete::bus_conflicts;
+use super::{Mapper, Mirroring, NametableSource};
 
 pub struct Mapper002 {
     game: GameFile,
     prg_rom_bank: u8,
     chr_ram: Option<Ram<{ 8 * 1024 }>>,
+    bus_conflicts: bool,
 }
 
 impl Mapper for Mapper002 {
@@ -23,6 +26,7 @@ impl Mapper for Mapper002 {
             } else {
                 Some(Ram::new())
             },
+            bus_conflicts: bus_conflicts(&game, true),
             game,
         })
     }
@@ -32,7 +36,6 @@ impl Mapper for Mapper002 {
     }
 
     fn cpu_read(&mut self, address: u16) -> u8 {
-        // TODO implement bus conflicts based on submapper and format
         match address {
             0x8000..=0xBFFF => self.game.prg_rom()[((self.prg_rom_bank as usize) << 14)
                 & (self.game.prg_rom().len() - 1)
@@ -45,6 +48,9 @@ impl Mapper for Mapper002 {
 
     fn cpu_write(&mut self, address: u16, byte: u8) {
         match address {
+            0x8000..=0xFFFF if self.bus_conflicts => {
+                self.prg_rom_bank = byte & self.cpu_read(address);
+            }
             0x8000..=0xFFFF => {
                 self.prg_rom_bank = byte;
             }
@@ -78,13 +84,21 @@ impl Mapper for Mapper002 {
         }
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
-        if self.game.mirroring_vertical {
-            address & 0b0000_0111_1111_1111
-        } else {
-            (address & 0b0000_0011_1111_1111) | ((address & 0b0000_1000_0000_0000) >> 1)
-        }
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
     }
 
     fn tick(&mut self, _cpu: &mut Cpu) {}
 }
+
+impl SaveState for Mapper002 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.prg_rom_bank);
+        self.chr_ram.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.prg_rom_bank = state.read_u8()?;
+        self.chr_ram.load_state(state)
+    }
+}
@@ -1,12 +1,15 @@
 use crate::cpu::Cpu;
 use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::Mapper;
+use super::discrete::bus_conflicts;
+use super::{Mapper, Mirroring, NametableSource};
 
 // TODO add audio support
 pub struct Mapper003 {
     game: GameFile,
     chr_rom_bank: u8,
+    bus_conflicts: bool,
 }
 
 impl Mapper for Mapper003 {
@@ -22,8 +25,9 @@ impl Mapper for Mapper003 {
         }
 
         Ok(Self {
-            game,
             chr_rom_bank: 0,
+            bus_conflicts: bus_conflicts(&game, true),
+            game,
         })
     }
 
@@ -32,7 +36,6 @@ impl Mapper for Mapper003 {
     }
 
     fn cpu_read(&mut self, address: u16) -> u8 {
-        // TODO implement bus conflicts based on submapper and format
         match address {
             0x8000..=0xFFFF => {
                 self.game.prg_rom()[(address - 0x8000) as usize & (self.game.prg_rom().len() - 1)]
@@ -43,6 +46,9 @@ impl Mapper for Mapper003 {
 
     fn cpu_write(&mut self, address: u16, byte: u8) {
         match address {
+            0x8000..=0xFFFF if self.bus_conflicts => {
+                self.chr_rom_bank = byte & self.cpu_read(address);
+            }
             0x8000..=0xFFFF => {
                 self.chr_rom_bank = byte;
             }
@@ -69,13 +75,20 @@ impl Mapper for Mapper003 {
         eprintln!("Mapper 003: PPU write to {:04X} out of bounds.", address);
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
-        if self.game.mirroring_vertical {
-            address & 0b0000_0111_1111_1111
-        } else {
-            (address & 0b0000_0011_1111_1111) | ((address & 0b0000_1000_0000_0000) >> 1)
-        }
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
     }
 
     fn tick(&mut self, _cpu: &mut Cpu) {}
 }
+
+impl SaveState for Mapper003 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.chr_rom_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr_rom_bank = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -1,12 +1,32 @@
 use crate::cpu::Cpu;
 use crate::game_file::{FileFormat, GameFile};
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::{DebugValue, Mapper};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// MMC3 boards with differences the mapper has to know about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Board {
+    Mmc3,
+    /// 1 KiB of RAM at $7000 inside the mapper, with halves protected separately.
+    Mmc6,
+    /// CHR bank bit 7 selects the CIRAM page of nametables (mapper 118).
+    TxSrom,
+    /// CHR bank bit 6 selects CHR-RAM instead of CHR-ROM (mapper 119).
+    Tqrom,
+}
 
 pub struct Mapper004 {
     game: GameFile,
+    board: Board,
+    /// MMC3A only raises an IRQ on reaching zero by decrementing or reload.
+    alternate_irq: bool,
     ram: Option<Ram<{ 8 * 1024 }>>,
+    mmc6_ram: Option<Ram<1024>>,
+    mmc6_ram_enable: bool,
+    mmc6_ram_control: u8,
+    chr_ram: Option<Ram<{ 8 * 1024 }>>,
     bank_to_update: u8,
     prg_rom_bank_mode: bool,
     chr_a12_inversion: bool,
@@ -35,13 +55,16 @@ impl Mapper004 {
         let a12_1 = address & 0b00010000_00000000 > 0;
 
         if a12_1 && self.cycle_count.abs_diff(self.cycle_count_a12_1) > 4 {
+            let old_counter = self.irq_counter;
+            let reload = self.irq_reload;
             if self.irq_counter == 0 || self.irq_reload {
                 self.irq_counter = self.irq_latch;
                 self.irq_reload = false;
             } else {
                 self.irq_counter = self.irq_counter.saturating_sub(1);
             }
-            if self.irq_counter == 0 && self.irq_enabled {
+            let triggered = !self.alternate_irq || old_counter != 0 || reload;
+            if self.irq_counter == 0 && self.irq_enabled && triggered {
                 self.irq_requested = true;
             }
         }
@@ -50,15 +73,68 @@ impl Mapper004 {
             self.cycle_count_a12_1 = self.cycle_count;
         }
     }
+
+    /// Returns the 1 KiB CHR bank register value for a pattern table address.
+    fn chr_bank(&self, address: u16) -> u8 {
+        let slot = (address >> 10) as usize & 0b111;
+        let slot = if self.chr_a12_inversion {
+            slot ^ 0b100
+        } else {
+            slot
+        };
+        match slot {
+            0 => self.r0,
+            1 => self.r0 | 1,
+            2 => self.r1,
+            3 => self.r1 | 1,
+            slot => [self.r2, self.r3, self.r4, self.r5][slot - 4],
+        }
+    }
+
+    fn chr_ram_selected(&self, bank: u8) -> bool {
+        match self.board {
+            Board::Tqrom => bank & 0b0100_0000 > 0,
+            _ => self.chr_ram.is_some(),
+        }
+    }
+
+    /// Tells if the CPU can read ($7000-$71FF, $7200-$73FF) half of MMC6 RAM.
+    fn mmc6_readable(&self, half: u16) -> bool {
+        self.mmc6_ram_enable && self.mmc6_ram_control & (0b0010_0000 << (half * 2)) > 0
+    }
+
+    fn mmc6_writable(&self, half: u16) -> bool {
+        self.mmc6_readable(half) && self.mmc6_ram_control & (0b0001_0000 << (half * 2)) > 0
+    }
 }
 
 impl Mapper for Mapper004 {
     fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        let board = match (game.mapper, game.submapper) {
+            (4, Some(1)) => Board::Mmc6,
+            (118, _) => Board::TxSrom,
+            (119, _) => Board::Tqrom,
+            _ => Board::Mmc3,
+        };
+
+        if board == Board::Tqrom && game.chr_rom().is_none() {
+            return Err("Mapper 004: Missing chr rom");
+        }
+
         Ok(Self {
+            board,
+            alternate_irq: game.mapper == 4 && game.submapper == Some(4),
             // Only Nes 2.0 can tell us if ram is present. For other formats assume present.
-            ram: (game.format == FileFormat::Nes20 && game.prg_ram_size.is_some()
-                || game.format != FileFormat::Nes20)
+            // MMC6 has its own RAM instead.
+            ram: (board != Board::Mmc6
+                && (game.format == FileFormat::Nes20
+                    && (game.prg_ram_size.is_some() || game.prg_nvram_size.is_some())
+                    || game.format != FileFormat::Nes20))
                 .then(|| Ram::new()),
+            mmc6_ram: (board == Board::Mmc6).then(Ram::new),
+            mmc6_ram_enable: false,
+            mmc6_ram_control: 0,
+            chr_ram: (board == Board::Tqrom || game.chr_rom().is_none()).then(Ram::new),
             bank_to_update: 0,
             prg_rom_bank_mode: false,
             chr_a12_inversion: false,
@@ -90,6 +166,7 @@ impl Mapper for Mapper004 {
     fn cpu_address_mapped(&self, address: u16) -> bool {
         match address {
             0x6000..=0x7FFF if self.ram.is_some() => true,
+            0x7000..=0x7FFF if self.mmc6_ram.is_some() => true,
             0x8000..=0xFFFF => true,
             _ => false,
         }
@@ -98,6 +175,13 @@ impl Mapper for Mapper004 {
     fn cpu_read(&mut self, address: u16) -> u8 {
         let offset = (address & 0x1FFF) as usize;
         match address {
+            0x7000..=0x7FFF if self.mmc6_ram.is_some() => {
+                let half = (address >> 9) & 1;
+                match &self.mmc6_ram {
+                    Some(ram) if self.mmc6_readable(half) => ram.read(address as usize),
+                    _ => 0,
+                }
+            }
             0x6000..=0x7FFF => match &self.ram {
                 Some(ram) if self.ram_enable => ram.read((address - 0x6000) as usize),
                 _ => 0,
@@ -137,6 +221,13 @@ impl Mapper for Mapper004 {
 
     fn cpu_write(&mut self, address: u16, byte: u8) {
         match address {
+            0x7000..=0x7FFF if self.mmc6_ram.is_some() => {
+                let writable = self.mmc6_writable((address >> 9) & 1);
+                match &mut self.mmc6_ram {
+                    Some(ram) if writable => ram.write(address as usize, byte),
+                    _ => {}
+                }
+            }
             0x6000..=0x7FFF => match &mut self.ram {
                 Some(ram) if self.ram_enable && !self.ram_write_protection => {
                     ram.write((address - 0x6000) as usize, byte);
@@ -147,6 +238,9 @@ impl Mapper for Mapper004 {
                 self.bank_to_update = byte & 0b111;
                 self.prg_rom_bank_mode = byte & 0b0100_0000 > 0;
                 self.chr_a12_inversion = byte & 0b1000_0000 > 0;
+                if self.board == Board::Mmc6 {
+                    self.mmc6_ram_enable = byte & 0b0010_0000 > 0;
+                }
             }
             0x8000..=0x9FFF => match self.bank_to_update {
                 0 => self.r0 = byte & 0b1111_1110,
@@ -162,6 +256,11 @@ impl Mapper for Mapper004 {
             0xA000..=0xBFFF if address % 2 == 0 => {
                 self.nametable_mirroring = byte & 1 > 0;
             }
+            0xA000..=0xBFFF if self.board == Board::Mmc6 => {
+                if self.mmc6_ram_enable {
+                    self.mmc6_ram_control = byte & 0b1111_0000;
+                }
+            }
             0xA000..=0xBFFF => {
                 self.ram_write_protection = byte & 0b0100_0000 > 0;
                 self.ram_enable = byte & 0b1000_0000 > 0;
@@ -190,64 +289,13 @@ impl Mapper for Mapper004 {
     }
 
     fn ppu_read(&mut self, address: u16) -> u8 {
-        let rel = (address & 0x03FF) as usize;
-        let mask = self.game.chr_rom().unwrap().len() - 1;
-        let byte = match address {
-            0x0000..=0x03FF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[((self.r2 as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[((self.r0 as usize) << 10 | rel) & mask]
-                }
-            }
-            0x0400..=0x07FF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[((self.r3 as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[(((self.r0 | 1) as usize) << 10 | rel) & mask]
-                }
-            }
-            0x0800..=0x0BFF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[((self.r4 as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[((self.r1 as usize) << 10 | rel) & mask]
-                }
-            }
-            0x0C00..=0x0FFF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[((self.r5 as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[(((self.r1 | 1) as usize) << 10 | rel) & mask]
-                }
-            }
-            0x1000..=0x13FF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[((self.r0 as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[((self.r2 as usize) << 10 | rel) & mask]
-                }
-            }
-            0x1400..=0x17FF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[(((self.r0 | 1) as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[((self.r3 as usize) << 10 | rel) & mask]
-                }
-            }
-            0x1800..=0x1BFF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[((self.r1 as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[((self.r4 as usize) << 10 | rel) & mask]
-                }
-            }
-            0x1C00..=0x1FFF => {
-                if self.chr_a12_inversion {
-                    self.game.chr_rom().unwrap()[(((self.r1 | 1) as usize) << 10 | rel) & mask]
-                } else {
-                    self.game.chr_rom().unwrap()[((self.r5 as usize) << 10 | rel) & mask]
-                }
+        let bank = self.chr_bank(address);
+        let index = (bank as usize) << 10 | (address & 0x03FF) as usize;
+        let byte = match (address, &self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) if self.chr_ram_selected(bank) => chr_ram.read(index),
+            (0x0000..=0x1FFF, _) => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                chr_rom[index & (chr_rom.len() - 1)]
             }
             _ => panic!("Mapper 004: PPU read of {:04X} out of bounds.", address),
         };
@@ -255,9 +303,13 @@ impl Mapper for Mapper004 {
         byte
     }
 
-    fn ppu_write(&mut self, address: u16, _byte: u8) {
-        match address {
-            0x0000..=0x1FFF => {
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        let bank = self.chr_bank(address);
+        let index = (bank as usize) << 10 | (address & 0x03FF) as usize;
+        let ram_selected = self.chr_ram_selected(bank);
+        match (address, &mut self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) if ram_selected => chr_ram.write(index, byte),
+            (0x0000..=0x1FFF, _) => {
                 eprintln!("Mapper 004: PPU write to {:04X} ignored.", address);
             }
             _ => panic!("Mapper 004: PPU write to {:04x} out of bounds.", address),
@@ -265,12 +317,21 @@ impl Mapper for Mapper004 {
         self.update_a12(address);
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
-        if self.nametable_mirroring == false {
-            address & 0b0000_0111_1111_1111
-        } else {
-            (address & 0b0000_0011_1111_1111) | ((address & 0b0000_1000_0000_0000) >> 1)
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        if self.board == Board::TxSrom {
+            // nametables follow the banks of the $0000-$0FFF pattern table
+            let nametable = (address >> 10) & 0b11;
+            return NametableSource::Vram(self.chr_bank(nametable << 10) >> 7);
         }
+        let mirroring = if self.game.four_screen_mode {
+            // four-screen boards ignore the mirroring register
+            Mirroring::FourScreen
+        } else if self.nametable_mirroring == false {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        mirroring.nametable_source(address)
     }
 
     fn tick(&mut self, cpu: &mut Cpu) {
@@ -284,6 +345,7 @@ impl Mapper for Mapper004 {
     fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
         vec![
             ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("mmc6", DebugValue::Dec((self.board == Board::Mmc6) as u64)),
             ("ram", DebugValue::Dec(self.ram.is_some() as u64)),
             (
                 "bank_to_update",
@@ -325,4 +387,83 @@ impl Mapper for Mapper004 {
             ("cycle_count", DebugValue::Dec(self.cycle_count as u64)),
         ]
     }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match (&self.ram, &self.mmc6_ram) {
+            (Some(ram), _) if self.game.battery_present => Some(ram.as_slice()),
+            (_, Some(ram)) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match (&mut self.ram, &mut self.mmc6_ram) {
+            (Some(ram), _) if self.game.battery_present => Some(ram.as_mut_slice()),
+            (_, Some(ram)) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper004 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        self.mmc6_ram.save_state(state);
+        state.write_bool(self.mmc6_ram_enable);
+        state.write_u8(self.mmc6_ram_control);
+        self.chr_ram.save_state(state);
+        state.write_u8(self.bank_to_update);
+        state.write_bool(self.prg_rom_bank_mode);
+        state.write_bool(self.chr_a12_inversion);
+        state.write_bool(self.nametable_mirroring);
+        state.write_bool(self.ram_write_protection);
+        state.write_bool(self.ram_enable);
+        state.write_u8(self.irq_latch);
+        state.write_u8(self.irq_counter);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_reload);
+        state.write_bool(self.irq_requested);
+        state.write_bytes(&[
+            self.r0, self.r1, self.r2, self.r3, self.r4, self.r5, self.r6, self.r7,
+        ]);
+        state.write_u64(self.cycle_count_a12_1);
+        state.write_u64(self.cycle_count);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.mmc6_ram.load_state(state)?;
+        self.mmc6_ram_enable = state.read_bool()?;
+        self.mmc6_ram_control = state.read_u8()?;
+        self.chr_ram.load_state(state)?;
+        self.bank_to_update = state.read_u8()?;
+        self.prg_rom_bank_mode = state.read_bool()?;
+        self.chr_a12_inversion = state.read_bool()?;
+        self.nametable_mirroring = state.read_bool()?;
+        self.ram_write_protection = state.read_bool()?;
+        self.ram_enable = state.read_bool()?;
+        self.irq_latch = state.read_u8()?;
+        self.irq_counter = state.read_u8()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_reload = state.read_bool()?;
+        self.irq_requested = state.read_bool()?;
+        let mut r = [0; 8];
+        state.read_bytes(&mut r)?;
+        [
+            self.r0, self.r1, self.r2, self.r3, self.r4, self.r5, self.r6, self.r7,
+        ] = r;
+        self.cycle_count_a12_1 = state.read_u64()?;
+        self.cycle_count = state.read_u64()?;
+        Ok(())
+    }
 }
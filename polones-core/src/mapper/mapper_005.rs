@@ -0,0 +1,564 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::mmc5_audio::Mmc5Audio;
+use super::{DebugValue, Mapper, NametableSource, PpuEvent};
+
+/// What the PPU is fetching, as far as the mapper can tell.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Fetch {
+    /// Rendering is off, accesses come from the CPU through $2007.
+    Idle,
+    Background,
+    Sprites,
+}
+
+pub struct Mapper005 {
+    game: GameFile,
+    prg_ram: Ram<{ 64 * 1024 }>,
+    prg_ram_size: usize,
+    ex_ram: Ram<1024>,
+
+    prg_mode: u8,
+    chr_mode: u8,
+    prg_ram_protect_1: u8,
+    prg_ram_protect_2: u8,
+    ex_ram_mode: u8,
+    nametable_mapping: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    /// $5113-$5117
+    prg_banks: [u8; 5],
+    /// $5120-$512B, including upper bits from $5130
+    chr_banks: [u16; 12],
+    chr_upper: u8,
+    /// Whether $5128-$512B were written after $5120-$5127.
+    chr_background_set_last: bool,
+
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+    split_y: u8,
+
+    irq_target: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline_counter: u8,
+    scanline: u16,
+
+    multiplicand: u8,
+    multiplier: u8,
+
+    large_sprites: bool,
+    fetch: Fetch,
+    /// Background tiles fetched since dot 321, the first two belong to the next scanline.
+    tile_column: u8,
+    split_tile: bool,
+    ex_attribute: u8,
+
+    audio: Mmc5Audio,
+}
+
+impl Mapper005 {
+    /// Returns whether the 8 KiB page at CPU address is ROM and its number.
+    fn prg_page(&self, address: u16) -> (bool, usize) {
+        let slot = (address as usize - 0x8000) >> 13;
+        let (value, mask) = match (self.prg_mode, slot) {
+            (0, _) => (self.prg_banks[4] | 0x80, 0b11),
+            (1, 0 | 1) => (self.prg_banks[2], 0b1),
+            (1, _) => (self.prg_banks[4] | 0x80, 0b1),
+            (2, 0 | 1) => (self.prg_banks[2], 0b1),
+            (_, 3) => (self.prg_banks[4] | 0x80, 0),
+            (_, _) => (self.prg_banks[1 + slot], 0),
+        };
+        let page = (value as usize & 0x7F & !mask) | (slot & mask);
+        (value & 0x80 != 0, page)
+    }
+
+    fn prg_ram_index(&self, page: usize, address: u16) -> Option<usize> {
+        if self.prg_ram_size == 0 {
+            return None;
+        }
+        Some(((page & 0b111) << 13 | (address as usize & 0x1FFF)) & (self.prg_ram_size - 1))
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_protect_1 == 0b10 && self.prg_ram_protect_2 == 0b01
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        let address = address as usize;
+        // Sprites use $5120-$5127, background $5128-$512B, but only in 8x16 mode.
+        let background_set = self.large_sprites
+            && match self.fetch {
+                Fetch::Background => true,
+                Fetch::Sprites => false,
+                Fetch::Idle => self.chr_background_set_last,
+            };
+        let register = match (self.chr_mode, background_set) {
+            (0, false) => 7,
+            (1, false) => 3 | (address >> 10 & 0b100),
+            (2, false) => 1 | (address >> 10 & 0b110),
+            (_, false) => address >> 10 & 0b111,
+            (0 | 1, true) => 11,
+            (2, true) => 9 | (address >> 10 & 0b010),
+            (_, true) => 8 | (address >> 10 & 0b011),
+        };
+        let shift = 13 - self.chr_mode as usize;
+        (self.chr_banks[register] as usize) << shift | (address & ((1 << shift) - 1))
+    }
+
+    fn tile_in_split(&self, column: u8) -> bool {
+        let threshold = self.split_control & 0b11111;
+        self.split_control & 0x80 != 0
+            && self.ex_ram_mode <= 1
+            && column < 34
+            && if self.split_control & 0x40 != 0 {
+                column >= threshold
+            } else {
+                column < threshold
+            }
+    }
+}
+
+impl Mapper for Mapper005 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.chr_rom().is_none() {
+            return Err("Mapper 005: Missing chr rom");
+        }
+
+        // iNES headers don't tell PRG RAM size, so give such games the maximum.
+        let prg_ram_size = match (game.prg_ram_size, game.prg_nvram_size) {
+            (None, None) if game.format != FileFormat::Nes20 => 64 * 1024,
+            (ram, nvram) => ram.unwrap_or(0) + nvram.unwrap_or(0),
+        };
+        if prg_ram_size > 64 * 1024 || !(prg_ram_size == 0 || prg_ram_size.is_power_of_two()) {
+            return Err("Mapper 005: Unexpected prg ram size");
+        }
+
+        Ok(Self {
+            prg_ram: Ram::new(),
+            prg_ram_size,
+            ex_ram: Ram::new(),
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect_1: 0,
+            prg_ram_protect_2: 0,
+            ex_ram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            prg_banks: [0, 0, 0, 0, 0xFF],
+            chr_banks: [0; 12],
+            chr_upper: 0,
+            chr_background_set_last: false,
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            split_y: 0,
+            irq_target: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline_counter: 0,
+            scanline: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            large_sprites: false,
+            fetch: Fetch::Idle,
+            tile_column: 0,
+            split_tile: false,
+            ex_attribute: 0,
+            audio: Mmc5Audio::new(),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        matches!(
+            address,
+            0x5000..=0x5007
+                | 0x5010..=0x5011
+                | 0x5015
+                | 0x5100..=0x5107
+                | 0x5113..=0x5117
+                | 0x5120..=0x512B
+                | 0x5130
+                | 0x5200..=0x5206
+                | 0x5C00..=0xFFFF
+        )
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x5010 | 0x5015 => self.audio.read(address),
+            0x5204 => {
+                let result = (self.irq_pending as u8) << 7 | (self.in_frame as u8) << 6;
+                self.irq_pending = false;
+                result
+            }
+            0x5205 => (self.multiplicand as u16 * self.multiplier as u16) as u8,
+            0x5206 => ((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8,
+            0x5C00..=0x5FFF => match self.ex_ram_mode {
+                2 | 3 => self.ex_ram.read((address - 0x5C00) as usize),
+                _ => 0,
+            },
+            0x5000..=0x5007
+            | 0x5011
+            | 0x5100..=0x5107
+            | 0x5113..=0x5117
+            | 0x5120..=0x512B
+            | 0x5130
+            | 0x5200..=0x5203 => {
+                eprintln!(
+                    "Mapper 005: CPU read from write-only register {:04X}.",
+                    address
+                );
+                0
+            }
+            0x6000..=0x7FFF => match self.prg_ram_index(self.prg_banks[0] as usize, address) {
+                Some(index) => self.prg_ram.read(index),
+                None => 0,
+            },
+            0x8000..=0xFFFF => {
+                let (rom, page) = self.prg_page(address);
+                let byte = if rom {
+                    let prg_rom = self.game.prg_rom();
+                    prg_rom[(page << 13 | (address as usize & 0x1FFF)) & (prg_rom.len() - 1)]
+                } else {
+                    match self.prg_ram_index(page, address) {
+                        Some(index) => self.prg_ram.read(index),
+                        None => 0,
+                    }
+                };
+                self.audio.cpu_read(address, byte);
+                byte
+            }
+            _ => panic!("Mapper 005: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x5000..=0x5007 | 0x5010..=0x5011 | 0x5015 => self.audio.write(address, byte),
+            0x5100 => self.prg_mode = byte & 0b11,
+            0x5101 => self.chr_mode = byte & 0b11,
+            0x5102 => self.prg_ram_protect_1 = byte & 0b11,
+            0x5103 => self.prg_ram_protect_2 = byte & 0b11,
+            0x5104 => self.ex_ram_mode = byte & 0b11,
+            0x5105 => self.nametable_mapping = byte,
+            0x5106 => self.fill_tile = byte,
+            0x5107 => self.fill_attribute = byte & 0b11,
+            0x5113..=0x5117 => self.prg_banks[(address - 0x5113) as usize] = byte,
+            0x5120..=0x512B => {
+                let register = (address - 0x5120) as usize;
+                self.chr_banks[register] = byte as u16 | (self.chr_upper as u16) << 8;
+                self.chr_background_set_last = register >= 8;
+            }
+            0x5130 => self.chr_upper = byte & 0b11,
+            0x5200 => self.split_control = byte,
+            0x5201 => self.split_scroll = byte,
+            0x5202 => self.split_bank = byte,
+            0x5203 => self.irq_target = byte,
+            0x5204 => self.irq_enabled = byte & 0x80 != 0,
+            0x5205 => self.multiplicand = byte,
+            0x5206 => self.multiplier = byte,
+            0x5C00..=0x5FFF => {
+                let index = (address - 0x5C00) as usize;
+                match self.ex_ram_mode {
+                    // writes outside of rendering store zeros
+                    0 | 1 => self
+                        .ex_ram
+                        .write(index, if self.in_frame { byte } else { 0 }),
+                    2 => self.ex_ram.write(index, byte),
+                    _ => {}
+                }
+            }
+            0x6000..=0x7FFF => {
+                let index = self.prg_ram_index(self.prg_banks[0] as usize, address);
+                if let (Some(index), true) = (index, self.prg_ram_writable()) {
+                    self.prg_ram.write(index, byte);
+                }
+            }
+            0x8000..=0xFFFF => {
+                let (rom, page) = self.prg_page(address);
+                let index = self.prg_ram_index(page, address);
+                if let (false, Some(index), true) = (rom, index, self.prg_ram_writable()) {
+                    self.prg_ram.write(index, byte);
+                }
+            }
+            _ => panic!("Mapper 005: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let chr_rom = self.game.chr_rom().unwrap();
+        let background = self.fetch == Fetch::Background;
+        match address {
+            0x0000..=0x1FFF if background && self.split_tile => {
+                let index = (self.split_bank as usize) << 12
+                    | (address as usize & 0x0FF8)
+                    | (self.split_y as usize & 0b111);
+                chr_rom[index & (chr_rom.len() - 1)]
+            }
+            0x0000..=0x1FFF if background && self.ex_ram_mode == 1 => {
+                let bank = (self.ex_attribute as usize & 0x3F) | (self.chr_upper as usize) << 6;
+                chr_rom[(bank << 12 | (address as usize & 0x0FFF)) & (chr_rom.len() - 1)]
+            }
+            0x0000..=0x1FFF => chr_rom[self.chr_index(address) & (chr_rom.len() - 1)],
+            0x2000..=0x3EFF => {
+                let offset = address & 0x03FF;
+                if background && self.split_tile {
+                    let column = self.tile_column as u16 - 1;
+                    let y = self.split_y as u16;
+                    if offset < 0x3C0 {
+                        self.ex_ram.read((y / 8 * 32 + column) as usize)
+                    } else {
+                        let attribute =
+                            self.ex_ram.read((0x3C0 + y / 32 * 8 + column / 4) as usize);
+                        let shift = ((y / 16) & 1) * 4 + ((column / 2) & 1) * 2;
+                        (attribute >> shift & 0b11) * 0b01010101
+                    }
+                } else if background && self.ex_ram_mode == 1 && offset >= 0x3C0 {
+                    (self.ex_attribute >> 6) * 0b01010101
+                } else {
+                    let nametable = (address >> 10) & 0b11;
+                    match (self.nametable_mapping >> (nametable * 2)) & 0b11 {
+                        2 if self.ex_ram_mode <= 1 => self.ex_ram.read(offset as usize),
+                        2 => 0,
+                        3 if offset < 0x3C0 => self.fill_tile,
+                        3 => self.fill_attribute * 0b01010101,
+                        _ => {
+                            eprintln!("Mapper 005: PPU read of {:04X} from CIRAM.", address);
+                            0
+                        }
+                    }
+                }
+            }
+            _ => panic!("Mapper 005: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                eprintln!("Mapper 005: PPU write to {:04X} ignored.", address);
+            }
+            0x2000..=0x3EFF => {
+                let nametable = (address >> 10) & 0b11;
+                let mapping = (self.nametable_mapping >> (nametable * 2)) & 0b11;
+                if mapping == 2 && self.ex_ram_mode <= 1 {
+                    self.ex_ram.write((address & 0x03FF) as usize, byte);
+                }
+            }
+            _ => panic!("Mapper 005: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let nametable = (address >> 10) & 0b11;
+        match (self.nametable_mapping >> (nametable * 2)) & 0b11 {
+            page @ (0 | 1) => NametableSource::Vram(page),
+            _ => NametableSource::Mapper,
+        }
+    }
+
+    fn nametable_read_source(&mut self, address: u16) -> NametableSource {
+        if self.fetch == Fetch::Background {
+            let offset = address & 0x03FF;
+            if offset < 0x3C0 {
+                self.split_tile = self.tile_in_split(self.tile_column);
+                self.ex_attribute = self.ex_ram.read(offset as usize);
+                self.tile_column = self.tile_column.saturating_add(1);
+                if self.split_tile {
+                    return NametableSource::Mapper;
+                }
+            } else if self.split_tile || self.ex_ram_mode == 1 {
+                return NametableSource::Mapper;
+            }
+        }
+        self.nametable_source(address)
+    }
+
+    fn ppu_event(&mut self, event: PpuEvent) {
+        match event {
+            PpuEvent::ScanlineStart(scanline) => {
+                if self.in_frame {
+                    self.scanline_counter = self.scanline_counter.wrapping_add(1);
+                    if self.scanline_counter == self.irq_target && self.irq_target != 0 {
+                        self.irq_pending = true;
+                    }
+                } else {
+                    self.in_frame = true;
+                    self.scanline_counter = 0;
+                }
+                self.scanline = scanline;
+            }
+            PpuEvent::SpriteFetches => self.fetch = Fetch::Sprites,
+            PpuEvent::BackgroundFetches => {
+                self.fetch = Fetch::Background;
+                self.tile_column = 0;
+                self.split_tile = false;
+                let next_scanline = if self.in_frame { self.scanline + 1 } else { 0 };
+                self.split_y = ((next_scanline + self.split_scroll as u16) % 240) as u8;
+            }
+            PpuEvent::RenderingStopped => {
+                self.in_frame = false;
+                self.fetch = Fetch::Idle;
+                self.split_tile = false;
+            }
+            PpuEvent::LargeSprites(large_sprites) => self.large_sprites = large_sprites,
+        }
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        self.audio.tick();
+        if self.irq_pending && self.irq_enabled || self.audio.irq() {
+            cpu.irq();
+        }
+    }
+
+    fn audio_output(&self) -> i32 {
+        self.audio.output()
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("prg_ram_size", DebugValue::Dec(self.prg_ram_size as u64)),
+            ("prg_mode", DebugValue::Dec(self.prg_mode as u64)),
+            ("chr_mode", DebugValue::Dec(self.chr_mode as u64)),
+            ("ex_ram_mode", DebugValue::Dec(self.ex_ram_mode as u64)),
+            (
+                "nametable_mapping",
+                DebugValue::U8Hex(self.nametable_mapping),
+            ),
+            ("prg_bank_6000", DebugValue::U8Hex(self.prg_banks[0])),
+            ("prg_bank_8000", DebugValue::U8Hex(self.prg_banks[1])),
+            ("prg_bank_a000", DebugValue::U8Hex(self.prg_banks[2])),
+            ("prg_bank_c000", DebugValue::U8Hex(self.prg_banks[3])),
+            ("prg_bank_e000", DebugValue::U8Hex(self.prg_banks[4])),
+            ("split_control", DebugValue::U8Hex(self.split_control)),
+            ("irq_target", DebugValue::Dec(self.irq_target as u64)),
+            ("irq_enabled", DebugValue::Dec(self.irq_enabled as u64)),
+            ("irq_pending", DebugValue::Dec(self.irq_pending as u64)),
+            ("in_frame", DebugValue::Dec(self.in_frame as u64)),
+            (
+                "scanline_counter",
+                DebugValue::Dec(self.scanline_counter as u64),
+            ),
+            ("large_sprites", DebugValue::Dec(self.large_sprites as u64)),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match self.game.battery_present && self.prg_ram_size > 0 {
+            true => Some(&self.prg_ram.as_slice()[..self.prg_ram_size]),
+            false => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match self.game.battery_present && self.prg_ram_size > 0 {
+            true => Some(&mut self.prg_ram.as_mut_slice()[..self.prg_ram_size]),
+            false => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        // $7000 is in the first PRG RAM bank until the game switches banks
+        if self.prg_ram_size < 8 * 1024 {
+            return false;
+        }
+        self.prg_ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+        true
+    }
+}
+
+impl SaveState for Mapper005 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.prg_ram.save_state(state);
+        self.ex_ram.save_state(state);
+        state.write_u8(self.prg_mode);
+        state.write_u8(self.chr_mode);
+        state.write_u8(self.prg_ram_protect_1);
+        state.write_u8(self.prg_ram_protect_2);
+        state.write_u8(self.ex_ram_mode);
+        state.write_u8(self.nametable_mapping);
+        state.write_u8(self.fill_tile);
+        state.write_u8(self.fill_attribute);
+        state.write_bytes(&self.prg_banks);
+        for bank in self.chr_banks {
+            state.write_u16(bank);
+        }
+        state.write_u8(self.chr_upper);
+        state.write_bool(self.chr_background_set_last);
+        state.write_u8(self.split_control);
+        state.write_u8(self.split_scroll);
+        state.write_u8(self.split_bank);
+        state.write_u8(self.split_y);
+        state.write_u8(self.irq_target);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_pending);
+        state.write_bool(self.in_frame);
+        state.write_u8(self.scanline_counter);
+        state.write_u16(self.scanline);
+        state.write_u8(self.multiplicand);
+        state.write_u8(self.multiplier);
+        state.write_bool(self.large_sprites);
+        state.write_u8(self.fetch as u8);
+        state.write_u8(self.tile_column);
+        state.write_bool(self.split_tile);
+        state.write_u8(self.ex_attribute);
+        self.audio.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.prg_ram.load_state(state)?;
+        self.ex_ram.load_state(state)?;
+        self.prg_mode = state.read_u8()? & 0b11;
+        self.chr_mode = state.read_u8()? & 0b11;
+        self.prg_ram_protect_1 = state.read_u8()?;
+        self.prg_ram_protect_2 = state.read_u8()?;
+        self.ex_ram_mode = state.read_u8()? & 0b11;
+        self.nametable_mapping = state.read_u8()?;
+        self.fill_tile = state.read_u8()?;
+        self.fill_attribute = state.read_u8()? & 0b11;
+        state.read_bytes(&mut self.prg_banks)?;
+        for bank in &mut self.chr_banks {
+            *bank = state.read_u16()?;
+        }
+        self.chr_upper = state.read_u8()?;
+        self.chr_background_set_last = state.read_bool()?;
+        self.split_control = state.read_u8()?;
+        self.split_scroll = state.read_u8()?;
+        self.split_bank = state.read_u8()?;
+        self.split_y = state.read_u8()?;
+        self.irq_target = state.read_u8()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        self.in_frame = state.read_bool()?;
+        self.scanline_counter = state.read_u8()?;
+        self.scanline = state.read_u16()?;
+        self.multiplicand = state.read_u8()?;
+        self.multiplier = state.read_u8()?;
+        self.large_sprites = state.read_bool()?;
+        self.fetch = match state.read_u8()? {
+            0 => Fetch::Idle,
+            1 => Fetch::Background,
+            2 => Fetch::Sprites,
+            _ => return Err("Save state: Invalid MMC5 fetch state"),
+        };
+        self.tile_column = state.read_u8()?;
+        self.split_tile = state.read_bool()?;
+        self.ex_attribute = state.read_u8()?;
+        self.audio.load_state(state)
+    }
+}
@@ -1,14 +1,17 @@
 use crate::cpu::Cpu;
 use crate::game_file::GameFile;
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::{Mapper, DebugValue};
+use super::discrete::bus_conflicts;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
 
 pub struct Mapper007 {
     game: GameFile,
     prg_rom_prefix: usize,
     nametable_address_prefix: u16,
     chr_ram: Ram<{ 8 * 1024 }>,
+    bus_conflicts: bool,
 }
 
 impl Mapper for Mapper007 {
@@ -21,6 +24,7 @@ impl Mapper for Mapper007 {
             prg_rom_prefix: 0,
             nametable_address_prefix: 0,
             chr_ram: Ram::new(),
+            bus_conflicts: bus_conflicts(&game, true),
             game,
         })
     }
@@ -55,6 +59,10 @@ impl Mapper for Mapper007 {
                 eprintln!("Mapper 007: CPU write to unmapped address {:04X}.", address);
             }
             0x8000..=0xFFFF => {
+                let byte = match self.bus_conflicts {
+                    true => byte & self.cpu_read(address),
+                    false => byte,
+                };
                 self.prg_rom_prefix = {
                     let bank = (byte & 0b1111) as usize;
                     let bank_size_mask = (self.game.prg_rom().len() as usize >> 15) - 1;
@@ -89,8 +97,9 @@ impl Mapper for Mapper007 {
         }
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
-        (address & 0b11_1111_1111) | self.nametable_address_prefix
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let page = (self.nametable_address_prefix >> 10) as u8;
+        Mirroring::SingleScreen(page).nametable_source(address)
     }
 
     fn tick(&mut self, _cpu: &mut Cpu) {}
@@ -103,3 +112,22 @@ impl Mapper for Mapper007 {
         ]
     }
 }
+
+impl SaveState for Mapper007 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.prg_rom_prefix as u32);
+        state.write_u16(self.nametable_address_prefix);
+        self.chr_ram.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.prg_rom_prefix = state.read_u32()? as usize;
+        self.nametable_address_prefix = state.read_u16()?;
+        if self.prg_rom_prefix >= self.game.prg_rom().len()
+            || self.nametable_address_prefix & !0b100_0000_0000 != 0
+        {
+            return Err("Save state: Invalid mapper 007 state");
+        }
+        self.chr_ram.load_state(state)
+    }
+}
@@ -1,53 +1,47 @@
 use crate::cpu::Cpu;
 use crate::game_file::GameFile;
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-use super::{DebugValue, Mapper};
+use super::mmc2_latch::Mmc2Latch;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
 
 pub struct Mapper009 {
     game: GameFile,
     prg_rom_bank_select: u8,
-    chr_rom_fd_0_bank_select: u8,
-    chr_rom_fe_0_bank_select: u8,
-    chr_rom_fd_1_bank_select: u8,
-    chr_rom_fe_1_bank_select: u8,
-    latch_0_fe: bool,
-    latch_1_fe: bool,
+    chr: Mmc2Latch,
     prg_ram: Ram<{ 8 * 1024 }>,
     mirroring_horizontal: bool,
 }
 
+impl Mapper009 {
+    fn has_prg_ram(&self) -> bool {
+        self.game.prg_ram_size.is_some() || self.game.prg_nvram_size.is_some()
+    }
+}
+
 impl Mapper for Mapper009 {
     fn from_game(game: GameFile) -> Result<Self, &'static str> {
-        if game.prg_rom().len() != 128 * 1024 {
+        if game.prg_rom().len() < 32 * 1024 {
             return Err("Mapper 009: Unexpected prg rom size");
         }
 
-        if game.prg_ram_size.is_some() && game.prg_ram_size.unwrap() != 8 * 1024 {
-            return Err("Mapper 009: Unexpected prg ram size");
-        }
-
-        if game.chr_rom().is_none() || game.chr_rom().unwrap().len() != 128 * 1024 {
-            return Err("Mapper 009: Unexpected chr rom size");
+        if game.chr_rom().is_none() {
+            return Err("Mapper 009: Missing chr rom");
         }
 
         Ok(Self {
             prg_rom_bank_select: 0,
             prg_ram: Ram::new(),
             mirroring_horizontal: false,
-            chr_rom_fd_0_bank_select: 0,
-            chr_rom_fe_0_bank_select: 0,
-            chr_rom_fd_1_bank_select: 0,
-            chr_rom_fe_1_bank_select: 0,
-            latch_0_fe: false,
-            latch_1_fe: false,
+            chr: Mmc2Latch::new(true),
             game,
         })
     }
 
     fn cpu_address_mapped(&self, address: u16) -> bool {
         match address {
-            0x6000..=0x7FFF => self.game.prg_ram_size.is_some(),
+            0x6000..=0x7FFF => self.has_prg_ram(),
             0x8000..=0xFFFF => true,
             _ => false,
         }
@@ -56,7 +50,7 @@ impl Mapper for Mapper009 {
     fn cpu_read(&mut self, address: u16) -> u8 {
         match address {
             0x6000..=0x7FFF => {
-                if self.game.prg_ram_size.is_some() {
+                if self.has_prg_ram() {
                     self.prg_ram.read((address - 0x6000) as usize)
                 } else {
                     eprintln!(
@@ -82,7 +76,7 @@ impl Mapper for Mapper009 {
     fn cpu_write(&mut self, address: u16, byte: u8) {
         match address {
             0x6000..=0x7FFF => {
-                if self.game.prg_ram_size.is_some() {
+                if self.has_prg_ram() {
                     self.prg_ram.write((address - 0x6000) as usize, byte);
                 } else {
                     eprintln!("Mapper 009: CPU write to unmapped address {:04X}.", address);
@@ -91,18 +85,7 @@ impl Mapper for Mapper009 {
             0xA000..=0xAFFF => {
                 self.prg_rom_bank_select = byte & 0b0000_1111;
             }
-            0xB000..=0xBFFF => {
-                self.chr_rom_fd_0_bank_select = byte & 0b0001_1111;
-            }
-            0xC000..=0xCFFF => {
-                self.chr_rom_fe_0_bank_select = byte & 0b0001_1111;
-            }
-            0xD000..=0xDFFF => {
-                self.chr_rom_fd_1_bank_select = byte & 0b0001_1111;
-            }
-            0xE000..=0xEFFF => {
-                self.chr_rom_fe_1_bank_select = byte & 0b0001_1111;
-            }
+            0xB000..=0xEFFF => self.chr.write_bank(address, byte),
             0xF000..=0xFFFF => {
                 self.mirroring_horizontal = byte & 1 > 0;
             }
@@ -119,40 +102,10 @@ impl Mapper for Mapper009 {
 
     fn ppu_read(&mut self, address: u16) -> u8 {
         match address {
-            0x0000..=0x0FFF => {
-                let page = if self.latch_0_fe {
-                    self.chr_rom_fe_0_bank_select
-                } else {
-                    self.chr_rom_fd_0_bank_select
-                };
-                let byte = self.game.chr_rom().unwrap()[((address as usize)
-                    + page as usize * 4 * 1024)
-                    & (self.game.chr_rom().unwrap().len() - 1)];
-
-                if address == 0x0FD8 {
-                    self.latch_0_fe = false;
-                } else if address == 0x0FE8 {
-                    self.latch_0_fe = true;
-                }
-
-                byte
-            }
-            0x1000..=0x1FFF => {
-                let page = if self.latch_1_fe {
-                    self.chr_rom_fe_1_bank_select
-                } else {
-                    self.chr_rom_fd_1_bank_select
-                };
-                let byte = self.game.chr_rom().unwrap()[((address as usize - 0x1000)
-                    + page as usize * 4 * 1024)
-                    & (self.game.chr_rom().unwrap().len() - 1)];
-
-                if (0x1FD8..=0x1FDF).contains(&address) {
-                    self.latch_1_fe = false;
-                } else if (0x1FE8..=0x1FEF).contains(&address) {
-                    self.latch_1_fe = true;
-                }
-
+            0x0000..=0x1FFF => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                let byte = chr_rom[self.chr.chr_index(address) & (chr_rom.len() - 1)];
+                self.chr.ppu_read(address);
                 byte
             }
             _ => panic!("Mapper 009: PPU read of {:04X} out of bounds.", address),
@@ -168,11 +121,11 @@ impl Mapper for Mapper009 {
         }
     }
 
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16 {
+    fn nametable_source(&self, address: u16) -> NametableSource {
         if self.mirroring_horizontal {
-            (address & 0b0000_0011_1111_1111) | ((address & 0b0000_1000_0000_0000) >> 1)
+            Mirroring::Horizontal.nametable_source(address)
         } else {
-            address & 0b0000_0111_1111_1111
+            Mirroring::Vertical.nametable_source(address)
         }
     }
 
@@ -187,26 +140,62 @@ impl Mapper for Mapper009 {
             ),
             (
                 "chr_rom_fd_0_bank_s",
-                DebugValue::U8Hex(self.chr_rom_fd_0_bank_select),
+                DebugValue::U8Hex(self.chr.fd_banks[0]),
             ),
             (
                 "chr_rom_fe_0_bank_s",
-                DebugValue::U8Hex(self.chr_rom_fe_0_bank_select),
+                DebugValue::U8Hex(self.chr.fe_banks[0]),
             ),
             (
                 "chr_rom_fd_1_bank_s",
-                DebugValue::U8Hex(self.chr_rom_fd_1_bank_select),
+                DebugValue::U8Hex(self.chr.fd_banks[1]),
             ),
             (
                 "chr_rom_fe_1_bank_s",
-                DebugValue::U8Hex(self.chr_rom_fe_1_bank_select),
+                DebugValue::U8Hex(self.chr.fe_banks[1]),
             ),
-            ("latch_0_fe", DebugValue::Dec(self.latch_0_fe as _)),
-            ("latch_1_fe", DebugValue::Dec(self.latch_1_fe as _)),
+            ("latch_0_fe", DebugValue::Dec(self.chr.latch_fe(0) as _)),
+            ("latch_1_fe", DebugValue::Dec(self.chr.latch_fe(1) as _)),
             (
                 "mirroring_horizontal",
                 DebugValue::Dec(self.mirroring_horizontal as _),
             ),
         ]
     }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        (self.game.battery_present && self.has_prg_ram()).then(|| self.prg_ram.as_slice())
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.game.battery_present && self.has_prg_ram() {
+            Some(self.prg_ram.as_mut_slice())
+        } else {
+            None
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        if self.has_prg_ram() {
+            self.prg_ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+        }
+        self.has_prg_ram()
+    }
+}
+
+impl SaveState for Mapper009 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.prg_rom_bank_select);
+        self.chr.save_state(state);
+        self.prg_ram.save_state(state);
+        state.write_bool(self.mirroring_horizontal);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.prg_rom_bank_select = state.read_u8()?;
+        self.chr.load_state(state)?;
+        self.prg_ram.load_state(state)?;
+        self.mirroring_horizontal = state.read_bool()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,170 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::mmc2_latch::Mmc2Latch;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// MMC4. Like MMC2, but with 16 KiB PRG banks and RAM at $6000.
+pub struct Mapper010 {
+    game: GameFile,
+    ram: Option<Ram<{ 8 * 1024 }>>,
+    prg_bank: u8,
+    chr: Mmc2Latch,
+    mirroring_horizontal: bool,
+}
+
+impl Mapper for Mapper010 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.chr_rom().is_none() {
+            return Err("Mapper 010: Missing chr rom");
+        }
+
+        Ok(Self {
+            // Only Nes 2.0 can tell us if ram is present. For other formats assume present.
+            ram: (game.format != FileFormat::Nes20
+                || game.prg_ram_size.is_some()
+                || game.prg_nvram_size.is_some())
+            .then(Ram::new),
+            prg_bank: 0,
+            chr: Mmc2Latch::new(false),
+            mirroring_horizontal: false,
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x6000..=0x7FFF => self.ram.is_some(),
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        let offset = address as usize & 0x3FFF;
+        match address {
+            0x6000..=0x7FFF => match &self.ram {
+                Some(ram) => ram.read(address as usize & 0x1FFF),
+                None => 0,
+            },
+            0x8000..=0xBFFF => {
+                let bank = (self.prg_bank & 0x0F) as usize;
+                prg_rom[(bank << 14 | offset) & (prg_rom.len() - 1)]
+            }
+            0xC000..=0xFFFF => prg_rom[prg_rom.len() - 0x4000 + offset],
+            _ => panic!("Mapper 010: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => {
+                if let Some(ram) = &mut self.ram {
+                    ram.write(address as usize & 0x1FFF, byte);
+                }
+            }
+            0x8000..=0x9FFF => {}
+            0xA000..=0xAFFF => self.prg_bank = byte,
+            0xB000..=0xEFFF => self.chr.write_bank(address, byte),
+            0xF000..=0xFFFF => self.mirroring_horizontal = byte & 1 != 0,
+            _ => panic!("Mapper 010: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                let byte = chr_rom[self.chr.chr_index(address) & (chr_rom.len() - 1)];
+                self.chr.ppu_read(address);
+                byte
+            }
+            _ => panic!("Mapper 010: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, _byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                eprintln!("Mapper 010: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 010: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        if self.mirroring_horizontal {
+            Mirroring::Horizontal.nametable_source(address)
+        } else {
+            Mirroring::Vertical.nametable_source(address)
+        }
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("ram", DebugValue::Dec(self.ram.is_some() as u64)),
+            ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            ("chr_bank_fd_0", DebugValue::U8Hex(self.chr.fd_banks[0])),
+            ("chr_bank_fe_0", DebugValue::U8Hex(self.chr.fe_banks[0])),
+            ("chr_bank_fd_1", DebugValue::U8Hex(self.chr.fd_banks[1])),
+            ("chr_bank_fe_1", DebugValue::U8Hex(self.chr.fe_banks[1])),
+            ("latch_0_fe", DebugValue::Dec(self.chr.latch_fe(0) as u64)),
+            ("latch_1_fe", DebugValue::Dec(self.chr.latch_fe(1) as u64)),
+            (
+                "mirroring_horizontal",
+                DebugValue::Dec(self.mirroring_horizontal as u64),
+            ),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper010 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        state.write_u8(self.prg_bank);
+        self.chr.save_state(state);
+        state.write_bool(self.mirroring_horizontal);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        self.chr.load_state(state)?;
+        self.mirroring_horizontal = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, bus_conflicts, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Color Dreams. Like GxROM, but with the PRG and CHR bits swapped and wider.
+pub struct Mapper011 {
+    game: GameFile,
+    chr: Chr,
+    prg_bank: u8,
+    chr_bank: u8,
+    bus_conflicts: bool,
+}
+
+impl Mapper for Mapper011 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            chr: Chr::new(&game),
+            prg_bank: 0,
+            chr_bank: 0,
+            bus_conflicts: bus_conflicts(&game, true),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x8000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x8000..=0xFFFF => {
+                let prg_rom = self.game.prg_rom();
+                prg_rom[bank_index(prg_rom, self.prg_bank as usize, 0x8000, address)]
+            }
+            _ => panic!("Mapper 011: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x8000..=0xFFFF => {
+                let byte = match self.bus_conflicts {
+                    true => byte & self.cpu_read(address),
+                    false => byte,
+                };
+                self.prg_bank = byte & 0b11;
+                self.chr_bank = byte >> 4;
+            }
+            _ => panic!("Mapper 011: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => {
+                let bank = self.chr_bank as usize;
+                self.chr.read(&self.game, bank, 0x2000, address)
+            }
+            _ => panic!("Mapper 011: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 011: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 011: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            ("chr_bank", DebugValue::U8Hex(self.chr_bank)),
+        ]
+    }
+}
+
+impl SaveState for Mapper011 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.chr.save_state(state);
+        state.write_u8(self.prg_bank);
+        state.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        self.chr_bank = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::bank_index;
+use super::eeprom::{Eeprom, EepromKind};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Bandai FCG boards: FCG-1/2 (mapper 16 submapper 4) with registers at
+/// $6000-$7FFF, LZ93D50 (mapper 16 submapper 5) with registers at $8000-$FFFF
+/// and a 24C02 EEPROM, LZ93D50 with a 24C01 EEPROM (mapper 159), and LZ93D50
+/// with 8 KiB of battery-backed SRAM and 512 KiB of PRG-ROM (mapper 153).
+/// Mapper 16 games without a submapper get registers at both addresses.
+pub struct Mapper016 {
+    game: GameFile,
+    registers_at_6000: bool,
+    registers_at_8000: bool,
+    eeprom: Option<Eeprom>,
+    /// SRAM of mapper 153.
+    prg_ram: Option<Ram<{ 8 * 1024 }>>,
+    prg_ram_enabled: bool,
+    chr_ram: Option<Ram<{ 8 * 1024 }>>,
+    chr_banks: [u8; 8],
+    prg_bank: u8,
+    /// 256 KiB PRG half of mapper 153, set by bit 0 of the CHR registers.
+    outer_prg_bank: u8,
+    mirroring: u8,
+    irq_enabled: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_pending: bool,
+}
+
+impl Mapper016 {
+    fn prg_index(&self, address: u16) -> usize {
+        let prg_rom = self.game.prg_rom();
+        let bank = match address {
+            0x8000..=0xBFFF => self.prg_bank as usize,
+            _ => 0x0F,
+        };
+        let bank = (self.outer_prg_bank as usize) << 4 | bank;
+        bank_index(prg_rom, bank, 0x4000, address)
+    }
+
+    fn write_register(&mut self, address: u16, byte: u8) {
+        match address & 0x0F {
+            register @ 0x0..=0x7 => {
+                self.chr_banks[register as usize] = byte;
+                if self.game.mapper == 153 && register < 4 {
+                    self.outer_prg_bank = byte & 1;
+                }
+            }
+            0x8 => self.prg_bank = byte & 0x0F,
+            0x9 => self.mirroring = byte & 0b11,
+            // FCG-1/2 writes the IRQ counter directly, LZ93D50 writes a latch
+            // copied to the counter when the IRQ gets enabled
+            0xA => {
+                self.irq_enabled = byte & 1 != 0;
+                if self.registers_at_8000 {
+                    self.irq_counter = self.irq_latch;
+                }
+                self.irq_pending = false;
+            }
+            0xB => {
+                self.irq_latch = (self.irq_latch & 0xFF00) | byte as u16;
+                if self.registers_at_6000 {
+                    self.irq_counter = (self.irq_counter & 0xFF00) | byte as u16;
+                }
+            }
+            0xC => {
+                self.irq_latch = (self.irq_latch & 0x00FF) | (byte as u16) << 8;
+                if self.registers_at_6000 {
+                    self.irq_counter = (self.irq_counter & 0x00FF) | (byte as u16) << 8;
+                }
+            }
+            0xD => {
+                if let Some(eeprom) = &mut self.eeprom {
+                    eeprom.write(byte & 0x20 != 0, byte & 0x40 != 0);
+                }
+                self.prg_ram_enabled = byte & 0x20 != 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mapper016 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.prg_rom().len() < 0x4000 {
+            return Err("Mapper 016: Unexpected prg rom size");
+        }
+
+        let (registers_at_6000, registers_at_8000) = match (game.mapper, game.submapper) {
+            (16, Some(4)) => (true, false),
+            (16, Some(5)) | (153 | 159, _) => (false, true),
+            _ => (true, true),
+        };
+        // Nes 2.0 tells the EEPROM size, for other formats assume the usual one
+        let eeprom = match (game.mapper, game.prg_nvram_size) {
+            (153, _) => None,
+            (_, Some(128)) => Some(EepromKind::X24C01),
+            (_, Some(256)) => Some(EepromKind::C24C02),
+            (_, Some(_)) => None,
+            (159, None) => Some(EepromKind::X24C01),
+            (_, None) if registers_at_8000 => Some(EepromKind::C24C02),
+            (_, None) => None,
+        };
+
+        Ok(Self {
+            registers_at_6000,
+            registers_at_8000,
+            eeprom: eeprom.map(Eeprom::new),
+            prg_ram: (game.mapper == 153).then(Ram::new),
+            prg_ram_enabled: false,
+            chr_ram: game.chr_rom().is_none().then(Ram::new),
+            chr_banks: [0; 8],
+            prg_bank: 0,
+            outer_prg_bank: 0,
+            mirroring: 0,
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x6000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x6000..=0x7FFF if self.prg_ram.is_some() => {
+                if self.prg_ram_enabled {
+                    self.prg_ram.as_ref().unwrap().read(address as usize)
+                } else {
+                    (address >> 8) as u8
+                }
+            }
+            // only bit 4 is driven, by the EEPROM data line
+            0x6000..=0x7FFF => match &self.eeprom {
+                Some(eeprom) => (address >> 8) as u8 & 0xEF | (eeprom.output() as u8) << 4,
+                None => (address >> 8) as u8,
+            },
+            0x8000..=0xFFFF => self.game.prg_rom()[self.prg_index(address)],
+            _ => panic!("Mapper 016: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF if self.prg_ram.is_some() => {
+                if self.prg_ram_enabled {
+                    self.prg_ram.as_mut().unwrap().write(address as usize, byte);
+                }
+            }
+            0x6000..=0x7FFF if self.registers_at_6000 => self.write_register(address, byte),
+            0x6000..=0x7FFF => {}
+            0x8000..=0xFFFF if self.registers_at_8000 => self.write_register(address, byte),
+            0x8000..=0xFFFF => {}
+            _ => panic!("Mapper 016: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        (0x0000..=0x1FFF).contains(&address)
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => match (&self.chr_ram, self.game.chr_rom()) {
+                (Some(chr_ram), _) => chr_ram.read(address as usize),
+                (None, Some(chr_rom)) => {
+                    let bank = self.chr_banks[address as usize >> 10] as usize;
+                    chr_rom[bank_index(chr_rom, bank, 0x0400, address)]
+                }
+                (None, None) => unreachable!(),
+            },
+            _ => panic!("Mapper 016: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match (address, &mut self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) => chr_ram.write(address as usize, byte),
+            (0x0000..=0x1FFF, None) => {
+                eprintln!("Mapper 016: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 016: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let mirroring = match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            page => Mirroring::SingleScreen(page - 2),
+        };
+        mirroring.nametable_source(address)
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        if self.irq_enabled {
+            // the counter is checked before decrementing
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+        if self.irq_pending {
+            cpu.irq();
+        }
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            ("outer_prg_bank", DebugValue::U8Hex(self.outer_prg_bank)),
+            ("chr_bank_0", DebugValue::U8Hex(self.chr_banks[0])),
+            ("chr_bank_1", DebugValue::U8Hex(self.chr_banks[1])),
+            ("chr_bank_2", DebugValue::U8Hex(self.chr_banks[2])),
+            ("chr_bank_3", DebugValue::U8Hex(self.chr_banks[3])),
+            ("chr_bank_4", DebugValue::U8Hex(self.chr_banks[4])),
+            ("chr_bank_5", DebugValue::U8Hex(self.chr_banks[5])),
+            ("chr_bank_6", DebugValue::U8Hex(self.chr_banks[6])),
+            ("chr_bank_7", DebugValue::U8Hex(self.chr_banks[7])),
+            ("mirroring", DebugValue::U8Hex(self.mirroring)),
+            ("irq_enabled", DebugValue::Dec(self.irq_enabled as u64)),
+            ("irq_counter", DebugValue::U16Hex(self.irq_counter)),
+            ("irq_latch", DebugValue::U16Hex(self.irq_latch)),
+        ]
+    }
+
+    /// EEPROM contents, or the SRAM of mapper 153.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match (&self.eeprom, &self.prg_ram) {
+            (Some(eeprom), _) => Some(eeprom.data()),
+            (None, Some(prg_ram)) if self.game.battery_present => Some(prg_ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match (&mut self.eeprom, &mut self.prg_ram) {
+            (Some(eeprom), _) => Some(eeprom.data_mut()),
+            (None, Some(prg_ram)) if self.game.battery_present => Some(prg_ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+}
+
+impl SaveState for Mapper016 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.eeprom.save_state(state);
+        self.prg_ram.save_state(state);
+        state.write_bool(self.prg_ram_enabled);
+        self.chr_ram.save_state(state);
+        state.write_bytes(&self.chr_banks);
+        state.write_u8(self.prg_bank);
+        state.write_u8(self.outer_prg_bank);
+        state.write_u8(self.mirroring);
+        state.write_bool(self.irq_enabled);
+        state.write_u16(self.irq_counter);
+        state.write_u16(self.irq_latch);
+        state.write_bool(self.irq_pending);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.eeprom.load_state(state)?;
+        self.prg_ram.load_state(state)?;
+        self.prg_ram_enabled = state.read_bool()?;
+        self.chr_ram.load_state(state)?;
+        state.read_bytes(&mut self.chr_banks)?;
+        self.prg_bank = state.read_u8()? & 0x0F;
+        self.outer_prg_bank = state.read_u8()? & 1;
+        self.mirroring = state.read_u8()? & 0b11;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_counter = state.read_u16()?;
+        self.irq_latch = state.read_u16()?;
+        self.irq_pending = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::n163_audio::{N163Audio, SOUND_RAM_SIZE};
+use super::{DebugValue, Mapper, NametableSource};
+
+/// Namco 163. Sound RAM follows the 8 KiB of PRG RAM in `ram`, so both are
+/// covered by the battery.
+pub struct Mapper019 {
+    game: GameFile,
+    ram: Vec<u8>,
+    chr_ram: Option<Ram<{ 8 * 1024 }>>,
+    prg_banks: [u8; 3],
+    /// 1 KiB banks of pattern tables, then of nametables.
+    chr_banks: [u8; 12],
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_pending: bool,
+    /// The sound RAM address port also protects PRG RAM from writes.
+    audio: N163Audio,
+}
+
+impl Mapper019 {
+    /// The audio chip and its sound RAM at the end of `ram`.
+    fn audio(&mut self) -> (&mut N163Audio, &mut [u8]) {
+        let start = self.ram.len() - SOUND_RAM_SIZE;
+        (&mut self.audio, &mut self.ram[start..])
+    }
+
+    fn prg_ram_present(&self) -> bool {
+        self.ram.len() > SOUND_RAM_SIZE
+    }
+
+    fn prg_ram_writable(&self, address: u16) -> bool {
+        let protection = self.audio.address();
+        protection & 0xF0 == 0x40 && protection & (1 << ((address >> 11) & 0b11)) == 0
+    }
+
+    fn sound_enabled(&self) -> bool {
+        self.prg_banks[0] & 0x40 == 0
+    }
+
+    /// Bank register used for a pattern table or nametable address.
+    fn chr_slot(address: u16) -> usize {
+        match address {
+            0x0000..=0x1FFF => (address >> 10) as usize,
+            _ => 8 + ((address >> 10) & 0b11) as usize,
+        }
+    }
+
+    /// Returns the CIRAM page selected for a bank register, if any. Banks
+    /// $E0-$FF select CIRAM, unless disabled for the pattern table by $E800.
+    fn ciram_page(&self, slot: usize) -> Option<u8> {
+        let bank = self.chr_banks[slot];
+        let ciram_disabled = slot < 8 && self.prg_banks[1] & (0x40 << (slot >> 2)) != 0;
+        (bank >= 0xE0 && !ciram_disabled).then_some(bank & 1)
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        (self.chr_banks[Self::chr_slot(address)] as usize) << 10 | (address as usize & 0x03FF)
+    }
+}
+
+impl Mapper for Mapper019 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        // Only Nes 2.0 can tell us if ram is present. For other formats assume present.
+        let prg_ram = game.format != FileFormat::Nes20
+            || game.prg_ram_size.is_some()
+            || game
+                .prg_nvram_size
+                .is_some_and(|size| size > SOUND_RAM_SIZE);
+
+        Ok(Self {
+            ram: vec![0; if prg_ram { 8 * 1024 } else { 0 } + SOUND_RAM_SIZE],
+            chr_ram: game.chr_rom().is_none().then(Ram::new),
+            prg_banks: [0; 3],
+            chr_banks: [0; 12],
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            audio: N163Audio::new(),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x4800..=0x5FFF => true,
+            0x6000..=0x7FFF => self.prg_ram_present(),
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        let offset = address as usize & 0x1FFF;
+        match address {
+            0x4800..=0x4FFF => {
+                let (audio, sound_ram) = self.audio();
+                audio.read_data(sound_ram)
+            }
+            0x5000..=0x57FF => self.irq_counter as u8,
+            0x5800..=0x5FFF => (self.irq_counter >> 8) as u8 | (self.irq_enabled as u8) << 7,
+            0x6000..=0x7FFF => self.ram[offset],
+            0x8000..=0xDFFF => {
+                let bank = (self.prg_banks[(address as usize - 0x8000) >> 13] & 0x3F) as usize;
+                prg_rom[(bank << 13 | offset) & (prg_rom.len() - 1)]
+            }
+            0xE000..=0xFFFF => prg_rom[prg_rom.len() - 0x2000 + offset],
+            _ => panic!("Mapper 019: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4800..=0x4FFF => {
+                let (audio, sound_ram) = self.audio();
+                audio.write_data(sound_ram, byte);
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | byte as u16;
+                self.irq_pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (byte as u16 & 0x7F) << 8;
+                self.irq_enabled = byte & 0x80 != 0;
+                self.irq_pending = false;
+            }
+            0x6000..=0x7FFF => {
+                if self.prg_ram_writable(address) {
+                    self.ram[address as usize & 0x1FFF] = byte;
+                }
+            }
+            0x8000..=0xDFFF => self.chr_banks[(address as usize - 0x8000) >> 11] = byte,
+            0xE000..=0xF7FF => self.prg_banks[(address as usize - 0xE000) >> 11] = byte,
+            0xF800..=0xFFFF => self.audio.write_address(byte),
+            _ => panic!("Mapper 019: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF && self.ciram_page(Self::chr_slot(address)).is_none()
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let index = self.chr_index(address);
+        match (address, &self.chr_ram) {
+            (0x0000..=0x3EFF, Some(chr_ram)) => chr_ram.read(index),
+            (0x0000..=0x3EFF, None) => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                chr_rom[index & (chr_rom.len() - 1)]
+            }
+            _ => panic!("Mapper 019: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        let index = self.chr_index(address);
+        match (address, &mut self.chr_ram) {
+            (0x0000..=0x3EFF, Some(chr_ram)) => chr_ram.write(index, byte),
+            (0x0000..=0x3EFF, None) => {
+                eprintln!("Mapper 019: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 019: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        match self.ciram_page(Self::chr_slot(address)) {
+            Some(page) => NametableSource::Vram(page),
+            None => NametableSource::Mapper,
+        }
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        if self.irq_enabled && self.irq_counter < 0x7FFF {
+            self.irq_counter += 1;
+            if self.irq_counter == 0x7FFF {
+                self.irq_pending = true;
+            }
+        }
+        if self.irq_pending {
+            cpu.irq();
+        }
+        if self.sound_enabled() {
+            let (audio, sound_ram) = self.audio();
+            audio.tick(sound_ram);
+        }
+    }
+
+    fn audio_output(&self) -> i32 {
+        match self.sound_enabled() {
+            true => self.audio.output(),
+            false => 0,
+        }
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("prg_ram", DebugValue::Dec(self.prg_ram_present() as u64)),
+            ("prg_bank_8000", DebugValue::U8Hex(self.prg_banks[0])),
+            ("prg_bank_a000", DebugValue::U8Hex(self.prg_banks[1])),
+            ("prg_bank_c000", DebugValue::U8Hex(self.prg_banks[2])),
+            ("chr_bank_0000", DebugValue::U8Hex(self.chr_banks[0])),
+            ("chr_bank_0400", DebugValue::U8Hex(self.chr_banks[1])),
+            ("chr_bank_0800", DebugValue::U8Hex(self.chr_banks[2])),
+            ("chr_bank_0c00", DebugValue::U8Hex(self.chr_banks[3])),
+            ("chr_bank_1000", DebugValue::U8Hex(self.chr_banks[4])),
+            ("chr_bank_1400", DebugValue::U8Hex(self.chr_banks[5])),
+            ("chr_bank_1800", DebugValue::U8Hex(self.chr_banks[6])),
+            ("chr_bank_1c00", DebugValue::U8Hex(self.chr_banks[7])),
+            ("nt_bank_2000", DebugValue::U8Hex(self.chr_banks[8])),
+            ("nt_bank_2400", DebugValue::U8Hex(self.chr_banks[9])),
+            ("nt_bank_2800", DebugValue::U8Hex(self.chr_banks[10])),
+            ("nt_bank_2c00", DebugValue::U8Hex(self.chr_banks[11])),
+            ("sound_address", DebugValue::U8Hex(self.audio.address())),
+            ("irq_counter", DebugValue::U16Hex(self.irq_counter)),
+            ("irq_enabled", DebugValue::Dec(self.irq_enabled as u64)),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match self.game.battery_present {
+            true => Some(&self.ram),
+            false => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match self.game.battery_present {
+            true => Some(&mut self.ram),
+            false => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match self.prg_ram_present() {
+            true => {
+                self.ram[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            false => false,
+        }
+    }
+}
+
+impl SaveState for Mapper019 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        self.chr_ram.save_state(state);
+        state.write_bytes(&self.prg_banks);
+        state.write_bytes(&self.chr_banks);
+        state.write_u16(self.irq_counter);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_pending);
+        self.audio.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        state.read_bytes(&mut self.ram)?;
+        self.chr_ram.load_state(state)?;
+        state.read_bytes(&mut self.prg_banks)?;
+        state.read_bytes(&mut self.chr_banks)?;
+        self.irq_counter = state.read_u16()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        self.audio.load_state(state)
+    }
+}
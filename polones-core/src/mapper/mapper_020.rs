@@ -0,0 +1,556 @@
+use crate::cpu::Cpu;
+use crate::disk_file::DiskFile;
+use crate::game_file::GameFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::fds_audio::FdsAudio;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Bytes of one disk side in the drive, including gaps and checksums.
+const TRACK_LENGTH: usize = 0x18000;
+
+/// Zero bytes before the first block of a side.
+const TRACK_START_GAP: usize = 28300 / 8;
+
+/// Zero bytes after each block.
+const BLOCK_GAP: usize = 976 / 8;
+
+/// CPU cycles per byte passing under the head, about 96.4 kbit/s.
+const BYTE_CYCLES: u32 = 150;
+
+/// CPU cycles the head takes to get back to the start of a side.
+const REWIND_CYCLES: u32 = 50000;
+
+/// CPU cycles the drive stays empty when switching disk sides, so the game
+/// notices the disk change.
+const DISK_SWAP_CYCLES: u32 = 1_000_000;
+
+/// Updates the checksum of a block with one byte.
+fn crc_step(mut crc: u16, byte: u8) -> u16 {
+    for bit in 0..8 {
+        let carry = crc & 1 != 0;
+        crc >>= 1;
+        if carry {
+            crc ^= 0x8408;
+        }
+        if byte & (1 << bit) != 0 {
+            crc ^= 0x8000;
+        }
+    }
+    crc
+}
+
+/// Lays out the blocks of a .fds disk side the way they are on the disk, with
+/// gaps, start marks and checksums.
+fn track_from_side(side: &[u8]) -> Vec<u8> {
+    let mut track = vec![0; TRACK_START_GAP];
+    let mut position = 0;
+    let mut file_size = 0;
+    while position < side.len() {
+        let length = match side[position] {
+            1 => 56,
+            2 => 2,
+            3 => 16,
+            4 => 1 + file_size,
+            _ => break,
+        };
+        let block = &side[position..(position + length).min(side.len())];
+        if block[0] == 3 && block.len() == 16 {
+            file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+        }
+
+        let mut crc = crc_step(0, 0x80);
+        for byte in block {
+            crc = crc_step(crc, *byte);
+        }
+        let crc = crc_step(crc_step(crc, 0), 0);
+        track.push(0x80);
+        track.extend_from_slice(block);
+        track.extend_from_slice(&crc.to_le_bytes());
+        track.extend_from_slice(&[0; BLOCK_GAP]);
+        position += length;
+    }
+    track.resize(TRACK_LENGTH, 0);
+    track
+}
+
+/// Disk drive and the RAM adapter's disk transfer logic. Transfers one byte
+/// every `BYTE_CYCLES` while the motor is on.
+struct DiskDrive {
+    /// Tracks of all disk sides, `TRACK_LENGTH` bytes each.
+    disk: Vec<u8>,
+    side: Option<usize>,
+    /// Side inserted when the swap delay runs out.
+    next_side: Option<usize>,
+    swap_delay: u32,
+    motor_on: bool,
+    transfer_reset: bool,
+    read_mode: bool,
+    crc_control: bool,
+    previous_crc_control: bool,
+    /// Set by the BIOS once the head is past the gap before a block.
+    ready: bool,
+    irq_enabled: bool,
+    position: usize,
+    delay: u32,
+    scanning: bool,
+    end_of_head: bool,
+    gap_ended: bool,
+    crc: u16,
+    read_data: u8,
+    write_data: u8,
+    transferred: bool,
+    irq: bool,
+}
+
+impl DiskDrive {
+    fn new(disk: Vec<u8>) -> Self {
+        Self {
+            disk,
+            side: Some(0),
+            next_side: None,
+            swap_delay: 0,
+            motor_on: false,
+            transfer_reset: false,
+            read_mode: true,
+            crc_control: false,
+            previous_crc_control: false,
+            ready: false,
+            irq_enabled: false,
+            position: 0,
+            delay: 0,
+            scanning: false,
+            end_of_head: true,
+            gap_ended: false,
+            crc: 0,
+            read_data: 0,
+            write_data: 0,
+            transferred: false,
+            irq: false,
+        }
+    }
+
+    fn side_count(&self) -> usize {
+        self.disk.len() / TRACK_LENGTH
+    }
+
+    fn insert(&mut self, side: Option<usize>) {
+        self.side = None;
+        self.next_side = side;
+        self.swap_delay = match side {
+            Some(_) => DISK_SWAP_CYCLES,
+            None => 0,
+        };
+    }
+
+    /// Writes $4025.
+    fn write_control(&mut self, value: u8) {
+        self.motor_on = value & 0x01 != 0;
+        self.transfer_reset = value & 0x02 != 0;
+        self.read_mode = value & 0x04 != 0;
+        self.crc_control = value & 0x10 != 0;
+        self.ready = value & 0x40 != 0;
+        self.irq_enabled = value & 0x80 != 0;
+        self.irq = false;
+    }
+
+    /// Reads $4032.
+    fn status(&self) -> u8 {
+        let missing = self.side.is_none();
+        // a missing disk is also not ready and write protected
+        missing as u8 | ((missing || !self.scanning) as u8) << 1 | (missing as u8) << 2 | 0x40
+    }
+
+    /// Runs one CPU cycle.
+    fn tick(&mut self) {
+        if self.swap_delay > 0 {
+            self.swap_delay -= 1;
+            if self.swap_delay == 0 {
+                self.side = self.next_side;
+            }
+        }
+
+        let side = match self.side {
+            Some(side) if self.motor_on => side,
+            _ => {
+                self.end_of_head = true;
+                self.scanning = false;
+                return;
+            }
+        };
+        if self.transfer_reset && !self.scanning {
+            return;
+        }
+        if self.end_of_head {
+            self.end_of_head = false;
+            self.delay = REWIND_CYCLES;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        let track = &mut self.disk[side * TRACK_LENGTH..(side + 1) * TRACK_LENGTH];
+        if self.read_mode {
+            let byte = track[self.position];
+            let mut irq = self.irq_enabled;
+            if !self.ready {
+                self.gap_ended = false;
+            } else if byte != 0 && !self.gap_ended {
+                // the start mark ends the gap, but doesn't raise an IRQ
+                self.gap_ended = true;
+                irq = false;
+            }
+            if self.gap_ended {
+                self.transferred = true;
+                self.read_data = byte;
+                self.irq |= irq;
+            }
+        } else {
+            let mut byte = 0;
+            if !self.crc_control {
+                self.transferred = true;
+                byte = self.write_data;
+                self.irq |= self.irq_enabled;
+            }
+            if !self.ready {
+                byte = 0;
+                self.crc = 0;
+            }
+            if !self.crc_control {
+                self.crc = crc_step(self.crc, byte);
+            } else {
+                if !self.previous_crc_control {
+                    self.crc = crc_step(crc_step(self.crc, 0), 0);
+                }
+                byte = self.crc as u8;
+                self.crc >>= 8;
+            }
+            // the write head is two bytes behind the read head
+            if let Some(position) = self.position.checked_sub(2) {
+                track[position] = byte;
+            }
+            self.gap_ended = false;
+        }
+        self.previous_crc_control = self.crc_control;
+
+        self.position += 1;
+        if self.position == TRACK_LENGTH {
+            self.motor_on = false;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+}
+
+impl SaveState for DiskDrive {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.disk);
+        state.write_bool(self.side.is_some());
+        state.write_u8(self.side.unwrap_or(0) as u8);
+        state.write_bool(self.next_side.is_some());
+        state.write_u8(self.next_side.unwrap_or(0) as u8);
+        state.write_u32(self.swap_delay);
+        state.write_bool(self.motor_on);
+        state.write_bool(self.transfer_reset);
+        state.write_bool(self.read_mode);
+        state.write_bool(self.crc_control);
+        state.write_bool(self.previous_crc_control);
+        state.write_bool(self.ready);
+        state.write_bool(self.irq_enabled);
+        state.write_u32(self.position as u32);
+        state.write_u32(self.delay);
+        state.write_bool(self.scanning);
+        state.write_bool(self.end_of_head);
+        state.write_bool(self.gap_ended);
+        state.write_u16(self.crc);
+        state.write_u8(self.read_data);
+        state.write_u8(self.write_data);
+        state.write_bool(self.transferred);
+        state.write_bool(self.irq);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        state.read_bytes(&mut self.disk)?;
+        let inserted = state.read_bool()?;
+        let side = state.read_u8()? as usize;
+        let next_inserted = state.read_bool()?;
+        let next_side = state.read_u8()? as usize;
+        if side >= self.side_count() || next_side >= self.side_count() {
+            return Err("Save state: Invalid mapper 020 state");
+        }
+        self.side = inserted.then_some(side);
+        self.next_side = next_inserted.then_some(next_side);
+        self.swap_delay = state.read_u32()?;
+        self.motor_on = state.read_bool()?;
+        self.transfer_reset = state.read_bool()?;
+        self.read_mode = state.read_bool()?;
+        self.crc_control = state.read_bool()?;
+        self.previous_crc_control = state.read_bool()?;
+        self.ready = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.position = state.read_u32()? as usize;
+        if self.position >= TRACK_LENGTH {
+            return Err("Save state: Invalid mapper 020 state");
+        }
+        self.delay = state.read_u32()?;
+        self.scanning = state.read_bool()?;
+        self.end_of_head = state.read_bool()?;
+        self.gap_ended = state.read_bool()?;
+        self.crc = state.read_u16()?;
+        self.read_data = state.read_u8()?;
+        self.write_data = state.read_u8()?;
+        self.transferred = state.read_bool()?;
+        self.irq = state.read_bool()?;
+        Ok(())
+    }
+}
+
+/// Famicom Disk System RAM adapter. Games load from disk into 32 KiB of PRG
+/// RAM and 8 KiB of CHR RAM with the help of the BIOS at $E000-$FFFF.
+pub struct Mapper020 {
+    bios: Vec<u8>,
+    ram: Ram<{ 32 * 1024 }>,
+    chr_ram: Ram<{ 8 * 1024 }>,
+    disk_registers_enabled: bool,
+    sound_registers_enabled: bool,
+    mirroring_horizontal: bool,
+    irq_reload: u16,
+    irq_counter: u16,
+    irq_repeat: bool,
+    irq_enabled: bool,
+    timer_irq: bool,
+    drive: DiskDrive,
+    audio: FdsAudio,
+}
+
+impl Mapper020 {
+    pub fn new(disk: &DiskFile, bios: &[u8]) -> Result<Self, &'static str> {
+        if bios.len() != 8 * 1024 {
+            return Err("Mapper 020: Unexpected bios size");
+        }
+
+        let tracks = (0..disk.side_count())
+            .flat_map(|side| track_from_side(disk.side(side)))
+            .collect();
+
+        Ok(Self {
+            bios: bios.to_vec(),
+            ram: Ram::new(),
+            chr_ram: Ram::new(),
+            disk_registers_enabled: false,
+            sound_registers_enabled: false,
+            mirroring_horizontal: false,
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_repeat: false,
+            irq_enabled: false,
+            timer_irq: false,
+            drive: DiskDrive::new(tracks),
+            audio: FdsAudio::new(),
+        })
+    }
+}
+
+impl Mapper for Mapper020 {
+    fn from_game(_game: GameFile) -> Result<Self, &'static str> {
+        Err("Mapper 020: Disk System games need a disk image")
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        matches!(address, 0x4020..=0x409F | 0x6000..=0xFFFF)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x4030 => {
+                let status = self.timer_irq as u8
+                    | (self.drive.transferred as u8) << 1
+                    | (self.drive.end_of_head as u8) << 6;
+                self.timer_irq = false;
+                self.drive.transferred = false;
+                self.drive.irq = false;
+                status
+            }
+            0x4031 => {
+                self.drive.transferred = false;
+                self.drive.irq = false;
+                self.drive.read_data
+            }
+            0x4032 => self.drive.status(),
+            // battery good
+            0x4033 => 0x80,
+            0x4040..=0x409F => self.audio.read(address),
+            // write-only registers, the bus keeps the high address byte
+            0x4020..=0x403F => 0x40,
+            0x6000..=0xDFFF => self.ram.read(address as usize - 0x6000),
+            0xE000..=0xFFFF => self.bios[address as usize & 0x1FFF],
+            _ => panic!("Mapper 020: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4020 => self.irq_reload = (self.irq_reload & 0xFF00) | byte as u16,
+            0x4021 => self.irq_reload = (self.irq_reload & 0x00FF) | (byte as u16) << 8,
+            0x4022 => {
+                self.irq_repeat = byte & 0x01 != 0;
+                self.irq_enabled = byte & 0x02 != 0 && self.disk_registers_enabled;
+                if self.irq_enabled {
+                    self.irq_counter = self.irq_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4023 => {
+                self.disk_registers_enabled = byte & 0x01 != 0;
+                self.sound_registers_enabled = byte & 0x02 != 0;
+                if !self.disk_registers_enabled {
+                    self.irq_enabled = false;
+                    self.timer_irq = false;
+                    self.drive.irq = false;
+                }
+            }
+            0x4024 | 0x4025 if !self.disk_registers_enabled => {}
+            0x4024 => {
+                self.drive.write_data = byte;
+                self.drive.transferred = false;
+                self.drive.irq = false;
+            }
+            0x4025 => {
+                self.mirroring_horizontal = byte & 0x08 != 0;
+                self.drive.write_control(byte);
+            }
+            0x4026..=0x403F => {}
+            0x4040..=0x409F if self.sound_registers_enabled => self.audio.write(address, byte),
+            0x4040..=0x409F => {}
+            0x6000..=0xDFFF => self.ram.write(address as usize - 0x6000, byte),
+            0xE000..=0xFFFF => {}
+            _ => panic!("Mapper 020: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.chr_ram.read(address as usize),
+            _ => panic!("Mapper 020: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.chr_ram.write(address as usize, byte),
+            _ => panic!("Mapper 020: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        if self.mirroring_horizontal {
+            Mirroring::Horizontal.nametable_source(address)
+        } else {
+            Mirroring::Vertical.nametable_source(address)
+        }
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        if self.irq_enabled {
+            if self.irq_counter == 0 {
+                self.timer_irq = true;
+                self.irq_counter = self.irq_reload;
+                self.irq_enabled = self.irq_repeat;
+            } else {
+                self.irq_counter -= 1;
+            }
+        }
+        self.drive.tick();
+        self.audio.tick();
+
+        if self.timer_irq || self.drive.irq {
+            cpu.irq();
+        }
+    }
+
+    fn audio_output(&self) -> i32 {
+        self.audio.output()
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        // sides count from 1, 0 is no disk
+        let side = self.drive.side.map_or(0, |side| side + 1);
+        vec![
+            ("disk_side", DebugValue::Dec(side as u64)),
+            ("motor_on", DebugValue::Dec(self.drive.motor_on as u64)),
+            ("read_mode", DebugValue::Dec(self.drive.read_mode as u64)),
+            (
+                "position",
+                DebugValue::U16Hex((self.drive.position >> 1) as u16),
+            ),
+            ("irq_reload", DebugValue::U16Hex(self.irq_reload)),
+            ("irq_counter", DebugValue::U16Hex(self.irq_counter)),
+            ("irq_enabled", DebugValue::Dec(self.irq_enabled as u64)),
+        ]
+    }
+
+    /// Contents of all disk sides, so disk writes are kept like battery-backed
+    /// RAM.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        Some(&self.drive.disk)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.drive.disk)
+    }
+
+    fn disk_side_count(&self) -> usize {
+        self.drive.side_count()
+    }
+
+    fn disk_side(&self) -> Option<usize> {
+        self.drive.side
+    }
+
+    fn insert_disk_side(&mut self, side: Option<usize>) {
+        self.drive.insert(side);
+    }
+}
+
+impl SaveState for Mapper020 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        self.chr_ram.save_state(state);
+        state.write_bool(self.disk_registers_enabled);
+        state.write_bool(self.sound_registers_enabled);
+        state.write_bool(self.mirroring_horizontal);
+        state.write_u16(self.irq_reload);
+        state.write_u16(self.irq_counter);
+        state.write_bool(self.irq_repeat);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.timer_irq);
+        self.drive.save_state(state);
+        self.audio.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.chr_ram.load_state(state)?;
+        self.disk_registers_enabled = state.read_bool()?;
+        self.sound_registers_enabled = state.read_bool()?;
+        self.mirroring_horizontal = state.read_bool()?;
+        self.irq_reload = state.read_u16()?;
+        self.irq_counter = state.read_u16()?;
+        self.irq_repeat = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.timer_irq = state.read_bool()?;
+        self.drive.load_state(state)?;
+        self.audio.load_state(state)
+    }
+}
@@ -0,0 +1,278 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::vrc_irq::VrcIrq;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Chip {
+    Vrc2,
+    Vrc4,
+}
+
+/// Konami VRC2 and VRC4 (mappers 21, 22, 23 and 25). Boards connect the
+/// chip's A0 and A1 inputs to different CPU address lines.
+pub struct Mapper021 {
+    game: GameFile,
+    ram: Option<Ram<{ 8 * 1024 }>>,
+    chip: Chip,
+    /// CPU address lines connected to the chip's A0 and A1.
+    a0_lines: u16,
+    a1_lines: u16,
+    /// VRC2a ignores the lowest bit of CHR banks.
+    chr_shift: u8,
+    prg_banks: [u8; 2],
+    chr_banks: [u16; 8],
+    mirroring: u8,
+    prg_swap_mode: bool,
+    /// Bit of the VRC2's serial EEPROM interface, readable on boards without RAM.
+    microwire_latch: u8,
+    irq: VrcIrq,
+}
+
+impl Mapper021 {
+    /// Reduces a CPU address to $x000-$x003 as seen by the chip.
+    fn register(&self, address: u16) -> u16 {
+        let a0 = (address & self.a0_lines != 0) as u16;
+        let a1 = (address & self.a1_lines != 0) as u16;
+        (address & 0xF000) | a1 << 1 | a0
+    }
+
+    fn microwire_mapped(&self, address: u16) -> bool {
+        self.chip == Chip::Vrc2 && self.ram.is_none() && (0x6000..=0x6FFF).contains(&address)
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        let bank = self.chr_banks[(address as usize >> 10) & 0b111] >> self.chr_shift;
+        (bank as usize) << 10 | (address as usize & 0x03FF)
+    }
+}
+
+impl Mapper for Mapper021 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.chr_rom().is_none() {
+            return Err("Mapper 021: Missing chr rom");
+        }
+
+        // iNES 1.0 files don't tell the boards apart, so listen on the address
+        // lines of all of them. VRC2 and VRC4 registers overlap well enough to
+        // run VRC2 games as VRC4, except mapper 22 which only VRC2a uses.
+        let (chip, a0_lines, a1_lines) = match (game.mapper, game.submapper) {
+            (21, Some(1)) => (Chip::Vrc4, 0x02, 0x04),
+            (21, Some(2)) => (Chip::Vrc4, 0x40, 0x80),
+            (21, _) => (Chip::Vrc4, 0x42, 0x84),
+            (22, _) => (Chip::Vrc2, 0x02, 0x01),
+            (23, Some(1)) => (Chip::Vrc4, 0x01, 0x02),
+            (23, Some(2)) => (Chip::Vrc4, 0x04, 0x08),
+            (23, Some(3)) => (Chip::Vrc2, 0x01, 0x02),
+            (23, _) => (Chip::Vrc4, 0x05, 0x0A),
+            (25, Some(1)) => (Chip::Vrc4, 0x02, 0x01),
+            (25, Some(2)) => (Chip::Vrc4, 0x08, 0x04),
+            (25, Some(3)) => (Chip::Vrc2, 0x02, 0x01),
+            (25, _) => (Chip::Vrc4, 0x0A, 0x05),
+            _ => return Err("Mapper 021: Unexpected mapper number"),
+        };
+
+        // Only Nes 2.0 can tell us if ram is present. For other formats assume
+        // present, except on VRC2a boards which never have it.
+        let ram_present = match game.format {
+            FileFormat::Nes20 => game.prg_ram_size.is_some() || game.prg_nvram_size.is_some(),
+            _ => game.mapper != 22,
+        };
+
+        Ok(Self {
+            ram: ram_present.then(Ram::new),
+            chip,
+            a0_lines,
+            a1_lines,
+            chr_shift: (game.mapper == 22) as u8,
+            prg_banks: [0; 2],
+            chr_banks: [0; 8],
+            mirroring: 0,
+            prg_swap_mode: false,
+            microwire_latch: 0,
+            irq: VrcIrq::new(),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x6000..=0x7FFF => self.ram.is_some() || self.microwire_mapped(address),
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        let offset = address as usize & 0x1FFF;
+        let second_last = (prg_rom.len() >> 13) - 2;
+        match address {
+            // upper bits come from open bus, which holds the high address byte
+            0x6000..=0x6FFF if self.microwire_mapped(address) => 0x60 | self.microwire_latch,
+            0x6000..=0x7FFF => match &self.ram {
+                Some(ram) => ram.read(offset),
+                None => 0,
+            },
+            0x8000..=0xDFFF => {
+                let bank = match ((address - 0x8000) >> 13, self.prg_swap_mode) {
+                    (0, false) | (2, true) => (self.prg_banks[0] & 0x1F) as usize,
+                    (1, _) => (self.prg_banks[1] & 0x1F) as usize,
+                    _ => second_last,
+                };
+                prg_rom[(bank << 13 | offset) & (prg_rom.len() - 1)]
+            }
+            0xE000..=0xFFFF => prg_rom[prg_rom.len() - 0x2000 + offset],
+            _ => panic!("Mapper 021: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        if address < 0x8000 {
+            if self.microwire_mapped(address) {
+                self.microwire_latch = byte & 1;
+            } else if let Some(ram) = &mut self.ram {
+                ram.write(address as usize & 0x1FFF, byte);
+            }
+            return;
+        }
+
+        let chr_high_mask = match self.chip {
+            Chip::Vrc2 => 0x0F,
+            Chip::Vrc4 => 0x1F,
+        };
+        match (self.register(address), self.chip) {
+            (0x8000..=0x8003, _) => self.prg_banks[0] = byte,
+            (0x9000..=0x9003, Chip::Vrc2) => self.mirroring = byte & 0b01,
+            (0x9000 | 0x9001, Chip::Vrc4) => self.mirroring = byte & 0b11,
+            (0x9002, Chip::Vrc4) => self.prg_swap_mode = byte & 0b10 != 0,
+            (0x9003, Chip::Vrc4) => {}
+            (0xA000..=0xA003, _) => self.prg_banks[1] = byte,
+            (register @ 0xB000..=0xE003, _) => {
+                let index = ((register - 0xB000) >> 11) as usize | (register as usize & 0b10) >> 1;
+                let bank = &mut self.chr_banks[index];
+                *bank = match register & 1 {
+                    0 => (*bank & 0x1F0) | (byte as u16 & 0x0F),
+                    _ => (*bank & 0x00F) | (byte as u16 & chr_high_mask) << 4,
+                };
+            }
+            (0xF000, Chip::Vrc4) => self.irq.latch = (self.irq.latch & 0xF0) | (byte & 0x0F),
+            (0xF001, Chip::Vrc4) => self.irq.latch = (self.irq.latch & 0x0F) | (byte << 4),
+            (0xF002, Chip::Vrc4) => self.irq.write_control(byte),
+            (0xF003, Chip::Vrc4) => self.irq.acknowledge(),
+            (0xF000..=0xF003, Chip::Vrc2) => {}
+            _ => panic!("Mapper 021: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                chr_rom[self.chr_index(address) & (chr_rom.len() - 1)]
+            }
+            _ => panic!("Mapper 021: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, _byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                eprintln!("Mapper 021: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 021: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let mirroring = match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreen(0),
+            _ => Mirroring::SingleScreen(1),
+        };
+        mirroring.nametable_source(address)
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        self.irq.tick(cpu);
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("vrc4", DebugValue::Dec((self.chip == Chip::Vrc4) as u64)),
+            ("ram", DebugValue::Dec(self.ram.is_some() as u64)),
+            ("prg_bank_0", DebugValue::U8Hex(self.prg_banks[0])),
+            ("prg_bank_1", DebugValue::U8Hex(self.prg_banks[1])),
+            ("prg_swap_mode", DebugValue::Dec(self.prg_swap_mode as u64)),
+            ("chr_bank_0000", DebugValue::U16Hex(self.chr_banks[0])),
+            ("chr_bank_0400", DebugValue::U16Hex(self.chr_banks[1])),
+            ("chr_bank_0800", DebugValue::U16Hex(self.chr_banks[2])),
+            ("chr_bank_0c00", DebugValue::U16Hex(self.chr_banks[3])),
+            ("chr_bank_1000", DebugValue::U16Hex(self.chr_banks[4])),
+            ("chr_bank_1400", DebugValue::U16Hex(self.chr_banks[5])),
+            ("chr_bank_1800", DebugValue::U16Hex(self.chr_banks[6])),
+            ("chr_bank_1c00", DebugValue::U16Hex(self.chr_banks[7])),
+            ("mirroring", DebugValue::Dec(self.mirroring as u64)),
+            ("irq_latch", DebugValue::U8Hex(self.irq.latch)),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper021 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        state.write_bytes(&self.prg_banks);
+        for bank in self.chr_banks {
+            state.write_u16(bank);
+        }
+        state.write_u8(self.mirroring);
+        state.write_bool(self.prg_swap_mode);
+        state.write_u8(self.microwire_latch);
+        self.irq.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        state.read_bytes(&mut self.prg_banks)?;
+        for bank in &mut self.chr_banks {
+            *bank = state.read_u16()?;
+        }
+        self.mirroring = state.read_u8()?;
+        self.prg_swap_mode = state.read_bool()?;
+        self.microwire_latch = state.read_u8()?;
+        self.irq.load_state(state)
+    }
+}
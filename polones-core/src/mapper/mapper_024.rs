@@ -0,0 +1,230 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::vrc6_audio::Vrc6Audio;
+use super::vrc_irq::VrcIrq;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Konami VRC6. Mapper 26 boards have PRG A0 and A1 lines swapped.
+pub struct Mapper024 {
+    game: GameFile,
+    ram: Option<Ram<{ 8 * 1024 }>>,
+    swap_address_lines: bool,
+    prg_bank_16k: u8,
+    prg_bank_8k: u8,
+    chr_banks: [u8; 8],
+    ppu_banking_mode: u8,
+    irq: VrcIrq,
+    audio: Vrc6Audio,
+}
+
+impl Mapper024 {
+    fn ram_enabled(&self) -> bool {
+        self.ppu_banking_mode & 0x80 != 0
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        let register = (address >> 10) as usize;
+        let (bank, large) = match (self.ppu_banking_mode & 0b11, register) {
+            (0, _) | (2 | 3, 0..=3) => (self.chr_banks[register], false),
+            (1, _) => (self.chr_banks[register >> 1], true),
+            (_, _) => (self.chr_banks[4 + ((register - 4) >> 1)], true),
+        };
+        // 2 KiB banks take A10 from the PPU or from the register
+        let bank = match large && self.ppu_banking_mode & 0x20 != 0 {
+            true => (bank as usize & !1) | (register & 1),
+            false => bank as usize,
+        };
+        bank << 10 | (address as usize & 0x03FF)
+    }
+}
+
+impl Mapper for Mapper024 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.chr_rom().is_none() {
+            return Err("Mapper 024: Missing chr rom");
+        }
+
+        Ok(Self {
+            // Only Nes 2.0 can tell us if ram is present. For other formats assume present.
+            ram: (game.format != FileFormat::Nes20
+                || game.prg_ram_size.is_some()
+                || game.prg_nvram_size.is_some())
+            .then(Ram::new),
+            swap_address_lines: game.mapper == 26,
+            prg_bank_16k: 0,
+            prg_bank_8k: 0,
+            chr_banks: [0; 8],
+            ppu_banking_mode: 0,
+            irq: VrcIrq::new(),
+            audio: Vrc6Audio::new(),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x6000..=0x7FFF => self.ram.is_some(),
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        let mask = prg_rom.len() - 1;
+        match address {
+            0x6000..=0x7FFF => match &self.ram {
+                Some(ram) if self.ram_enabled() => ram.read((address - 0x6000) as usize),
+                _ => 0,
+            },
+            0x8000..=0xBFFF => {
+                let bank = (self.prg_bank_16k & 0x0F) as usize;
+                prg_rom[(bank << 14 | (address as usize & 0x3FFF)) & mask]
+            }
+            0xC000..=0xDFFF => {
+                let bank = (self.prg_bank_8k & 0x1F) as usize;
+                prg_rom[(bank << 13 | (address as usize & 0x1FFF)) & mask]
+            }
+            0xE000..=0xFFFF => prg_rom[prg_rom.len() - 0x2000 + (address as usize & 0x1FFF)],
+            _ => panic!("Mapper 024: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        let address = if self.swap_address_lines && address >= 0x8000 {
+            (address & !0b11) | (address & 0b01) << 1 | (address & 0b10) >> 1
+        } else {
+            address
+        };
+        let register = address & 0b11;
+        match address & 0xF003 {
+            0x6000..=0x7FFF => {
+                let enabled = self.ram_enabled();
+                if let (Some(ram), true) = (&mut self.ram, enabled) {
+                    ram.write((address - 0x6000) as usize, byte);
+                }
+            }
+            0x8000..=0x8003 => self.prg_bank_16k = byte,
+            0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002 => self.audio.write(address, byte),
+            0xB003 => self.ppu_banking_mode = byte,
+            0xC000..=0xC003 => self.prg_bank_8k = byte,
+            0xD000..=0xD003 => self.chr_banks[register as usize] = byte,
+            0xE000..=0xE003 => self.chr_banks[4 + register as usize] = byte,
+            0xF000 => self.irq.latch = byte,
+            0xF001 => self.irq.write_control(byte),
+            0xF002 => self.irq.acknowledge(),
+            0xA003 | 0xF003 => {}
+            _ => panic!("Mapper 024: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                chr_rom[self.chr_index(address) & (chr_rom.len() - 1)]
+            }
+            _ => panic!("Mapper 024: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, _byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                eprintln!("Mapper 024: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 024: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let mirroring = match (self.ppu_banking_mode >> 2) & 0b11 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreen(0),
+            _ => Mirroring::SingleScreen(1),
+        };
+        mirroring.nametable_source(address)
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        self.irq.tick(cpu);
+        self.audio.tick();
+    }
+
+    fn audio_output(&self) -> i32 {
+        self.audio.output()
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("ram", DebugValue::Dec(self.ram.is_some() as u64)),
+            ("prg_bank_16k", DebugValue::U8Hex(self.prg_bank_16k)),
+            ("prg_bank_8k", DebugValue::U8Hex(self.prg_bank_8k)),
+            ("ppu_banking_mode", DebugValue::U8Hex(self.ppu_banking_mode)),
+            ("r0", DebugValue::U8Hex(self.chr_banks[0])),
+            ("r1", DebugValue::U8Hex(self.chr_banks[1])),
+            ("r2", DebugValue::U8Hex(self.chr_banks[2])),
+            ("r3", DebugValue::U8Hex(self.chr_banks[3])),
+            ("r4", DebugValue::U8Hex(self.chr_banks[4])),
+            ("r5", DebugValue::U8Hex(self.chr_banks[5])),
+            ("r6", DebugValue::U8Hex(self.chr_banks[6])),
+            ("r7", DebugValue::U8Hex(self.chr_banks[7])),
+            ("irq_latch", DebugValue::U8Hex(self.irq.latch)),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper024 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        state.write_u8(self.prg_bank_16k);
+        state.write_u8(self.prg_bank_8k);
+        state.write_bytes(&self.chr_banks);
+        state.write_u8(self.ppu_banking_mode);
+        self.irq.save_state(state);
+        self.audio.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.prg_bank_16k = state.read_u8()?;
+        self.prg_bank_8k = state.read_u8()?;
+        state.read_bytes(&mut self.chr_banks)?;
+        self.ppu_banking_mode = state.read_u8()?;
+        self.irq.load_state(state)?;
+        self.audio.load_state(state)
+    }
+}
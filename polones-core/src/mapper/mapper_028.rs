@@ -0,0 +1,183 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::bank_index;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Action 53, the multicart board of the NESdev competitions. Writes to
+/// $5000-$5FFF select one of four registers, writes to $8000-$FFFF set it.
+/// The outer bank picks a game, the mode register makes the inner bank behave
+/// like NROM, BNROM, UNROM or the mapper 180 variant of it.
+pub struct Mapper028 {
+    game: GameFile,
+    /// 32 KiB of CHR-RAM if the game has no CHR-ROM.
+    chr_ram: Option<Ram<{ 32 * 1024 }>>,
+    /// Register written at $8000-$FFFF: $00, $01, $80 or $81.
+    selected: u8,
+    /// $00: ...M..CC CHR bank and one screen page.
+    chr_bank: u8,
+    /// $01: ...MPPPP inner PRG bank and one screen page.
+    inner_bank: u8,
+    /// $80: ..SSBBMM game size, PRG bank mode and mirroring.
+    mode: u8,
+    /// $81: outer 32 KiB PRG bank.
+    outer_bank: u8,
+}
+
+impl Mapper028 {
+    /// Returns the 16 KiB PRG bank at `address`.
+    fn prg_bank(&self, address: u16) -> usize {
+        let a14 = (address as usize >> 14) & 1;
+        let bank_mode = (self.mode as usize >> 2) & 0b11;
+        let outer = (self.outer_bank as usize) << 1;
+
+        // UNROM mode fixes $C000 and mode 2 fixes $8000 to the outer bank
+        if bank_mode & 0b10 != 0 && a14 == bank_mode & 1 {
+            return outer | a14;
+        }
+
+        let inner = if bank_mode & 0b10 == 0 {
+            (self.inner_bank as usize) << 1 | a14
+        } else {
+            self.inner_bank as usize
+        };
+        // the game size tells how many low bits come from the inner bank
+        let mask = (2 << ((self.mode >> 4) & 0b11)) - 1;
+        (outer & !mask) | (inner & mask)
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        (self.chr_bank as usize) << 13 | (address as usize & 0x1FFF)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mode & 0b11 {
+            0 => Mirroring::SingleScreen(0),
+            1 => Mirroring::SingleScreen(1),
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+}
+
+impl Mapper for Mapper028 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.prg_rom().is_empty() {
+            return Err("Mapper 028: Unexpected prg rom size");
+        }
+
+        Ok(Self {
+            chr_ram: game.chr_rom().is_none().then(Ram::new),
+            game,
+            selected: 0,
+            chr_bank: 0,
+            inner_bank: 0,
+            mode: 0,
+            // the menu starts from the last bank
+            outer_bank: 0xFF,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        matches!(address, 0x5000..=0x5FFF | 0x8000..=0xFFFF)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            // the register can't be read, the bus keeps the high address byte
+            0x5000..=0x5FFF => (address >> 8) as u8,
+            0x8000..=0xFFFF => {
+                let prg_rom = self.game.prg_rom();
+                prg_rom[bank_index(prg_rom, self.prg_bank(address), 0x4000, address)]
+            }
+            _ => panic!("Mapper 028: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x5000..=0x5FFF => self.selected = byte & 0x81,
+            0x8000..=0xFFFF => {
+                // in one screen modes CHR and PRG writes also select the screen
+                if self.selected & 0x80 == 0 && self.mode & 0b10 == 0 {
+                    self.mode = (self.mode & !1) | ((byte >> 4) & 1);
+                }
+                match self.selected {
+                    0x00 => self.chr_bank = byte & 0b11,
+                    0x01 => self.inner_bank = byte & 0x0F,
+                    0x80 => self.mode = byte & 0x3F,
+                    _ => self.outer_bank = byte,
+                }
+            }
+            _ => panic!("Mapper 028: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        (0x0000..=0x1FFF).contains(&address)
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => match (&self.chr_ram, self.game.chr_rom()) {
+                (Some(chr_ram), _) => chr_ram.read(self.chr_index(address)),
+                (None, Some(chr_rom)) => {
+                    chr_rom[bank_index(chr_rom, self.chr_bank as usize, 0x2000, address)]
+                }
+                (None, None) => unreachable!(),
+            },
+            _ => panic!("Mapper 028: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                let index = self.chr_index(address);
+                match &mut self.chr_ram {
+                    Some(chr_ram) => chr_ram.write(index, byte),
+                    None => eprintln!("Mapper 028: PPU write to {:04X} ignored.", address),
+                }
+            }
+            _ => panic!("Mapper 028: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        self.mirroring().nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("selected", DebugValue::U8Hex(self.selected)),
+            ("chr_bank", DebugValue::U8Hex(self.chr_bank)),
+            ("inner_bank", DebugValue::U8Hex(self.inner_bank)),
+            ("mode", DebugValue::U8Hex(self.mode)),
+            ("outer_bank", DebugValue::U8Hex(self.outer_bank)),
+        ]
+    }
+}
+
+impl SaveState for Mapper028 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.selected);
+        state.write_u8(self.chr_bank);
+        state.write_u8(self.inner_bank);
+        state.write_u8(self.mode);
+        state.write_u8(self.outer_bank);
+        self.chr_ram.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.selected = state.read_u8()?;
+        self.chr_bank = state.read_u8()?;
+        self.inner_bank = state.read_u8()?;
+        self.mode = state.read_u8()?;
+        self.outer_bank = state.read_u8()?;
+        self.chr_ram.load_state(state)
+    }
+}
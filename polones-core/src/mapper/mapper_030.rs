@@ -0,0 +1,172 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::bank_index;
+use super::flash::Flash;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// UNROM 512. 16 KiB PRG banks with the last bank fixed at $C000, 8 KiB banks
+/// of 32 KiB CHR-RAM. Boards with the battery flag have self-flashable PRG:
+/// writes to $8000-$BFFF go to the flash chip and the register moves to
+/// $C000-$FFFF.
+///
+/// The header's four-screen flag selects the nametable layout: with vertical
+/// mirroring nametables are in the last 8 KiB of CHR-RAM, with horizontal
+/// mirroring bit 7 of the register selects a single screen.
+pub struct Mapper030 {
+    game: GameFile,
+    /// PRG-ROM, changed by flash writes.
+    prg: Vec<u8>,
+    flash: Option<Flash>,
+    chr_ram: Ram<{ 32 * 1024 }>,
+    /// Number of 8 KiB CHR-RAM banks.
+    chr_banks: u8,
+    /// MCCPPPPP: one screen page, CHR bank, PRG bank.
+    register: u8,
+}
+
+impl Mapper030 {
+    fn prg_index(&self, address: u16) -> usize {
+        let bank = match address {
+            0x8000..=0xBFFF => self.register as usize & 0x1F,
+            _ => self.prg.len() / 0x4000 - 1,
+        };
+        bank_index(&self.prg, bank, 0x4000, address)
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        let bank = (self.register >> 5) & (self.chr_banks - 1);
+        (bank as usize) << 13 | (address as usize & 0x1FFF)
+    }
+}
+
+impl Mapper for Mapper030 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.prg_rom().len() < 0x4000 {
+            return Err("Mapper 030: Unexpected prg rom size");
+        }
+        if game.chr_rom().is_some() {
+            return Err("Mapper 030: Unexpected chr rom");
+        }
+        let chr_banks = match game.chr_ram_size {
+            Some(size @ (0x2000 | 0x4000 | 0x8000)) => (size / 0x2000) as u8,
+            Some(_) => return Err("Mapper 030: Unexpected chr ram size"),
+            None => 4,
+        };
+
+        Ok(Self {
+            prg: game.prg_rom().to_vec(),
+            flash: game.battery_present.then(Flash::new),
+            chr_ram: Ram::new(),
+            chr_banks,
+            register: 0,
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x8000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x8000..=0xFFFF => {
+                let index = self.prg_index(address);
+                match &self.flash {
+                    Some(flash) => flash.read(&self.prg, index),
+                    None => self.prg[index],
+                }
+            }
+            _ => panic!("Mapper 030: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x8000..=0xBFFF if self.flash.is_some() => {
+                let index = self.prg_index(address);
+                self.flash
+                    .as_mut()
+                    .unwrap()
+                    .write(&mut self.prg, index, byte);
+            }
+            0xC000..=0xFFFF if self.flash.is_some() => self.register = byte,
+            // boards without flash have bus conflicts
+            0x8000..=0xFFFF => self.register = byte & self.cpu_read(address),
+            _ => panic!("Mapper 030: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        (0x0000..=0x1FFF).contains(&address)
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.chr_ram.read(self.chr_index(address)),
+            // four-screen nametables
+            0x2000..=0x3EFF => self.chr_ram.read(0x6000 | (address as usize & 0x0FFF)),
+            _ => panic!("Mapper 030: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.chr_ram.write(self.chr_index(address), byte),
+            0x2000..=0x3EFF => self
+                .chr_ram
+                .write(0x6000 | (address as usize & 0x0FFF), byte),
+            _ => panic!("Mapper 030: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        match (self.game.four_screen_mode, self.game.mirroring_vertical) {
+            (true, true) => NametableSource::Mapper,
+            (true, false) => Mirroring::SingleScreen(self.register >> 7).nametable_source(address),
+            (false, _) => Mirroring::from_game(&self.game).nametable_source(address),
+        }
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("prg_bank", DebugValue::U8Hex(self.register & 0x1F)),
+            ("chr_bank", DebugValue::U8Hex((self.register >> 5) & 0b11)),
+            ("screen", DebugValue::U8Hex(self.register >> 7)),
+        ]
+    }
+
+    /// Flash contents, so flash writes are kept like battery-backed RAM.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.flash.as_ref().map(|_| &self.prg[..])
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.flash.as_ref().map(|_| &mut self.prg[..])
+    }
+}
+
+impl SaveState for Mapper030 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+        self.chr_ram.save_state(state);
+        self.flash.save_state(state);
+        if self.flash.is_some() {
+            state.write_bytes(&self.prg);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.register = state.read_u8()?;
+        self.chr_ram.load_state(state)?;
+        self.flash.load_state(state)?;
+        if self.flash.is_some() {
+            state.read_bytes(&mut self.prg)?;
+        }
+        Ok(())
+    }
+}
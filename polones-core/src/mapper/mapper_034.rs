@@ -0,0 +1,172 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, bus_conflicts, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// BNROM (submapper 2) switches 32 KiB PRG banks with writes to ROM. AVE
+/// NINA-001 (submapper 1) has registers at the end of PRG RAM, also switching
+/// two 4 KiB CHR banks.
+pub struct Mapper034 {
+    game: GameFile,
+    nina001: bool,
+    ram: Option<Ram<{ 8 * 1024 }>>,
+    chr: Chr,
+    prg_bank: u8,
+    chr_banks: [u8; 2],
+    bus_conflicts: bool,
+}
+
+impl Mapper for Mapper034 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        // iNES 1.0 files don't tell the boards apart, but only NINA-001 has
+        // more than 8 KiB of CHR
+        let nina001 = match game.submapper {
+            Some(1) => true,
+            Some(2) => false,
+            _ => game
+                .chr_rom()
+                .is_some_and(|chr_rom| chr_rom.len() > 8 * 1024),
+        };
+
+        // Only Nes 2.0 can tell us if ram is present. For other formats assume
+        // present on NINA-001.
+        let ram_present = match game.format {
+            FileFormat::Nes20 => game.prg_ram_size.is_some() || game.prg_nvram_size.is_some(),
+            _ => nina001,
+        };
+
+        Ok(Self {
+            nina001,
+            ram: ram_present.then(Ram::new),
+            chr: Chr::new(&game),
+            prg_bank: 0,
+            chr_banks: [0; 2],
+            bus_conflicts: bus_conflicts(&game, true),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x6000..=0x7FFF => self.ram.is_some() || self.nina001,
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x6000..=0x7FFF => match &self.ram {
+                Some(ram) => ram.read(address as usize & 0x1FFF),
+                None => 0,
+            },
+            0x8000..=0xFFFF => {
+                let prg_rom = self.game.prg_rom();
+                prg_rom[bank_index(prg_rom, self.prg_bank as usize, 0x8000, address)]
+            }
+            _ => panic!("Mapper 034: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        if let (0x6000..=0x7FFF, Some(ram)) = (address, &mut self.ram) {
+            ram.write(address as usize & 0x1FFF, byte);
+        }
+
+        match (address, self.nina001) {
+            (0x7FFD, true) => self.prg_bank = byte & 1,
+            (0x7FFE, true) => self.chr_banks[0] = byte & 0b1111,
+            (0x7FFF, true) => self.chr_banks[1] = byte & 0b1111,
+            (0x6000..=0x7FFF, _) | (0x8000..=0xFFFF, true) => {}
+            (0x8000..=0xFFFF, false) if self.bus_conflicts => {
+                self.prg_bank = byte & self.cpu_read(address);
+            }
+            (0x8000..=0xFFFF, false) => self.prg_bank = byte,
+            _ => panic!("Mapper 034: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF if self.nina001 => {
+                let bank = self.chr_banks[(address >> 12) as usize] as usize;
+                self.chr.read(&self.game, bank, 0x1000, address)
+            }
+            0x0000..=0x1FFF => self.chr.read(&self.game, 0, 0x2000, address),
+            _ => panic!("Mapper 034: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 034: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 034: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("nina001", DebugValue::Dec(self.nina001 as u64)),
+            ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            ("chr_bank_0", DebugValue::U8Hex(self.chr_banks[0])),
+            ("chr_bank_1", DebugValue::U8Hex(self.chr_banks[1])),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper034 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        self.chr.save_state(state);
+        state.write_u8(self.prg_bank);
+        state.write_bytes(&self.chr_banks);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        state.read_bytes(&mut self.chr_banks)
+    }
+}
@@ -0,0 +1,238 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::sunsoft_5b::Sunsoft5b;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Sunsoft FME-7 and 5B. Registers are written by selecting a command at
+/// $8000 and writing its parameter to $A000.
+pub struct Mapper069 {
+    game: GameFile,
+    ram: Option<Ram<{ 8 * 1024 }>>,
+    chr_ram: Option<Ram<{ 8 * 1024 }>>,
+    command: u8,
+    chr_banks: [u8; 8],
+    /// Banks at $6000, $8000, $A000 and $C000. Bank at $6000 also selects RAM.
+    prg_banks: [u8; 4],
+    mirroring: u8,
+    irq_control: u8,
+    irq_counter: u16,
+    irq_pending: bool,
+    audio: Sunsoft5b,
+}
+
+impl Mapper069 {
+    fn ram_selected(&self) -> bool {
+        self.prg_banks[0] & 0x40 != 0
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.prg_banks[0] & 0x80 != 0
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        (self.chr_banks[(address as usize >> 10) & 0b111] as usize) << 10
+            | (address as usize & 0x03FF)
+    }
+}
+
+impl Mapper for Mapper069 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            // Only Nes 2.0 can tell us if ram is present. For other formats assume present.
+            ram: (game.format != FileFormat::Nes20
+                || game.prg_ram_size.is_some()
+                || game.prg_nvram_size.is_some())
+            .then(Ram::new),
+            chr_ram: game.chr_rom().is_none().then(Ram::new),
+            command: 0,
+            chr_banks: [0; 8],
+            prg_banks: [0; 4],
+            mirroring: 0,
+            irq_control: 0,
+            irq_counter: 0,
+            irq_pending: false,
+            audio: Sunsoft5b::new(),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        address >= 0x6000
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        let offset = address as usize & 0x1FFF;
+        match address {
+            0x6000..=0x7FFF if self.ram_selected() => match &self.ram {
+                Some(ram) if self.ram_enabled() => ram.read(offset),
+                _ => 0,
+            },
+            0x6000..=0xDFFF => {
+                let bank = (self.prg_banks[(address as usize - 0x6000) >> 13] & 0x3F) as usize;
+                prg_rom[(bank << 13 | offset) & (prg_rom.len() - 1)]
+            }
+            0xE000..=0xFFFF => prg_rom[prg_rom.len() - 0x2000 + offset],
+            _ => panic!("Mapper 069: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => {
+                let enabled = self.ram_selected() && self.ram_enabled();
+                if let (Some(ram), true) = (&mut self.ram, enabled) {
+                    ram.write(address as usize & 0x1FFF, byte);
+                }
+            }
+            0x8000..=0x9FFF => self.command = byte & 0x0F,
+            0xA000..=0xBFFF => match self.command {
+                0x0..=0x7 => self.chr_banks[self.command as usize] = byte,
+                0x8..=0xB => self.prg_banks[self.command as usize - 0x8] = byte,
+                0xC => self.mirroring = byte & 0b11,
+                0xD => {
+                    self.irq_control = byte;
+                    self.irq_pending = false;
+                }
+                0xE => self.irq_counter = (self.irq_counter & 0xFF00) | byte as u16,
+                _ => self.irq_counter = (self.irq_counter & 0x00FF) | (byte as u16) << 8,
+            },
+            0xC000..=0xDFFF => self.audio.write_address(byte),
+            0xE000..=0xFFFF => self.audio.write_data(byte),
+            _ => panic!("Mapper 069: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let index = self.chr_index(address);
+        match (address, &self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) => chr_ram.read(index),
+            (0x0000..=0x1FFF, None) => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                chr_rom[index & (chr_rom.len() - 1)]
+            }
+            _ => panic!("Mapper 069: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        let index = self.chr_index(address);
+        match (address, &mut self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) => chr_ram.write(index, byte),
+            (0x0000..=0x1FFF, None) => {
+                eprintln!("Mapper 069: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 069: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let mirroring = match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreen(0),
+            _ => Mirroring::SingleScreen(1),
+        };
+        mirroring.nametable_source(address)
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        // bit 7 enables counting, bit 0 enables the IRQ
+        if self.irq_control & 0x80 != 0 {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_control & 0x01 != 0 {
+                self.irq_pending = true;
+            }
+        }
+        if self.irq_pending {
+            cpu.irq();
+        }
+        self.audio.tick();
+    }
+
+    fn audio_output(&self) -> i32 {
+        self.audio.output()
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("ram", DebugValue::Dec(self.ram.is_some() as u64)),
+            ("command", DebugValue::U8Hex(self.command)),
+            ("prg_bank_6000", DebugValue::U8Hex(self.prg_banks[0])),
+            ("prg_bank_8000", DebugValue::U8Hex(self.prg_banks[1])),
+            ("prg_bank_a000", DebugValue::U8Hex(self.prg_banks[2])),
+            ("prg_bank_c000", DebugValue::U8Hex(self.prg_banks[3])),
+            ("chr_bank_0000", DebugValue::U8Hex(self.chr_banks[0])),
+            ("chr_bank_0400", DebugValue::U8Hex(self.chr_banks[1])),
+            ("chr_bank_0800", DebugValue::U8Hex(self.chr_banks[2])),
+            ("chr_bank_0c00", DebugValue::U8Hex(self.chr_banks[3])),
+            ("chr_bank_1000", DebugValue::U8Hex(self.chr_banks[4])),
+            ("chr_bank_1400", DebugValue::U8Hex(self.chr_banks[5])),
+            ("chr_bank_1800", DebugValue::U8Hex(self.chr_banks[6])),
+            ("chr_bank_1c00", DebugValue::U8Hex(self.chr_banks[7])),
+            ("mirroring", DebugValue::Dec(self.mirroring as u64)),
+            ("irq_control", DebugValue::U8Hex(self.irq_control)),
+            ("irq_counter", DebugValue::U16Hex(self.irq_counter)),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper069 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        self.chr_ram.save_state(state);
+        state.write_u8(self.command);
+        state.write_bytes(&self.chr_banks);
+        state.write_bytes(&self.prg_banks);
+        state.write_u8(self.mirroring);
+        state.write_u8(self.irq_control);
+        state.write_u16(self.irq_counter);
+        state.write_bool(self.irq_pending);
+        self.audio.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.chr_ram.load_state(state)?;
+        self.command = state.read_u8()?;
+        state.read_bytes(&mut self.chr_banks)?;
+        state.read_bytes(&mut self.prg_banks)?;
+        self.mirroring = state.read_u8()?;
+        self.irq_control = state.read_u8()?;
+        self.irq_counter = state.read_u16()?;
+        self.irq_pending = state.read_bool()?;
+        self.audio.load_state(state)
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Camerica and Codemasters boards. Like UxROM with the register at
+/// $C000-$FFFF. The Fire Hawk board also selects a single-screen nametable
+/// at $8000-$9FFF.
+pub struct Mapper071 {
+    game: GameFile,
+    chr: Chr,
+    prg_bank: u8,
+    single_screen: Option<u8>,
+}
+
+impl Mapper for Mapper071 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            chr: Chr::new(&game),
+            prg_bank: 0,
+            single_screen: None,
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x8000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        match address {
+            0x8000..=0xBFFF => {
+                prg_rom[bank_index(prg_rom, self.prg_bank as usize, 0x4000, address)]
+            }
+            0xC000..=0xFFFF => prg_rom[prg_rom.len() - 0x4000 + (address as usize & 0x3FFF)],
+            _ => panic!("Mapper 071: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            // submapper 1 is Fire Hawk, iNES 1.0 files don't tell the boards
+            // apart but other games don't write there
+            0x8000..=0x9FFF if self.game.submapper != Some(0) => {
+                self.single_screen = Some((byte >> 4) & 1);
+            }
+            0x8000..=0xBFFF => {}
+            0xC000..=0xFFFF => self.prg_bank = byte & 0b1111,
+            _ => panic!("Mapper 071: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.chr.read(&self.game, 0, 0x2000, address),
+            _ => panic!("Mapper 071: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 071: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 071: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        match self.single_screen {
+            Some(page) => Mirroring::SingleScreen(page).nametable_source(address),
+            None => Mirroring::from_game(&self.game).nametable_source(address),
+        }
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            (
+                "single_screen",
+                DebugValue::Dec(self.single_screen.unwrap_or(0) as u64),
+            ),
+        ]
+    }
+}
+
+impl SaveState for Mapper071 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.chr.save_state(state);
+        state.write_u8(self.prg_bank);
+        state.write_bool(self.single_screen.is_some());
+        state.write_u8(self.single_screen.unwrap_or(0));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        let single_screen = state.read_bool()?;
+        let page = state.read_u8()?;
+        self.single_screen = single_screen.then_some(page);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// AVE NINA-03 and NINA-06. The register at $4100-$5FFF selects a 32 KiB PRG
+/// bank and an 8 KiB CHR bank.
+pub struct Mapper079 {
+    game: GameFile,
+    chr: Chr,
+    prg_bank: u8,
+    chr_bank: u8,
+}
+
+impl Mapper for Mapper079 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            chr: Chr::new(&game),
+            prg_bank: 0,
+            chr_bank: 0,
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            // the register is mirrored where address bit 8 is set
+            0x4100..=0x5FFF => address & 0x0100 != 0,
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            // the register can't be read, the bus keeps the high address byte
+            0x4100..=0x5FFF => (address >> 8) as u8,
+            0x8000..=0xFFFF => {
+                let prg_rom = self.game.prg_rom();
+                prg_rom[bank_index(prg_rom, self.prg_bank as usize, 0x8000, address)]
+            }
+            _ => panic!("Mapper 079: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4100..=0x5FFF => {
+                self.prg_bank = (byte >> 3) & 1;
+                self.chr_bank = byte & 0b111;
+            }
+            0x8000..=0xFFFF => {}
+            _ => panic!("Mapper 079: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => {
+                let bank = self.chr_bank as usize;
+                self.chr.read(&self.game, bank, 0x2000, address)
+            }
+            _ => panic!("Mapper 079: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 079: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 079: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("prg_bank", DebugValue::U8Hex(self.prg_bank)),
+            ("chr_bank", DebugValue::U8Hex(self.chr_bank)),
+        ]
+    }
+}
+
+impl SaveState for Mapper079 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.chr.save_state(state);
+        state.write_u8(self.prg_bank);
+        state.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        self.chr_bank = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use crate::cpu::Cpu;
+use crate::game_file::{FileFormat, GameFile};
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::opll::{Opll, OPLL_MIX_LEVEL};
+use super::vrc_irq::VrcIrq;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Konami VRC7. VRC7a boards decode registers with A4, VRC7b boards with A3.
+pub struct Mapper085 {
+    game: GameFile,
+    ram: Option<Ram<{ 8 * 1024 }>>,
+    chr_ram: Option<Ram<{ 8 * 1024 }>>,
+    /// Address bits telling apart the two registers at each $x000 address.
+    register_select_mask: u16,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    control: u8,
+    irq: VrcIrq,
+    opll: Opll,
+}
+
+impl Mapper085 {
+    fn ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        (self.chr_banks[(address as usize >> 10) & 0b111] as usize) << 10
+            | (address as usize & 0x03FF)
+    }
+
+    fn audio_silenced(&self) -> bool {
+        self.control & 0x40 != 0
+    }
+}
+
+impl Mapper for Mapper085 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        let register_select_mask = match game.submapper {
+            Some(1) => 0x08,
+            Some(2) => 0x10,
+            // unknown boards, accept both
+            _ => 0x18,
+        };
+
+        Ok(Self {
+            // Only Nes 2.0 can tell us if ram is present. For other formats assume present.
+            ram: (game.format != FileFormat::Nes20
+                || game.prg_ram_size.is_some()
+                || game.prg_nvram_size.is_some())
+            .then(Ram::new),
+            chr_ram: game.chr_rom().is_none().then(Ram::new),
+            register_select_mask,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            control: 0,
+            irq: VrcIrq::new(),
+            opll: Opll::new(),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x6000..=0x7FFF => self.ram.is_some(),
+            0x8000..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        let offset = address as usize & 0x1FFF;
+        match address {
+            0x6000..=0x7FFF => match &self.ram {
+                Some(ram) if self.ram_enabled() => ram.read(offset),
+                _ => 0,
+            },
+            0x8000..=0xDFFF => {
+                let bank = (self.prg_banks[(address as usize - 0x8000) >> 13] & 0x3F) as usize;
+                prg_rom[(bank << 13 | offset) & (prg_rom.len() - 1)]
+            }
+            0xE000..=0xFFFF => prg_rom[prg_rom.len() - 0x2000 + offset],
+            _ => panic!("Mapper 085: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        // $x000 or $x010/$x008
+        let second = address & self.register_select_mask != 0;
+        match (address & 0xF000, second) {
+            (0x6000 | 0x7000, _) => {
+                let enabled = self.ram_enabled();
+                if let (Some(ram), true) = (&mut self.ram, enabled) {
+                    ram.write(address as usize & 0x1FFF, byte);
+                }
+            }
+            (0x8000, false) => self.prg_banks[0] = byte,
+            (0x8000, true) => self.prg_banks[1] = byte,
+            (0x9000, false) => self.prg_banks[2] = byte,
+            // the sound chip sits at $9010 and $9030
+            (0x9000, true) if address & 0x20 == 0 => self.opll.write_address(byte),
+            (0x9000, true) => self.opll.write_data(byte),
+            (0xA000..=0xD000, _) => {
+                let register = ((address - 0xA000) >> 11) as usize | second as usize;
+                self.chr_banks[register] = byte;
+            }
+            (0xE000, false) => {
+                self.control = byte;
+                if self.audio_silenced() {
+                    self.opll = Opll::new();
+                }
+            }
+            (0xE000, true) => self.irq.latch = byte,
+            (0xF000, false) => self.irq.write_control(byte),
+            (0xF000, true) => self.irq.acknowledge(),
+            _ => panic!("Mapper 085: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let index = self.chr_index(address);
+        match (address, &self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) => chr_ram.read(index),
+            (0x0000..=0x1FFF, None) => {
+                let chr_rom = self.game.chr_rom().unwrap();
+                chr_rom[index & (chr_rom.len() - 1)]
+            }
+            _ => panic!("Mapper 085: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        let index = self.chr_index(address);
+        match (address, &mut self.chr_ram) {
+            (0x0000..=0x1FFF, Some(chr_ram)) => chr_ram.write(index, byte),
+            (0x0000..=0x1FFF, None) => {
+                eprintln!("Mapper 085: PPU write to {:04X} ignored.", address);
+            }
+            _ => panic!("Mapper 085: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        let mirroring = match self.control & 0b11 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreen(0),
+            _ => Mirroring::SingleScreen(1),
+        };
+        mirroring.nametable_source(address)
+    }
+
+    fn tick(&mut self, cpu: &mut Cpu) {
+        self.irq.tick(cpu);
+        if !self.audio_silenced() {
+            self.opll.tick();
+        }
+    }
+
+    fn audio_output(&self) -> i32 {
+        (self.opll.output() * OPLL_MIX_LEVEL) as i32
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("mapper", DebugValue::Dec(self.game.mapper as u64)),
+            ("ram", DebugValue::Dec(self.ram.is_some() as u64)),
+            ("control", DebugValue::U8Hex(self.control)),
+            ("prg_bank_8000", DebugValue::U8Hex(self.prg_banks[0])),
+            ("prg_bank_a000", DebugValue::U8Hex(self.prg_banks[1])),
+            ("prg_bank_c000", DebugValue::U8Hex(self.prg_banks[2])),
+            ("r0", DebugValue::U8Hex(self.chr_banks[0])),
+            ("r1", DebugValue::U8Hex(self.chr_banks[1])),
+            ("r2", DebugValue::U8Hex(self.chr_banks[2])),
+            ("r3", DebugValue::U8Hex(self.chr_banks[3])),
+            ("r4", DebugValue::U8Hex(self.chr_banks[4])),
+            ("r5", DebugValue::U8Hex(self.chr_banks[5])),
+            ("r6", DebugValue::U8Hex(self.chr_banks[6])),
+            ("r7", DebugValue::U8Hex(self.chr_banks[7])),
+            ("irq_latch", DebugValue::U8Hex(self.irq.latch)),
+        ]
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match &self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) if self.game.battery_present => Some(ram.as_mut_slice()),
+            _ => None,
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) -> bool {
+        match &mut self.ram {
+            Some(ram) => {
+                ram.as_mut_slice()[0x1000..0x1200].copy_from_slice(trainer);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SaveState for Mapper085 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.ram.save_state(state);
+        self.chr_ram.save_state(state);
+        state.write_bytes(&self.prg_banks);
+        state.write_bytes(&self.chr_banks);
+        state.write_u8(self.control);
+        self.irq.save_state(state);
+        self.opll.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.ram.load_state(state)?;
+        self.chr_ram.load_state(state)?;
+        state.read_bytes(&mut self.prg_banks)?;
+        state.read_bytes(&mut self.chr_banks)?;
+        self.control = state.read_u8()?;
+        self.irq.load_state(state)?;
+        self.opll.load_state(state)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Jaleco JF-xx, Konami and Taito boards with a CHR bank register at
+/// $6000-$7FFF. The two register bits are wired in reverse order.
+pub struct Mapper087 {
+    game: GameFile,
+    chr: Chr,
+    chr_bank: u8,
+}
+
+impl Mapper for Mapper087 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            chr: Chr::new(&game),
+            chr_bank: 0,
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x6000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            // the register can't be read, the bus keeps the high address byte
+            0x6000..=0x7FFF => (address >> 8) as u8,
+            0x8000..=0xFFFF => {
+                let prg_rom = self.game.prg_rom();
+                prg_rom[bank_index(prg_rom, 0, 0x8000, address)]
+            }
+            _ => panic!("Mapper 087: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x6000..=0x7FFF => self.chr_bank = (byte & 0b01) << 1 | (byte & 0b10) >> 1,
+            0x8000..=0xFFFF => {}
+            _ => panic!("Mapper 087: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => {
+                let bank = self.chr_bank as usize;
+                self.chr.read(&self.game, bank, 0x2000, address)
+            }
+            _ => panic!("Mapper 087: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 087: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 087: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![("chr_bank", DebugValue::U8Hex(self.chr_bank))]
+    }
+}
+
+impl SaveState for Mapper087 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.chr.save_state(state);
+        state.write_u8(self.chr_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr.load_state(state)?;
+        self.chr_bank = state.read_u8()?;
+        Ok(())
+    }
+}
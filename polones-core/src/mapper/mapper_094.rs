@@ -0,0 +1,98 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, bus_conflicts, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// UN1ROM. Like UxROM, but the PRG bank number starts at bit 2.
+pub struct Mapper094 {
+    game: GameFile,
+    chr: Chr,
+    prg_bank: u8,
+    bus_conflicts: bool,
+}
+
+impl Mapper for Mapper094 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            chr: Chr::new(&game),
+            prg_bank: 0,
+            bus_conflicts: bus_conflicts(&game, true),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x8000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        match address {
+            0x8000..=0xBFFF => {
+                prg_rom[bank_index(prg_rom, self.prg_bank as usize, 0x4000, address)]
+            }
+            0xC000..=0xFFFF => prg_rom[prg_rom.len() - 0x4000 + (address as usize & 0x3FFF)],
+            _ => panic!("Mapper 094: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x8000..=0xFFFF => {
+                let byte = match self.bus_conflicts {
+                    true => byte & self.cpu_read(address),
+                    false => byte,
+                };
+                self.prg_bank = (byte >> 2) & 0b111;
+            }
+            _ => panic!("Mapper 094: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.chr.read(&self.game, 0, 0x2000, address),
+            _ => panic!("Mapper 094: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 094: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 094: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![("prg_bank", DebugValue::U8Hex(self.prg_bank))]
+    }
+}
+
+impl SaveState for Mapper094 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.chr.save_state(state);
+        state.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::bank_index;
+use super::flash::Flash;
+use super::{DebugValue, Mapper, NametableSource};
+
+/// GTROM (Cheapocabra). 32 KiB banks of self-flashable PRG, two 8 KiB banks of
+/// CHR-RAM and two pages of four-screen nametable RAM. The register at
+/// $5000-$5FFF and $7000-$7FFF also drives two LEDs.
+pub struct Mapper111 {
+    /// PRG-ROM, changed by flash writes.
+    prg: Vec<u8>,
+    flash: Flash,
+    chr_ram: Ram<{ 16 * 1024 }>,
+    nametable_ram: Ram<{ 8 * 1024 }>,
+    /// GRNCPPPP: green LED, red LED, nametable page, CHR bank, PRG bank.
+    register: u8,
+}
+
+impl Mapper111 {
+    fn prg_index(&self, address: u16) -> usize {
+        bank_index(&self.prg, self.register as usize & 0x0F, 0x8000, address)
+    }
+
+    fn chr_index(&self, address: u16) -> usize {
+        ((self.register as usize >> 4) & 1) << 13 | (address as usize & 0x1FFF)
+    }
+
+    fn nametable_index(&self, address: u16) -> usize {
+        ((self.register as usize >> 5) & 1) << 12 | (address as usize & 0x0FFF)
+    }
+}
+
+impl Mapper for Mapper111 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        if game.prg_rom().is_empty() {
+            return Err("Mapper 111: Unexpected prg rom size");
+        }
+        if game.chr_rom().is_some() {
+            return Err("Mapper 111: Unexpected chr rom");
+        }
+
+        Ok(Self {
+            prg: game.prg_rom().to_vec(),
+            flash: Flash::new(),
+            chr_ram: Ram::new(),
+            nametable_ram: Ram::new(),
+            register: 0,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        matches!(address, 0x5000..=0x5FFF | 0x7000..=0xFFFF)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        match address {
+            // the register can't be read, the bus keeps the high address byte
+            0x5000..=0x5FFF | 0x7000..=0x7FFF => (address >> 8) as u8,
+            0x8000..=0xFFFF => self.flash.read(&self.prg, self.prg_index(address)),
+            _ => panic!("Mapper 111: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x5000..=0x5FFF | 0x7000..=0x7FFF => self.register = byte,
+            0x8000..=0xFFFF => {
+                let index = self.prg_index(address);
+                self.flash.write(&mut self.prg, index, byte);
+            }
+            _ => panic!("Mapper 111: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        (0x0000..=0x1FFF).contains(&address)
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.chr_ram.read(self.chr_index(address)),
+            0x2000..=0x3EFF => self.nametable_ram.read(self.nametable_index(address)),
+            _ => panic!("Mapper 111: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => self.chr_ram.write(self.chr_index(address), byte),
+            0x2000..=0x3EFF => {
+                let index = self.nametable_index(address);
+                self.nametable_ram.write(index, byte);
+            }
+            _ => panic!("Mapper 111: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, _address: u16) -> NametableSource {
+        NametableSource::Mapper
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("prg_bank", DebugValue::U8Hex(self.register & 0x0F)),
+            ("chr_bank", DebugValue::U8Hex((self.register >> 4) & 1)),
+            ("nametables", DebugValue::U8Hex((self.register >> 5) & 1)),
+            ("red_led", DebugValue::Dec((self.register >> 6) as u64 & 1)),
+            ("green_led", DebugValue::Dec((self.register >> 7) as u64)),
+        ]
+    }
+
+    /// Flash contents, so flash writes are kept like battery-backed RAM.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        Some(&self.prg)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.prg)
+    }
+}
+
+impl SaveState for Mapper111 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+        self.chr_ram.save_state(state);
+        self.nametable_ram.save_state(state);
+        self.flash.save_state(state);
+        state.write_bytes(&self.prg);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.register = state.read_u8()?;
+        self.chr_ram.load_state(state)?;
+        self.nametable_ram.load_state(state)?;
+        self.flash.load_state(state)?;
+        state.read_bytes(&mut self.prg)
+    }
+}
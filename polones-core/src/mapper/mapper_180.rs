@@ -0,0 +1,96 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::discrete::{bank_index, bus_conflicts, Chr};
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// UNROM variant with the first PRG bank fixed at $8000 and the switchable
+/// bank at $C000, used by Crazy Climber.
+pub struct Mapper180 {
+    game: GameFile,
+    chr: Chr,
+    prg_bank: u8,
+    bus_conflicts: bool,
+}
+
+impl Mapper for Mapper180 {
+    fn from_game(game: GameFile) -> Result<Self, &'static str> {
+        Ok(Self {
+            chr: Chr::new(&game),
+            prg_bank: 0,
+            bus_conflicts: bus_conflicts(&game, true),
+            game,
+        })
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        (0x8000..=0xFFFF).contains(&address)
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let prg_rom = self.game.prg_rom();
+        match address {
+            0x8000..=0xBFFF => prg_rom[bank_index(prg_rom, 0, 0x4000, address)],
+            0xC000..=0xFFFF => {
+                prg_rom[bank_index(prg_rom, self.prg_bank as usize, 0x4000, address)]
+            }
+            _ => panic!("Mapper 180: CPU read from {:04X} out of bounds.", address),
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x8000..=0xFFFF if self.bus_conflicts => {
+                self.prg_bank = byte & self.cpu_read(address) & 0b111;
+            }
+            0x8000..=0xFFFF => self.prg_bank = byte & 0b111,
+            _ => panic!("Mapper 180: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => self.chr.read(&self.game, 0, 0x2000, address),
+            _ => panic!("Mapper 180: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                if !self.chr.write(address, byte) {
+                    eprintln!("Mapper 180: PPU write to {:04X} ignored.", address);
+                }
+            }
+            _ => panic!("Mapper 180: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::from_game(&self.game).nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {}
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![("prg_bank", DebugValue::U8Hex(self.prg_bank))]
+    }
+}
+
+impl SaveState for Mapper180 {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.chr.save_state(state);
+        state.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// CHR latches of MMC2 and MMC4. Each 4 KiB pattern table has two banks, one
+/// selected after the PPU fetches tile $FD and one after it fetches tile $FE.
+pub struct Mmc2Latch {
+    pub fd_banks: [u8; 2],
+    pub fe_banks: [u8; 2],
+    latches_fe: [bool; 2],
+    /// MMC2 watches only the first address of tile $FD/$FE in the left
+    /// pattern table, MMC4 all 8 like in the right one.
+    exact_left: bool,
+}
+
+impl Mmc2Latch {
+    pub fn new(exact_left: bool) -> Self {
+        Self {
+            fd_banks: [0; 2],
+            fe_banks: [0; 2],
+            latches_fe: [false; 2],
+            exact_left,
+        }
+    }
+
+    pub fn latch_fe(&self, table: usize) -> bool {
+        self.latches_fe[table]
+    }
+
+    /// Writes one of the bank registers at $B000-$EFFF.
+    pub fn write_bank(&mut self, address: u16, byte: u8) {
+        let bank = byte & 0b0001_1111;
+        match address & 0xF000 {
+            0xB000 => self.fd_banks[0] = bank,
+            0xC000 => self.fe_banks[0] = bank,
+            0xD000 => self.fd_banks[1] = bank,
+            _ => self.fe_banks[1] = bank,
+        }
+    }
+
+    /// Returns the CHR memory index of a pattern table address.
+    pub fn chr_index(&self, address: u16) -> usize {
+        let table = (address >> 12) as usize & 1;
+        let bank = match self.latches_fe[table] {
+            true => self.fe_banks[table],
+            false => self.fd_banks[table],
+        };
+        (bank as usize) << 12 | (address as usize & 0x0FFF)
+    }
+
+    /// Updates the latches after the PPU read a pattern table address. The
+    /// new bank applies from the next read.
+    pub fn ppu_read(&mut self, address: u16) {
+        let table = (address >> 12) as usize & 1;
+        let address = match table == 0 && self.exact_left {
+            true => address,
+            false => address & 0xFFF8,
+        };
+        match address & 0x0FFF {
+            0x0FD8 => self.latches_fe[table] = false,
+            0x0FE8 => self.latches_fe[table] = true,
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for Mmc2Latch {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.fd_banks[0]);
+        state.write_u8(self.fe_banks[0]);
+        state.write_u8(self.fd_banks[1]);
+        state.write_u8(self.fe_banks[1]);
+        state.write_bool(self.latches_fe[0]);
+        state.write_bool(self.latches_fe[1]);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.fd_banks[0] = state.read_u8()?;
+        self.fe_banks[0] = state.read_u8()?;
+        self.fd_banks[1] = state.read_u8()?;
+        self.fe_banks[1] = state.read_u8()?;
+        self.latches_fe[0] = state.read_bool()?;
+        self.latches_fe[1] = state.read_bool()?;
+        Ok(())
+    }
+}
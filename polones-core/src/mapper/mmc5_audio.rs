@@ -0,0 +1,170 @@
+use crate::apu::{Pulse, LENGTH_COUNTER_TABLE, OTHER_MIX_TABLE, PULSE_MIX_TABLE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// CPU cycles between clocks of the audio envelopes and length counters (240 Hz).
+const AUDIO_FRAME_PERIOD: u16 = 7457;
+
+/// MMC5 audio: two pulse channels like the 2A03 ones, without sweep units,
+/// and a PCM channel.
+pub struct Mmc5Audio {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    pcm_read_mode: bool,
+    pcm_irq_enabled: bool,
+    pcm_irq_pending: bool,
+    pcm: u8,
+    frame_counter: u16,
+    cpu_cycle_odd: bool,
+}
+
+impl Mmc5Audio {
+    pub fn new() -> Self {
+        Self {
+            pulse1: Pulse::new_without_complement(),
+            pulse2: Pulse::new_without_complement(),
+            pcm_read_mode: false,
+            pcm_irq_enabled: false,
+            pcm_irq_pending: false,
+            pcm: 0,
+            frame_counter: 0,
+            cpu_cycle_odd: false,
+        }
+    }
+
+    fn write_pulse(pulse: &mut Pulse, register: u16, value: u8) {
+        match register {
+            0 => {
+                pulse.sequencer_duty = value >> 6;
+                pulse.envelope_loop_flag = (value & 0b100000) > 0;
+                pulse.length_counter_halt = pulse.envelope_loop_flag;
+                pulse.envelope_constant_volume_flag = (value & 0b10000) > 0;
+                pulse.envelope_divider_period = value & 0b1111;
+            }
+            2 => {
+                pulse.timer_divider_period &= 0xFF00;
+                pulse.timer_divider_period |= value as u16;
+            }
+            3 => {
+                pulse.timer_divider_period &= 0x00FF;
+                pulse.timer_divider_period |= (value as u16 & 0b111) << 8;
+                if pulse.length_counter_enabled {
+                    pulse.length_counter = LENGTH_COUNTER_TABLE[(value >> 3) as usize];
+                }
+                pulse.sequencer_step = 0;
+                pulse.envelope_start_flag = true;
+            }
+            // no sweep unit
+            _ => {}
+        }
+    }
+
+    fn pulse_sample(pulse: &Pulse) -> u8 {
+        // MMC5 pulses are not silenced at high frequencies
+        let muted = pulse.sequencer_mutes_channel() || pulse.length_counter_mutes_channel();
+        !muted as u8 * pulse.volume()
+    }
+
+    /// Reads the PCM status at $5010 or the pulse status at $5015.
+    pub fn read(&mut self, address: u16) -> u8 {
+        match address {
+            0x5010 => {
+                let result = (self.pcm_irq_pending as u8) << 7 | self.pcm_read_mode as u8;
+                self.pcm_irq_pending = false;
+                result
+            }
+            0x5015 => {
+                (self.pulse1.length_counter > 0) as u8
+                    | ((self.pulse2.length_counter > 0) as u8) << 1
+            }
+            _ => 0,
+        }
+    }
+
+    /// Writes a register at $5000-$5015.
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x5000..=0x5003 => Self::write_pulse(&mut self.pulse1, address & 0b11, value),
+            0x5004..=0x5007 => Self::write_pulse(&mut self.pulse2, address & 0b11, value),
+            0x5010 => {
+                self.pcm_read_mode = value & 1 != 0;
+                self.pcm_irq_enabled = value & 0x80 != 0;
+            }
+            0x5011 if !self.pcm_read_mode && value != 0 => self.pcm = value,
+            0x5015 => {
+                self.pulse1.length_counter_enabled = value & 0b01 != 0;
+                self.pulse2.length_counter_enabled = value & 0b10 != 0;
+                if !self.pulse1.length_counter_enabled {
+                    self.pulse1.length_counter = 0;
+                }
+                if !self.pulse2.length_counter_enabled {
+                    self.pulse2.length_counter = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sees a byte the CPU read from `address`. In PCM read mode, bytes read
+    /// from $8000-$BFFF are played, and a zero raises the IRQ.
+    pub fn cpu_read(&mut self, address: u16, byte: u8) {
+        if self.pcm_read_mode && (0x8000..=0xBFFF).contains(&address) {
+            if byte == 0 {
+                self.pcm_irq_pending = true;
+            } else {
+                self.pcm = byte;
+            }
+        }
+    }
+
+    pub fn irq(&self) -> bool {
+        self.pcm_irq_pending && self.pcm_irq_enabled
+    }
+
+    pub fn tick(&mut self) {
+        if self.frame_counter == 0 {
+            self.frame_counter = AUDIO_FRAME_PERIOD;
+            for pulse in [&mut self.pulse1, &mut self.pulse2] {
+                pulse.tick_envelope();
+                pulse.tick_length_counter();
+            }
+        }
+        self.frame_counter -= 1;
+
+        if !self.cpu_cycle_odd {
+            self.pulse1.tick();
+            self.pulse2.tick();
+        }
+        self.cpu_cycle_odd = !self.cpu_cycle_odd;
+    }
+
+    pub fn output(&self) -> i32 {
+        let pulses = Self::pulse_sample(&self.pulse1) + Self::pulse_sample(&self.pulse2);
+        let output = PULSE_MIX_TABLE[pulses as usize] + OTHER_MIX_TABLE[(self.pcm >> 1) as usize];
+        output as i32
+    }
+}
+
+impl SaveState for Mmc5Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        state.write_bool(self.pcm_read_mode);
+        state.write_bool(self.pcm_irq_enabled);
+        state.write_bool(self.pcm_irq_pending);
+        state.write_u8(self.pcm);
+        state.write_u16(self.frame_counter);
+        state.write_bool(self.cpu_cycle_odd);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.pcm_read_mode = state.read_bool()?;
+        self.pcm_irq_enabled = state.read_bool()?;
+        self.pcm_irq_pending = state.read_bool()?;
+        self.pcm = state.read_u8()?;
+        self.frame_counter = state.read_u16()?;
+        self.cpu_cycle_odd = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,23 +1,122 @@
 use crate::cpu::Cpu;
+use crate::disk_file::DiskFile;
 use crate::game_file::GameFile;
+use crate::nes::Region;
+use crate::nsf_file::NsfFile;
+use crate::save_state::SaveState;
 
+mod discrete;
+mod eeprom;
+mod fds_audio;
+mod flash;
 mod mapper_000;
 mod mapper_001;
 mod mapper_002;
 mod mapper_003;
 mod mapper_004;
+mod mapper_005;
 mod mapper_007;
 mod mapper_009;
+mod mapper_010;
+mod mapper_011;
+mod mapper_016;
+mod mapper_019;
+mod mapper_020;
+mod mapper_021;
+mod mapper_024;
+mod mapper_028;
+mod mapper_030;
+mod mapper_034;
+mod mapper_066;
+mod mapper_069;
+mod mapper_071;
+mod mapper_079;
+mod mapper_085;
+mod mapper_087;
+mod mapper_094;
+mod mapper_111;
+mod mapper_152;
+mod mapper_180;
+mod mmc2_latch;
+mod mmc5_audio;
+mod n163_audio;
+mod nsf_player;
+mod opll;
+mod sunsoft_5b;
+mod vrc6_audio;
+mod vrc_irq;
 
 type DynMapper = Box<dyn Mapper + Send + 'static>;
 
+/// Memory backing a nametable address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NametableSource {
+    /// One of four 1 KiB pages of VRAM. Pages 0 and 1 are the console's CIRAM,
+    /// pages 2 and 3 exist only on four-screen cartridges.
+    Vram(u8),
+    /// The mapper handles the access in `ppu_read` and `ppu_write`, e.g. to
+    /// use CHR-ROM as nametables.
+    Mapper,
+}
+
+/// Common nametable layouts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    /// All nametables use the given VRAM page.
+    SingleScreen(u8),
+    /// Each nametable has its own VRAM page.
+    FourScreen,
+}
+
+impl Mirroring {
+    /// Mirroring hardwired on the cartridge, as described by the header.
+    pub fn from_game(game: &GameFile) -> Self {
+        if game.four_screen_mode {
+            Mirroring::FourScreen
+        } else if game.mirroring_vertical {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        }
+    }
+
+    pub fn nametable_source(self, address: u16) -> NametableSource {
+        let nametable = ((address >> 10) & 0b11) as u8;
+        NametableSource::Vram(match self {
+            Mirroring::Horizontal => nametable >> 1,
+            Mirroring::Vertical => nametable & 1,
+            Mirroring::SingleScreen(page) => page,
+            Mirroring::FourScreen => nametable,
+        })
+    }
+}
+
+/// Rendering milestones reported by the PPU to the mapper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PpuEvent {
+    /// Rendering of the given visible scanline starts. Sent on dot 1 of
+    /// scanlines 0-239 if background or sprite rendering is enabled.
+    ScanlineStart(u16),
+    /// Sprite pattern fetches for the next scanline start (dot 257).
+    SpriteFetches,
+    /// Background tile fetches for the next scanline start (dot 321).
+    BackgroundFetches,
+    /// The visible part of the frame ended or rendering got disabled.
+    RenderingStopped,
+    /// PPUCTRL was written. Tells if sprites are 8x16.
+    LargeSprites(bool),
+}
+
 pub enum DebugValue {
     Dec(u64),
     U8Hex(u8),
     U16Hex(u16),
 }
 
-pub trait Mapper {
+/// Mappers save their registers and RAM with save states. ROM is not saved.
+pub trait Mapper: SaveState {
     fn from_game(game: GameFile) -> Result<Self, &'static str>
     where
         Self: Sized;
@@ -27,9 +126,58 @@ pub trait Mapper {
     fn ppu_address_mapped(&self, address: u16) -> bool;
     fn ppu_read(&mut self, address: u16) -> u8;
     fn ppu_write(&mut self, address: u16, byte: u8);
-    fn ppu_nametable_address_mapped(&self, address: u16) -> u16;
+    /// Tells where nametable address $2000-$3EFF is stored. Also asked about
+    /// pattern table addresses not claimed by `ppu_address_mapped`, so mappers
+    /// can place CIRAM there.
+    fn nametable_source(&self, address: u16) -> NametableSource;
+    /// Like `nametable_source`, but called only for PPU reads. Mappers can
+    /// override it to follow nametable and attribute fetches.
+    fn nametable_read_source(&mut self, address: u16) -> NametableSource {
+        self.nametable_source(address)
+    }
+    /// Called by the PPU on rendering milestones, so mappers don't have to
+    /// decode PPU memory accesses to follow rendering.
+    fn ppu_event(&mut self, _event: PpuEvent) {}
     fn tick(&mut self, cpu: &mut Cpu);
+    /// Output of the cartridge's expansion audio, added to APU samples.
+    fn audio_output(&self) -> i32 {
+        0
+    }
     fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> { Vec::new() }
+    /// Memory that keeps its contents when the console is off, usually
+    /// battery-backed PRG RAM. None if the cartridge has no such memory.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+    /// Copies the 512-byte trainer to PRG RAM at $7000-$71FF. Returns false if
+    /// there is no RAM at that address.
+    fn load_trainer(&mut self, _trainer: &[u8]) -> bool {
+        false
+    }
+    /// Number of disk sides of a Disk System game, 0 for cartridges.
+    fn disk_side_count(&self) -> usize {
+        0
+    }
+    /// Disk side in the drive, None if the drive is empty.
+    fn disk_side(&self) -> Option<usize> {
+        None
+    }
+    /// Ejects the disk and inserts the given side a moment later.
+    fn insert_disk_side(&mut self, _side: Option<usize>) {}
+    /// Number of songs of NSF music, 0 for games.
+    fn song_count(&self) -> usize {
+        0
+    }
+    /// Song being played, None for games.
+    fn song(&self) -> Option<usize> {
+        None
+    }
+    /// Prepares the driver to play a song from the start at the rate of the
+    /// given region. The CPU must be reset afterwards.
+    fn select_song(&mut self, _song: usize, _region: Region) {}
 }
 
 pub fn mapper_from_game_file(game: GameFile) -> Result<Box<dyn Mapper + Send + 'static>, &'static str> {
@@ -37,8 +185,7 @@ pub fn mapper_from_game_file(game: GameFile) -> Result<Box<dyn Mapper + Send + '
         (0, _) => {
             mapper_000::Mapper000::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
         }
-        (1, Some(5)) => Err("unsupported mapper"), // todo mapper 155
-        (1, _) => {
+        (1 | 155, _) => {
             mapper_001::Mapper001::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
         }
         (2, _) => {
@@ -47,15 +194,85 @@ pub fn mapper_from_game_file(game: GameFile) -> Result<Box<dyn Mapper + Send + '
         (3, _) => {
             mapper_003::Mapper003::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
         }
-        (4, _) => {
+        (4 | 118 | 119, _) => {
             mapper_004::Mapper004::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
         }
+        (5, _) => {
+            mapper_005::Mapper005::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
         (7, _) => {
             mapper_007::Mapper007::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
         }
         (9, _) => {
             mapper_009::Mapper009::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
         }
+        (10, _) => {
+            mapper_010::Mapper010::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (11, _) => {
+            mapper_011::Mapper011::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (16 | 153 | 159, _) => {
+            mapper_016::Mapper016::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (19, _) => {
+            mapper_019::Mapper019::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (21 | 22 | 23 | 25, _) => {
+            mapper_021::Mapper021::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (24 | 26, _) => {
+            mapper_024::Mapper024::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (28, _) => {
+            mapper_028::Mapper028::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (30, _) => {
+            mapper_030::Mapper030::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (34, _) => {
+            mapper_034::Mapper034::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (66 | 140, _) => {
+            mapper_066::Mapper066::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (69, _) => {
+            mapper_069::Mapper069::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (71, _) => {
+            mapper_071::Mapper071::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (79, _) => {
+            mapper_079::Mapper079::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (85, _) => {
+            mapper_085::Mapper085::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (87, _) => {
+            mapper_087::Mapper087::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (94, _) => {
+            mapper_094::Mapper094::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (111, _) => {
+            mapper_111::Mapper111::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (152, _) => {
+            mapper_152::Mapper152::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
+        (180, _) => {
+            mapper_180::Mapper180::from_game(game).map(|mapper| Box::new(mapper) as DynMapper)
+        }
         _ => Err("unsupported mapper"),
     }
 }
+
+/// Creates the Disk System RAM adapter with the given disk inserted.
+pub fn mapper_from_disk_file(disk: &DiskFile, bios: &[u8]) -> Result<DynMapper, &'static str> {
+    mapper_020::Mapper020::new(disk, bios).map(|mapper| Box::new(mapper) as DynMapper)
+}
+
+/// Creates the player of NSF music, set up to play the starting song.
+pub fn mapper_from_nsf_file(nsf: &NsfFile) -> Result<DynMapper, &'static str> {
+    nsf_player::NsfPlayer::new(nsf).map(|mapper| Box::new(mapper) as DynMapper)
+}
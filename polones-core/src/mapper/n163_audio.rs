@@ -0,0 +1,115 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Size of the chip's internal RAM holding wavetables and channel registers.
+pub const SOUND_RAM_SIZE: usize = 128;
+/// CPU cycles the chip spends updating each channel.
+const CYCLES_PER_CHANNEL: u8 = 15;
+/// Mixer level of one step of channel output.
+const N163_MIX_STEP: i32 = 48;
+
+/// Namco 163 wavetable audio. Boards can keep sound RAM behind the battery,
+/// so it's owned by the caller and passed in.
+pub struct N163Audio {
+    /// Sound RAM address with auto-increment flag.
+    address: u8,
+    timer: u8,
+    channel: u8,
+    output: i32,
+}
+
+impl N163Audio {
+    pub fn new() -> Self {
+        Self {
+            address: 0,
+            timer: 0,
+            channel: 7,
+            output: 0,
+        }
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Writes the address port at $F800-$FFFF.
+    pub fn write_address(&mut self, byte: u8) {
+        self.address = byte;
+    }
+
+    /// Returns the sound RAM index for a data port access and increments the
+    /// address if auto-increment is on.
+    fn data_index(&mut self) -> usize {
+        let address = self.address;
+        if address & 0x80 != 0 {
+            self.address = 0x80 | (address.wrapping_add(1) & 0x7F);
+        }
+        (address & 0x7F) as usize
+    }
+
+    /// Reads the data port at $4800-$4FFF.
+    pub fn read_data(&mut self, sound_ram: &[u8]) -> u8 {
+        sound_ram[self.data_index()]
+    }
+
+    /// Writes the data port at $4800-$4FFF.
+    pub fn write_data(&mut self, sound_ram: &mut [u8], byte: u8) {
+        sound_ram[self.data_index()] = byte;
+    }
+
+    pub fn tick(&mut self, sound_ram: &mut [u8]) {
+        self.timer += 1;
+        if self.timer < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.timer = 0;
+
+        let channel = self.channel as usize;
+        let registers = &mut sound_ram[0x40 + channel * 8..0x48 + channel * 8];
+        let frequency =
+            registers[0] as u32 | (registers[2] as u32) << 8 | (registers[4] as u32 & 0b11) << 16;
+        let length = 256 - (registers[4] & 0xFC) as u32;
+        let phase = registers[1] as u32 | (registers[3] as u32) << 8 | (registers[5] as u32) << 16;
+        let phase = (phase + frequency) % (length << 16);
+        registers[1] = phase as u8;
+        registers[3] = (phase >> 8) as u8;
+        registers[5] = (phase >> 16) as u8;
+        let wave_address = (registers[6] as u32 + (phase >> 16)) as u8;
+        let volume = (registers[7] & 0x0F) as i32;
+
+        // samples are 4 bits, low nibble first
+        let byte = sound_ram[wave_address as usize >> 1];
+        let sample = match wave_address & 1 {
+            0 => byte & 0x0F,
+            _ => byte >> 4,
+        };
+        let channels = (sound_ram[0x7F] >> 4) & 0b111;
+        self.output = (sample as i32 - 8) * volume;
+
+        // enabled channels are updated from 7 down
+        self.channel = match self.channel <= 7 - channels {
+            true => 7,
+            false => self.channel - 1,
+        };
+    }
+
+    pub fn output(&self) -> i32 {
+        self.output * N163_MIX_STEP
+    }
+}
+
+impl SaveState for N163Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.address);
+        state.write_u8(self.timer);
+        state.write_u8(self.channel);
+        state.write_u32(self.output as u32);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.address = state.read_u8()?;
+        self.timer = state.read_u8()? % CYCLES_PER_CHANNEL;
+        self.channel = state.read_u8()? & 0b111;
+        self.output = state.read_u32()? as i32;
+        Ok(())
+    }
+}
@@ -0,0 +1,486 @@
+use crate::cpu::Cpu;
+use crate::game_file::GameFile;
+use crate::nes::Region;
+use crate::nsf_file::NsfFile;
+use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::fds_audio::FdsAudio;
+use super::mmc5_audio::Mmc5Audio;
+use super::n163_audio::{N163Audio, SOUND_RAM_SIZE};
+use super::opll::{Opll, OPLL_MIX_LEVEL};
+use super::sunsoft_5b::Sunsoft5b;
+use super::vrc6_audio::Vrc6Audio;
+use super::{DebugValue, Mapper, Mirroring, NametableSource};
+
+/// Address of the built-in driver. No console or expansion chip register
+/// lives in this page.
+const DRIVER_ADDRESS: u16 = 0x4100;
+/// Registers the driver reads: song number, region, and the addresses of the
+/// init and play routines used for indirect jumps.
+const DRIVER_SONG: u16 = 0x41F0;
+const DRIVER_REGION: u16 = 0x41F1;
+const DRIVER_INIT: u16 = 0x41F2;
+const DRIVER_PLAY: u16 = 0x41F4;
+/// Reads 1 when the play routine is due. Writes acknowledge it, the first
+/// write after reset starts the play timer.
+const DRIVER_PLAY_DUE: u16 = 0x41F6;
+const DRIVER_RESET: u16 = 0x4100;
+const DRIVER_INTERRUPT: u16 = 0x414F;
+
+/// Resets the console state, calls the init routine and then calls the play
+/// routine whenever it's due. Interrupts are left disabled.
+#[rustfmt::skip]
+const DRIVER: [u8; 0x56] = [
+    // reset ($4100)
+    0x78,                   // SEI
+    0xD8,                   // CLD
+    0xA2, 0xFF,             // LDX #$FF
+    0x9A,                   // TXS
+    0xA9, 0x00,             // LDA #$00
+    0xAA,                   // TAX
+    0x9D, 0x00, 0x00,       // STA $0000,X
+    0x9D, 0x00, 0x01,       // STA $0100,X
+    0x9D, 0x00, 0x02,       // STA $0200,X
+    0x9D, 0x00, 0x03,       // STA $0300,X
+    0x9D, 0x00, 0x04,       // STA $0400,X
+    0x9D, 0x00, 0x05,       // STA $0500,X
+    0x9D, 0x00, 0x06,       // STA $0600,X
+    0x9D, 0x00, 0x07,       // STA $0700,X
+    0xE8,                   // INX
+    0xD0, 0xE5,             // BNE $4108
+    0xA2, 0x13,             // LDX #$13
+    0x9D, 0x00, 0x40,       // STA $4000,X
+    0xCA,                   // DEX
+    0x10, 0xFA,             // BPL $4125
+    0xA9, 0x0F,             // LDA #$0F
+    0x8D, 0x15, 0x40,       // STA $4015
+    0xA9, 0x40,             // LDA #$40
+    0x8D, 0x17, 0x40,       // STA $4017
+    0xAD, 0xF0, 0x41,       // LDA $41F0
+    0xAE, 0xF1, 0x41,       // LDX $41F1
+    0x20, 0x50, 0x41,       // JSR $4150
+    0x8D, 0xF6, 0x41,       // STA $41F6
+    // idle loop ($4141)
+    0xAD, 0xF6, 0x41,       // LDA $41F6
+    0xF0, 0xFB,             // BEQ $4141
+    0x8D, 0xF6, 0x41,       // STA $41F6
+    0x20, 0x53, 0x41,       // JSR $4153
+    0x4C, 0x41, 0x41,       // JMP $4141
+    // nmi and irq ($414F)
+    0x40,                   // RTI
+    // init and play trampolines ($4150)
+    0x6C, 0xF2, 0x41,       // JMP ($41F2)
+    0x6C, 0xF4, 0x41,       // JMP ($41F4)
+];
+
+/// Plays NSF music with a built-in driver, the way hardware NSF players do.
+/// PRG is switched in 4 KiB banks with registers at $5FF8-$5FFF. Disk System
+/// music gets RAM at $6000-$FFFF, filled from banks selected at $5FF6-$5FFF.
+/// MMC5 music also gets the multiplier and ExRAM of MMC5.
+pub struct NsfPlayer {
+    /// Program data in 4 KiB banks. The load address is at its offset in the
+    /// first bank.
+    prg: Vec<u8>,
+    /// Banks at $6000-$FFFF selected before the init routine runs.
+    initial_banks: [u8; 10],
+    banks: [u8; 10],
+    /// RAM at $6000-$7FFF, or at $6000-$FFFF for Disk System music.
+    ram: Vec<u8>,
+    song_count: usize,
+    song: usize,
+    init_address: u16,
+    play_address: u16,
+    ntsc_speed: u16,
+    pal_speed: u16,
+    region: Region,
+    /// CPU cycles between calls of the play routine.
+    play_period: u32,
+    play_timer: u32,
+    playing: bool,
+    play_due: bool,
+    vrc6: Option<Vrc6Audio>,
+    vrc7: Option<Opll>,
+    fds: Option<FdsAudio>,
+    mmc5: Option<Mmc5Audio>,
+    n163: Option<N163Audio>,
+    sunsoft_5b: Option<Sunsoft5b>,
+    n163_ram: [u8; SOUND_RAM_SIZE],
+    /// MMC5 multiplicand and multiplier at $5205-$5206.
+    mmc5_multiplier: [u8; 2],
+    mmc5_ex_ram: Option<Ram<1024>>,
+}
+
+impl NsfPlayer {
+    pub fn new(nsf: &NsfFile) -> Result<Self, &'static str> {
+        let fds = nsf.expansion_chips.fds;
+        let padding = nsf.load_address as usize & 0x0FFF;
+        let mut prg = vec![0; padding];
+        prg.extend_from_slice(nsf.data());
+        prg.resize(prg.len().next_multiple_of(0x1000), 0);
+
+        let initial_banks = match nsf.banks {
+            // Disk System music fills $6000-$7FFF with the banks of $E000-$FFFF
+            Some(banks) => std::array::from_fn(|page| match page {
+                0 | 1 if fds => banks[page + 6],
+                0 | 1 => 0,
+                _ => banks[page - 2],
+            }),
+            // without bank switching the data is loaded at its address
+            None => {
+                let first_page = (nsf.load_address >> 12) as u8;
+                std::array::from_fn(|page| (page as u8 + 6).wrapping_sub(first_page))
+            }
+        };
+
+        let mut player = Self {
+            prg,
+            initial_banks,
+            banks: initial_banks,
+            ram: vec![0; if fds { 0xA000 } else { 0x2000 }],
+            song_count: nsf.song_count,
+            song: nsf.starting_song,
+            init_address: nsf.init_address,
+            play_address: nsf.play_address,
+            ntsc_speed: nsf.ntsc_speed,
+            pal_speed: nsf.pal_speed,
+            region: Region::Ntsc,
+            play_period: 1,
+            play_timer: 1,
+            playing: false,
+            play_due: false,
+            vrc6: nsf.expansion_chips.vrc6.then(Vrc6Audio::new),
+            vrc7: nsf.expansion_chips.vrc7.then(Opll::new),
+            fds: fds.then(FdsAudio::new),
+            mmc5: nsf.expansion_chips.mmc5.then(Mmc5Audio::new),
+            n163: nsf.expansion_chips.n163.then(N163Audio::new),
+            sunsoft_5b: nsf.expansion_chips.sunsoft_5b.then(Sunsoft5b::new),
+            n163_ram: [0; SOUND_RAM_SIZE],
+            mmc5_multiplier: [0xFF; 2],
+            mmc5_ex_ram: nsf.expansion_chips.mmc5.then(Ram::new),
+        };
+        let region = nsf.region.unwrap_or_default();
+        player.select_song(nsf.starting_song, region);
+        Ok(player)
+    }
+
+    fn prg_read(&self, bank: u8, address: u16) -> u8 {
+        let index = (bank as usize) << 12 | (address as usize & 0x0FFF);
+        self.prg.get(index).copied().unwrap_or(0)
+    }
+
+    /// Sets the bank of a 4 KiB page at $6000-$FFFF. Disk System music copies
+    /// the bank to RAM.
+    fn switch_bank(&mut self, page: usize, bank: u8) {
+        self.banks[page] = bank;
+        if self.fds.is_some() {
+            let start = page << 12;
+            for offset in 0..0x1000 {
+                self.ram[start + offset] = self.prg_read(bank, offset as u16);
+            }
+        }
+    }
+
+    /// Writes a register of expansion chips at $8000-$FFFF. Unlike on game
+    /// boards, addresses are fully decoded, so chips don't see each other's
+    /// registers.
+    fn write_expansion_audio(&mut self, address: u16, byte: u8) {
+        if let (0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002, Some(vrc6)) =
+            (address, &mut self.vrc6)
+        {
+            vrc6.write(address, byte);
+        }
+        match (
+            address,
+            &mut self.vrc7,
+            &mut self.sunsoft_5b,
+            &mut self.n163,
+        ) {
+            (0x9010, Some(vrc7), _, _) => vrc7.write_address(byte),
+            (0x9030, Some(vrc7), _, _) => vrc7.write_data(byte),
+            (0xC000, _, Some(sunsoft_5b), _) => sunsoft_5b.write_address(byte),
+            (0xE000, _, Some(sunsoft_5b), _) => sunsoft_5b.write_data(byte),
+            (0xF800..=0xFFFF, _, _, Some(n163)) => n163.write_address(byte),
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for NsfPlayer {
+    fn from_game(_game: GameFile) -> Result<Self, &'static str> {
+        Err("NSF player: NSF music needs an NSF file")
+    }
+
+    fn cpu_address_mapped(&self, address: u16) -> bool {
+        match address {
+            0x4040..=0x409F => self.fds.is_some(),
+            0x4800..=0x4FFF => self.n163.is_some(),
+            0x5000..=0x5007 | 0x5010..=0x5011 | 0x5015 | 0x5205..=0x5206 | 0x5C00..=0x5FF5 => {
+                self.mmc5.is_some()
+            }
+            0x4100..=0x41FF | 0x5FF6..=0xFFFF => true,
+            _ => false,
+        }
+    }
+
+    fn cpu_read(&mut self, address: u16) -> u8 {
+        let byte = match address {
+            0x4040..=0x409F => self.fds.as_ref().map_or(0x40, |fds| fds.read(address)),
+            0x4800..=0x4FFF => match &mut self.n163 {
+                Some(n163) => n163.read_data(&self.n163_ram),
+                None => (address >> 8) as u8,
+            },
+            DRIVER_SONG => self.song as u8,
+            DRIVER_REGION => (self.region != Region::Ntsc) as u8,
+            DRIVER_INIT => self.init_address as u8,
+            0x41F3 => (self.init_address >> 8) as u8,
+            DRIVER_PLAY => self.play_address as u8,
+            0x41F5 => (self.play_address >> 8) as u8,
+            DRIVER_PLAY_DUE => self.play_due as u8,
+            0x4100..=0x41FF => {
+                let offset = (address - DRIVER_ADDRESS) as usize;
+                DRIVER.get(offset).copied().unwrap_or(0)
+            }
+            0x5010 | 0x5015 => match &mut self.mmc5 {
+                Some(mmc5) => mmc5.read(address),
+                None => (address >> 8) as u8,
+            },
+            0x5205 => {
+                let [multiplicand, multiplier] = self.mmc5_multiplier;
+                (multiplicand as u16 * multiplier as u16) as u8
+            }
+            0x5206 => {
+                let [multiplicand, multiplier] = self.mmc5_multiplier;
+                ((multiplicand as u16 * multiplier as u16) >> 8) as u8
+            }
+            0x5C00..=0x5FF5 => match &self.mmc5_ex_ram {
+                Some(ex_ram) => ex_ram.read((address - 0x5C00) as usize),
+                None => (address >> 8) as u8,
+            },
+            // the register can't be read, the bus keeps the high address byte
+            0x5000..=0x5007 | 0x5011 | 0x5FF6..=0x5FFF => (address >> 8) as u8,
+            // vectors point to the driver
+            0xFFFA | 0xFFFE => DRIVER_INTERRUPT as u8,
+            0xFFFC => DRIVER_RESET as u8,
+            0xFFFB | 0xFFFD | 0xFFFF => (DRIVER_ADDRESS >> 8) as u8,
+            0x6000..=0xFFFF if self.fds.is_some() => self.ram[address as usize - 0x6000],
+            0x6000..=0x7FFF => self.ram[address as usize - 0x6000],
+            0x8000..=0xFFFF => {
+                let bank = self.banks[(address as usize >> 12) - 6];
+                self.prg_read(bank, address)
+            }
+            _ => panic!("NSF player: CPU read from {:04X} out of bounds.", address),
+        };
+        if let Some(mmc5) = &mut self.mmc5 {
+            mmc5.cpu_read(address, byte);
+        }
+        byte
+    }
+
+    fn cpu_write(&mut self, address: u16, byte: u8) {
+        match address {
+            0x4040..=0x409F => {
+                if let Some(fds) = &mut self.fds {
+                    fds.write(address, byte);
+                }
+            }
+            0x4800..=0x4FFF => {
+                if let Some(n163) = &mut self.n163 {
+                    n163.write_data(&mut self.n163_ram, byte);
+                }
+            }
+            0x5000..=0x5015 => {
+                if let Some(mmc5) = &mut self.mmc5 {
+                    mmc5.write(address, byte);
+                }
+            }
+            0x5205..=0x5206 => self.mmc5_multiplier[(address - 0x5205) as usize] = byte,
+            0x5C00..=0x5FF5 => {
+                if let Some(ex_ram) = &mut self.mmc5_ex_ram {
+                    ex_ram.write((address - 0x5C00) as usize, byte);
+                }
+            }
+            DRIVER_PLAY_DUE => {
+                if !self.playing {
+                    self.playing = true;
+                    self.play_timer = self.play_period;
+                }
+                self.play_due = false;
+            }
+            0x4100..=0x41FF => {}
+            0x5FF6..=0x5FF7 if self.fds.is_none() => {}
+            0x5FF6..=0x5FFF => self.switch_bank((address - 0x5FF6) as usize, byte),
+            0x6000..=0x7FFF => self.ram[address as usize - 0x6000] = byte,
+            0x8000..=0xFFFF => {
+                if self.fds.is_some() {
+                    self.ram[address as usize - 0x6000] = byte;
+                }
+                self.write_expansion_audio(address, byte);
+            }
+            _ => panic!("NSF player: CPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_address_mapped(&self, address: u16) -> bool {
+        address <= 0x1FFF
+    }
+
+    /// There is no CHR memory, the driver never enables rendering.
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        match address {
+            0x0000..=0x1FFF => 0,
+            _ => panic!("NSF player: PPU read of {:04X} out of bounds.", address),
+        }
+    }
+
+    fn ppu_write(&mut self, address: u16, _byte: u8) {
+        match address {
+            0x0000..=0x1FFF => {}
+            _ => panic!("NSF player: PPU write to {:04X} out of bounds.", address),
+        }
+    }
+
+    fn nametable_source(&self, address: u16) -> NametableSource {
+        Mirroring::Vertical.nametable_source(address)
+    }
+
+    fn tick(&mut self, _cpu: &mut Cpu) {
+        if self.playing {
+            self.play_timer -= 1;
+            if self.play_timer == 0 {
+                self.play_timer = self.play_period;
+                self.play_due = true;
+            }
+        }
+        if let Some(vrc6) = &mut self.vrc6 {
+            vrc6.tick();
+        }
+        if let Some(vrc7) = &mut self.vrc7 {
+            vrc7.tick();
+        }
+        if let Some(fds) = &mut self.fds {
+            fds.tick();
+        }
+        if let Some(mmc5) = &mut self.mmc5 {
+            mmc5.tick();
+        }
+        if let Some(n163) = &mut self.n163 {
+            n163.tick(&mut self.n163_ram);
+        }
+        if let Some(sunsoft_5b) = &mut self.sunsoft_5b {
+            sunsoft_5b.tick();
+        }
+    }
+
+    fn audio_output(&self) -> i32 {
+        let vrc6 = self.vrc6.as_ref().map_or(0, |vrc6| vrc6.output());
+        let vrc7 = self.vrc7.as_ref().map_or(0.0, |vrc7| vrc7.output());
+        let fds = self.fds.as_ref().map_or(0, |fds| fds.output());
+        let mmc5 = self.mmc5.as_ref().map_or(0, |mmc5| mmc5.output());
+        let n163 = self.n163.as_ref().map_or(0, |n163| n163.output());
+        let sunsoft_5b = self.sunsoft_5b.as_ref().map_or(0, |chip| chip.output());
+        vrc6 + (vrc7 * OPLL_MIX_LEVEL) as i32 + fds + mmc5 + n163 + sunsoft_5b
+    }
+
+    fn gather_debug_info(&self) -> Vec<(&'static str, DebugValue)> {
+        vec![
+            ("song", DebugValue::Dec(self.song as u64 + 1)),
+            ("song_count", DebugValue::Dec(self.song_count as u64)),
+            ("play_period", DebugValue::Dec(self.play_period as u64)),
+            ("bank_6000", DebugValue::U8Hex(self.banks[0])),
+            ("bank_7000", DebugValue::U8Hex(self.banks[1])),
+            ("bank_8000", DebugValue::U8Hex(self.banks[2])),
+            ("bank_9000", DebugValue::U8Hex(self.banks[3])),
+            ("bank_a000", DebugValue::U8Hex(self.banks[4])),
+            ("bank_b000", DebugValue::U8Hex(self.banks[5])),
+            ("bank_c000", DebugValue::U8Hex(self.banks[6])),
+            ("bank_d000", DebugValue::U8Hex(self.banks[7])),
+            ("bank_e000", DebugValue::U8Hex(self.banks[8])),
+            ("bank_f000", DebugValue::U8Hex(self.banks[9])),
+        ]
+    }
+
+    fn song_count(&self) -> usize {
+        self.song_count
+    }
+
+    fn song(&self) -> Option<usize> {
+        Some(self.song)
+    }
+
+    fn select_song(&mut self, song: usize, region: Region) {
+        self.song = song;
+        self.region = region;
+        let speed = match region {
+            Region::Ntsc => self.ntsc_speed,
+            Region::Pal | Region::Dendy => self.pal_speed,
+        };
+        // without a rate the play routine runs once per frame
+        let speed = match (speed, region) {
+            (0, Region::Ntsc) => 16639,
+            (0, Region::Pal | Region::Dendy) => 19997,
+            (speed, _) => speed,
+        };
+        let cycles = speed as u64 * region.cpu_clock_rate() as u64 / 1_000_000;
+        self.play_period = (cycles as u32).max(1);
+        self.play_timer = self.play_period;
+        self.playing = false;
+        self.play_due = false;
+
+        self.ram.fill(0);
+        for page in 0..self.banks.len() {
+            self.switch_bank(page, self.initial_banks[page]);
+        }
+        self.vrc6 = self.vrc6.as_ref().map(|_| Vrc6Audio::new());
+        self.vrc7 = self.vrc7.as_ref().map(|_| Opll::new());
+        self.fds = self.fds.as_ref().map(|_| FdsAudio::new());
+        self.mmc5 = self.mmc5.as_ref().map(|_| Mmc5Audio::new());
+        self.n163 = self.n163.as_ref().map(|_| N163Audio::new());
+        self.sunsoft_5b = self.sunsoft_5b.as_ref().map(|_| Sunsoft5b::new());
+        self.n163_ram = [0; SOUND_RAM_SIZE];
+        self.mmc5_multiplier = [0xFF; 2];
+        self.mmc5_ex_ram = self.mmc5_ex_ram.as_ref().map(|_| Ram::new());
+    }
+}
+
+impl SaveState for NsfPlayer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.banks);
+        state.write_bytes(&self.ram);
+        state.write_u8(self.song as u8);
+        self.region.save_state(state);
+        state.write_u32(self.play_period);
+        state.write_u32(self.play_timer);
+        state.write_bool(self.playing);
+        state.write_bool(self.play_due);
+        self.vrc6.save_state(state);
+        self.vrc7.save_state(state);
+        self.fds.save_state(state);
+        self.mmc5.save_state(state);
+        self.n163.save_state(state);
+        self.sunsoft_5b.save_state(state);
+        state.write_bytes(&self.n163_ram);
+        state.write_bytes(&self.mmc5_multiplier);
+        self.mmc5_ex_ram.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        state.read_bytes(&mut self.banks)?;
+        state.read_bytes(&mut self.ram)?;
+        self.song = (state.read_u8()? as usize).min(self.song_count - 1);
+        self.region.load_state(state)?;
+        self.play_period = state.read_u32()?.max(1);
+        self.play_timer = state.read_u32()?.clamp(1, self.play_period);
+        self.playing = state.read_bool()?;
+        self.play_due = state.read_bool()?;
+        self.vrc6.load_state(state)?;
+        self.vrc7.load_state(state)?;
+        self.fds.load_state(state)?;
+        self.mmc5.load_state(state)?;
+        self.n163.load_state(state)?;
+        self.sunsoft_5b.load_state(state)?;
+        state.read_bytes(&mut self.n163_ram)?;
+        state.read_bytes(&mut self.mmc5_multiplier)?;
+        self.mmc5_ex_ram.load_state(state)
+    }
+}
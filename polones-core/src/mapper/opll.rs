@@ -0,0 +1,451 @@
+//! FM synthesizer of Konami VRC7, a cut-down Yamaha YM2413 (OPLL) with six
+//! two-operator channels and its own set of built-in instruments.
+//!
+//! The emulation follows the chip's structure (phase generators, envelope
+//! generators, feedback and phase modulation), but computes in floating point
+//! rather than reproducing the chip's log-sin and exponent tables.
+
+use std::f32::consts::TAU;
+
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Mixer level of a channel at full volume.
+pub const OPLL_MIX_LEVEL: f32 = 4000.0;
+
+/// Built-in instruments 1-15 as register values $00-$07. Instrument 0 is
+/// the custom instrument defined by writes to those registers.
+const PATCHES: [[u8; 8]; 16] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+const MULTIPLIERS: [f32; 16] = [
+    0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 10.0, 12.0, 12.0, 15.0, 15.0,
+];
+
+/// Key scale attenuation in dB at block 7, indexed by the top F-number bits.
+const KEY_SCALE_LEVELS: [f32; 16] = [
+    0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25, 36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25,
+    42.0,
+];
+
+/// Key scale level 0-3 means 0, 1.5, 3 and 6 dB per octave.
+const KEY_SCALE_FACTORS: [f32; 4] = [0.0, 0.5, 1.0, 2.0];
+
+/// CPU cycles per synthesizer sample (the chip runs at 49716 Hz).
+const CYCLES_PER_SAMPLE: u8 = 36;
+const SAMPLE_RATE: f32 = 49716.0;
+
+/// Attenuation in dB at which an operator is silent.
+const MAX_ATTENUATION: f32 = 48.0;
+/// dB per sample of decay and release at rate 0 (before scaling by 2^(rate/4)).
+const DECAY_STEP: f32 = 1.2e-5;
+/// Fraction of the remaining attenuation removed per sample of attack at rate 0.
+const ATTACK_STEP: f32 = 4.6e-6;
+const TREMOLO_DEPTH: f32 = 4.8;
+const TREMOLO_RATE: f32 = 3.7;
+const VIBRATO_DEPTH: f32 = 0.008;
+const VIBRATO_RATE: f32 = 6.4;
+/// Phase shift of the carrier, in cycles, at full modulator output.
+const MODULATION_DEPTH: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Operator parameters of an instrument.
+struct OperatorPatch {
+    tremolo: bool,
+    vibrato: bool,
+    sustained: bool,
+    key_scale_rate: bool,
+    multiplier: f32,
+    key_scale_level: u8,
+    rectified: bool,
+    attack_rate: u8,
+    decay_rate: u8,
+    sustain_level: u8,
+    release_rate: u8,
+}
+
+impl OperatorPatch {
+    /// Decodes operator 0 (modulator) or 1 (carrier) of an instrument.
+    fn new(patch: &[u8; 8], operator: usize) -> Self {
+        Self {
+            tremolo: patch[operator] & 0x80 != 0,
+            vibrato: patch[operator] & 0x40 != 0,
+            sustained: patch[operator] & 0x20 != 0,
+            key_scale_rate: patch[operator] & 0x10 != 0,
+            multiplier: MULTIPLIERS[(patch[operator] & 0x0F) as usize],
+            key_scale_level: patch[2 + operator] >> 6,
+            rectified: patch[3] & (0x08 << operator) != 0,
+            attack_rate: patch[4 + operator] >> 4,
+            decay_rate: patch[4 + operator] & 0x0F,
+            sustain_level: patch[6 + operator] >> 4,
+            release_rate: patch[6 + operator] & 0x0F,
+        }
+    }
+}
+
+struct Operator {
+    /// Position in the waveform, in cycles.
+    phase: f32,
+    /// Envelope attenuation in dB.
+    envelope: f32,
+    state: EnvelopeState,
+}
+
+impl Operator {
+    fn new() -> Self {
+        Self {
+            phase: 0.0,
+            envelope: MAX_ATTENUATION,
+            state: EnvelopeState::Release,
+        }
+    }
+
+    fn key_on(&mut self) {
+        self.phase = 0.0;
+        self.envelope = MAX_ATTENUATION;
+        self.state = EnvelopeState::Attack;
+    }
+
+    fn key_off(&mut self) {
+        self.state = EnvelopeState::Release;
+    }
+
+    /// Advances the envelope by one sample. `key_scale` is the rate offset
+    /// derived from the pitch and `sustain` the channel's sustain flag.
+    fn tick_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, sustain: bool) {
+        let rate = |rate: u8| match rate {
+            0 => 0,
+            rate => (rate * 4 + (key_scale >> if patch.key_scale_rate { 0 } else { 2 })).min(63),
+        };
+        let decay_step = |rate: u8| match rate {
+            0 => 0.0,
+            rate => 2f32.powf(rate as f32 / 4.0) * DECAY_STEP,
+        };
+        let sustain_level = patch.sustain_level as f32 * 3.0;
+
+        match self.state {
+            EnvelopeState::Attack => {
+                match rate(patch.attack_rate) {
+                    0 => {}
+                    60.. => self.envelope = 0.0,
+                    rate => {
+                        let step = (2f32.powf(rate as f32 / 4.0) * ATTACK_STEP).min(1.0);
+                        self.envelope -= (self.envelope + 1.0) * step;
+                    }
+                }
+                if self.envelope <= 0.0 {
+                    self.envelope = 0.0;
+                    self.state = EnvelopeState::Decay;
+                }
+            }
+            EnvelopeState::Decay => {
+                self.envelope += decay_step(rate(patch.decay_rate));
+                if self.envelope >= sustain_level {
+                    self.envelope = sustain_level;
+                    self.state = EnvelopeState::Sustain;
+                }
+            }
+            EnvelopeState::Sustain => {
+                // percussive instruments keep decaying while the key is held
+                if !patch.sustained {
+                    self.envelope += decay_step(rate(patch.release_rate));
+                }
+            }
+            EnvelopeState::Release => {
+                let release_rate = if sustain {
+                    5
+                } else if patch.sustained {
+                    patch.release_rate
+                } else {
+                    7
+                };
+                self.envelope += decay_step(rate(release_rate));
+            }
+        }
+        self.envelope = self.envelope.min(MAX_ATTENUATION);
+    }
+
+    /// Returns the operator output for the given phase shift and attenuation.
+    fn output(&self, patch: &OperatorPatch, phase_shift: f32, attenuation: f32) -> f32 {
+        let attenuation = self.envelope + attenuation;
+        if attenuation >= MAX_ATTENUATION {
+            return 0.0;
+        }
+        let wave = ((self.phase + phase_shift) * TAU).sin();
+        let wave = if patch.rectified { wave.max(0.0) } else { wave };
+        wave * 10f32.powf(-attenuation / 20.0)
+    }
+}
+
+struct Channel {
+    f_number: u16,
+    block: u8,
+    key_on: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+    modulator: Operator,
+    carrier: Operator,
+    /// Last two modulator outputs, for feedback.
+    feedback: [f32; 2],
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            f_number: 0,
+            block: 0,
+            key_on: false,
+            sustain: false,
+            instrument: 0,
+            volume: 0,
+            modulator: Operator::new(),
+            carrier: Operator::new(),
+            feedback: [0.0; 2],
+        }
+    }
+
+    fn key_scale_level(&self, patch: &OperatorPatch) -> f32 {
+        let level = KEY_SCALE_LEVELS[(self.f_number >> 5) as usize] - 6.0 * (7 - self.block) as f32;
+        level.max(0.0) * KEY_SCALE_FACTORS[patch.key_scale_level as usize]
+    }
+}
+
+pub struct Opll {
+    address: u8,
+    custom_patch: [u8; 8],
+    channels: [Channel; 6],
+    /// Position of the tremolo and vibrato oscillators, in cycles.
+    tremolo_phase: f32,
+    vibrato_phase: f32,
+    cycles: u8,
+    output: f32,
+}
+
+impl Opll {
+    pub fn new() -> Self {
+        Self {
+            address: 0,
+            custom_patch: [0; 8],
+            channels: std::array::from_fn(|_| Channel::new()),
+            tremolo_phase: 0.0,
+            vibrato_phase: 0.0,
+            cycles: 0,
+            output: 0.0,
+        }
+    }
+
+    pub fn write_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        let index = (self.address & 0x0F) as usize;
+        match self.address {
+            0x00..=0x07 => self.custom_patch[index] = value,
+            0x10..=0x15 => {
+                let channel = &mut self.channels[index];
+                channel.f_number = (channel.f_number & 0x100) | value as u16;
+            }
+            0x20..=0x25 => {
+                let channel = &mut self.channels[index];
+                channel.f_number = (channel.f_number & 0xFF) | (value as u16 & 1) << 8;
+                channel.block = (value >> 1) & 0b111;
+                channel.sustain = value & 0x20 != 0;
+                let key_on = value & 0x10 != 0;
+                if key_on && !channel.key_on {
+                    channel.modulator.key_on();
+                    channel.carrier.key_on();
+                    channel.feedback = [0.0; 2];
+                } else if !key_on && channel.key_on {
+                    channel.modulator.key_off();
+                    channel.carrier.key_off();
+                }
+                channel.key_on = key_on;
+            }
+            0x30..=0x35 => {
+                let channel = &mut self.channels[index];
+                channel.instrument = value >> 4;
+                channel.volume = value & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs one CPU cycle.
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+        if self.cycles == CYCLES_PER_SAMPLE {
+            self.cycles = 0;
+            self.output = self.generate_sample();
+        }
+    }
+
+    /// Sum of channel outputs, between -6 and 6.
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+
+    fn generate_sample(&mut self) -> f32 {
+        self.tremolo_phase = (self.tremolo_phase + TREMOLO_RATE / SAMPLE_RATE).fract();
+        self.vibrato_phase = (self.vibrato_phase + VIBRATO_RATE / SAMPLE_RATE).fract();
+        let tremolo = (1.0 - (self.tremolo_phase * TAU).cos()) * 0.5 * TREMOLO_DEPTH;
+        let vibrato = 1.0 + (self.vibrato_phase * TAU).sin() * VIBRATO_DEPTH;
+
+        let mut output = 0.0;
+        for channel in &mut self.channels {
+            let patch = match channel.instrument {
+                0 => &self.custom_patch,
+                instrument => &PATCHES[instrument as usize],
+            };
+            let modulator = OperatorPatch::new(patch, 0);
+            let carrier = OperatorPatch::new(patch, 1);
+            let total_level = (patch[2] & 0x3F) as f32 * 0.75;
+            let feedback = patch[3] & 0b111;
+
+            let key_scale = channel.block << 1 | (channel.f_number >> 8) as u8;
+            let sustain = channel.sustain;
+            channel
+                .modulator
+                .tick_envelope(&modulator, key_scale, sustain);
+            channel.carrier.tick_envelope(&carrier, key_scale, sustain);
+
+            // cycles per sample: F-number * 2^block * 49716 Hz / 2^19
+            let frequency = channel.f_number as f32 * (1 << channel.block) as f32 / 524288.0;
+            for (operator, patch) in [
+                (&mut channel.modulator, &modulator),
+                (&mut channel.carrier, &carrier),
+            ] {
+                let vibrato = if patch.vibrato { vibrato } else { 1.0 };
+                operator.phase = (operator.phase + frequency * patch.multiplier * vibrato).fract();
+            }
+
+            let tremolo_for = |patch: &OperatorPatch| if patch.tremolo { tremolo } else { 0.0 };
+
+            let feedback_shift = match feedback {
+                0 => 0.0,
+                feedback => {
+                    // from pi/16 at feedback 1 to 4 pi at feedback 7
+                    let average = (channel.feedback[0] + channel.feedback[1]) / 2.0;
+                    average * (1 << (feedback - 1)) as f32 / 32.0
+                }
+            };
+            let modulator_attenuation =
+                total_level + channel.key_scale_level(&modulator) + tremolo_for(&modulator);
+            let modulator_output =
+                channel
+                    .modulator
+                    .output(&modulator, feedback_shift, modulator_attenuation);
+            channel.feedback = [channel.feedback[1], modulator_output];
+
+            let carrier_attenuation = channel.volume as f32 * 3.0
+                + channel.key_scale_level(&carrier)
+                + tremolo_for(&carrier);
+            output += channel.carrier.output(
+                &carrier,
+                modulator_output * MODULATION_DEPTH,
+                carrier_attenuation,
+            );
+        }
+        output
+    }
+}
+
+impl SaveState for Operator {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.phase.to_bits());
+        state.write_u32(self.envelope.to_bits());
+        state.write_u8(self.state as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.phase = f32::from_bits(state.read_u32()?);
+        self.envelope = f32::from_bits(state.read_u32()?);
+        self.state = match state.read_u8()? {
+            0 => EnvelopeState::Attack,
+            1 => EnvelopeState::Decay,
+            2 => EnvelopeState::Sustain,
+            3 => EnvelopeState::Release,
+            _ => return Err("Save state: Invalid OPLL envelope state"),
+        };
+        Ok(())
+    }
+}
+
+impl SaveState for Channel {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.f_number);
+        state.write_u8(self.block);
+        state.write_bool(self.key_on);
+        state.write_bool(self.sustain);
+        state.write_u8(self.instrument);
+        state.write_u8(self.volume);
+        self.modulator.save_state(state);
+        self.carrier.save_state(state);
+        state.write_u32(self.feedback[0].to_bits());
+        state.write_u32(self.feedback[1].to_bits());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.f_number = state.read_u16()? & 0x1FF;
+        self.block = state.read_u8()? & 0b111;
+        self.key_on = state.read_bool()?;
+        self.sustain = state.read_bool()?;
+        self.instrument = state.read_u8()? & 0x0F;
+        self.volume = state.read_u8()? & 0x0F;
+        self.modulator.load_state(state)?;
+        self.carrier.load_state(state)?;
+        self.feedback[0] = f32::from_bits(state.read_u32()?);
+        self.feedback[1] = f32::from_bits(state.read_u32()?);
+        Ok(())
+    }
+}
+
+impl SaveState for Opll {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.address);
+        state.write_bytes(&self.custom_patch);
+        for channel in &self.channels {
+            channel.save_state(state);
+        }
+        state.write_u32(self.tremolo_phase.to_bits());
+        state.write_u32(self.vibrato_phase.to_bits());
+        state.write_u8(self.cycles);
+        state.write_u32(self.output.to_bits());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.address = state.read_u8()?;
+        state.read_bytes(&mut self.custom_patch)?;
+        for channel in &mut self.channels {
+            channel.load_state(state)?;
+        }
+        self.tremolo_phase = f32::from_bits(state.read_u32()?);
+        self.vibrato_phase = f32::from_bits(state.read_u32()?);
+        self.cycles = state.read_u8()? % CYCLES_PER_SAMPLE;
+        self.output = f32::from_bits(state.read_u32()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Mixer levels of the 5B's 32 logarithmic volume steps, 1.5 dB apart.
+#[rustfmt::skip]
+const SUNSOFT_5B_LEVELS: [u16; 32] = [
+    0, 45, 53, 64, 76, 90, 107, 127, 151, 179, 213, 253, 301, 357, 425, 505,
+    600, 713, 847, 1007, 1197, 1423, 1691, 2010, 2388, 2839, 3374, 4009, 4765, 5664, 6731, 8000,
+];
+
+/// CPU cycles per tick of the tone, noise and envelope generators.
+const SUNSOFT_5B_PRESCALER: u8 = 16;
+
+struct Sunsoft5bTone {
+    period: u16,
+    counter: u16,
+    output: bool,
+}
+
+impl Sunsoft5bTone {
+    fn new() -> Self {
+        Self {
+            period: 0,
+            counter: 0,
+            output: false,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.counter += 1;
+        if self.counter >= self.period {
+            self.counter = 0;
+            self.output = !self.output;
+        }
+    }
+}
+
+/// Sunsoft 5B audio, a YM2149F clone with three square channels sharing one
+/// noise generator and one envelope generator.
+pub struct Sunsoft5b {
+    address: u8,
+    tones: [Sunsoft5bTone; 3],
+    noise_period: u8,
+    noise_counter: u8,
+    /// 17-bit linear feedback shift register.
+    noise: u32,
+    mixer: u8,
+    volumes: [u8; 3],
+    envelope_period: u16,
+    envelope_counter: u16,
+    envelope_shape: u8,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+    prescaler: u8,
+}
+
+impl Sunsoft5b {
+    pub fn new() -> Self {
+        Self {
+            address: 0,
+            tones: [
+                Sunsoft5bTone::new(),
+                Sunsoft5bTone::new(),
+                Sunsoft5bTone::new(),
+            ],
+            noise_period: 0,
+            noise_counter: 0,
+            noise: 1,
+            mixer: 0,
+            volumes: [0; 3],
+            envelope_period: 0,
+            envelope_counter: 0,
+            envelope_shape: 0,
+            envelope_step: 0,
+            envelope_attack: false,
+            envelope_holding: true,
+            prescaler: 0,
+        }
+    }
+
+    pub fn write_address(&mut self, value: u8) {
+        self.address = value;
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        match self.address {
+            0x00..=0x05 => {
+                let tone = &mut self.tones[self.address as usize / 2];
+                tone.period = match self.address & 1 {
+                    0 => (tone.period & 0x0F00) | value as u16,
+                    _ => (tone.period & 0x00FF) | (value as u16 & 0x0F) << 8,
+                };
+            }
+            0x06 => self.noise_period = value & 0x1F,
+            0x07 => self.mixer = value,
+            0x08..=0x0A => self.volumes[self.address as usize - 0x08] = value & 0x1F,
+            0x0B => self.envelope_period = (self.envelope_period & 0xFF00) | value as u16,
+            0x0C => self.envelope_period = (self.envelope_period & 0x00FF) | (value as u16) << 8,
+            0x0D => {
+                self.envelope_shape = value & 0x0F;
+                self.envelope_step = 0;
+                self.envelope_counter = 0;
+                self.envelope_attack = value & 0b0100 != 0;
+                self.envelope_holding = false;
+            }
+            // I/O ports, not connected, and addresses with the upper bits
+            // set, which act as a chip select
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < SUNSOFT_5B_PRESCALER {
+            return;
+        }
+        self.prescaler = 0;
+
+        for tone in &mut self.tones {
+            tone.tick();
+        }
+
+        self.noise_counter += 1;
+        if self.noise_counter >= self.noise_period {
+            self.noise_counter = 0;
+            let feedback = (self.noise ^ (self.noise >> 3)) & 1;
+            self.noise = (self.noise >> 1) | feedback << 16;
+        }
+
+        self.envelope_counter += 1;
+        if self.envelope_counter >= self.envelope_period {
+            self.envelope_counter = 0;
+            self.tick_envelope();
+        }
+    }
+
+    fn tick_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+        self.envelope_step += 1;
+        if self.envelope_step < 32 {
+            return;
+        }
+        let continue_ = self.envelope_shape & 0b1000 != 0;
+        let alternate = self.envelope_shape & 0b0010 != 0;
+        let hold = self.envelope_shape & 0b0001 != 0;
+        if !continue_ {
+            self.envelope_step = 31;
+            self.envelope_attack = false;
+            self.envelope_holding = true;
+        } else if hold {
+            self.envelope_step = 31;
+            self.envelope_attack ^= alternate;
+            self.envelope_holding = true;
+        } else {
+            self.envelope_step = 0;
+            self.envelope_attack ^= alternate;
+        }
+    }
+
+    fn envelope_level(&self) -> u8 {
+        match self.envelope_attack {
+            true => self.envelope_step,
+            false => 31 - self.envelope_step,
+        }
+    }
+
+    pub fn output(&self) -> i32 {
+        let noise = self.noise & 1 != 0;
+        (0..3)
+            .map(|channel| {
+                let tone_disabled = self.mixer & (0b001 << channel) != 0;
+                let noise_disabled = self.mixer & (0b1000 << channel) != 0;
+                let high =
+                    (self.tones[channel].output || tone_disabled) && (noise || noise_disabled);
+                let volume = self.volumes[channel];
+                let level = match volume & 0x10 {
+                    0 if volume & 0x0F == 0 => 0,
+                    0 => (volume & 0x0F) * 2 + 1,
+                    _ => self.envelope_level(),
+                };
+                match high {
+                    true => SUNSOFT_5B_LEVELS[level as usize] as i32,
+                    false => 0,
+                }
+            })
+            .sum()
+    }
+}
+
+impl SaveState for Sunsoft5bTone {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.period);
+        state.write_u16(self.counter);
+        state.write_bool(self.output);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.period = state.read_u16()?;
+        self.counter = state.read_u16()?;
+        self.output = state.read_bool()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Sunsoft5b {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.address);
+        for tone in &self.tones {
+            tone.save_state(state);
+        }
+        state.write_u8(self.noise_period);
+        state.write_u8(self.noise_counter);
+        state.write_u32(self.noise);
+        state.write_u8(self.mixer);
+        state.write_bytes(&self.volumes);
+        state.write_u16(self.envelope_period);
+        state.write_u16(self.envelope_counter);
+        state.write_u8(self.envelope_shape);
+        state.write_u8(self.envelope_step);
+        state.write_bool(self.envelope_attack);
+        state.write_bool(self.envelope_holding);
+        state.write_u8(self.prescaler);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.address = state.read_u8()?;
+        for tone in &mut self.tones {
+            tone.load_state(state)?;
+        }
+        self.noise_period = state.read_u8()?;
+        self.noise_counter = state.read_u8()?;
+        self.noise = state.read_u32()?;
+        self.mixer = state.read_u8()?;
+        state.read_bytes(&mut self.volumes)?;
+        self.envelope_period = state.read_u16()?;
+        self.envelope_counter = state.read_u16()?;
+        self.envelope_shape = state.read_u8()?;
+        self.envelope_step = state.read_u8()?;
+        self.envelope_attack = state.read_bool()?;
+        self.envelope_holding = state.read_bool()?;
+        self.prescaler = state.read_u8()?;
+        Ok(())
+    }
+}
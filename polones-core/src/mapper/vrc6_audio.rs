@@ -0,0 +1,242 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Mixer level of one step of VRC6 channel volume, close to a 2A03 pulse.
+const VRC6_MIX_STEP: i32 = 640;
+
+struct Vrc6Pulse {
+    volume: u8,
+    duty: u8,
+    ignore_duty: bool,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+}
+
+impl Vrc6Pulse {
+    fn new() -> Self {
+        Self {
+            volume: 0,
+            duty: 0,
+            ignore_duty: false,
+            period: 0,
+            enabled: false,
+            timer: 0,
+            step: 15,
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.ignore_duty = value & 0x80 != 0;
+                self.duty = (value >> 4) & 0b111;
+                self.volume = value & 0b1111;
+            }
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | (value as u16 & 0x0F) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 15;
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self, period_shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> period_shift;
+            self.step = self.step.wrapping_sub(1) & 0b1111;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        match self.enabled && (self.ignore_duty || self.step <= self.duty) {
+            true => self.volume,
+            false => 0,
+        }
+    }
+}
+
+struct Vrc6Sawtooth {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl Vrc6Sawtooth {
+    fn new() -> Self {
+        Self {
+            rate: 0,
+            period: 0,
+            enabled: false,
+            timer: 0,
+            step: 0,
+            accumulator: 0,
+        }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => self.rate = value & 0b111111,
+            1 => self.period = (self.period & 0x0F00) | value as u16,
+            _ => {
+                self.period = (self.period & 0x00FF) | (value as u16 & 0x0F) << 8;
+                self.enabled = value & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self, period_shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> period_shift;
+            // the rate is added on every second clock, the 14th clock resets
+            self.step += 1;
+            if self.step == 14 {
+                self.step = 0;
+                self.accumulator = 0;
+            } else if self.step & 1 == 0 {
+                self.accumulator = self.accumulator.wrapping_add(self.rate);
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+/// VRC6 audio: two pulse channels and a sawtooth channel.
+pub struct Vrc6Audio {
+    pulse1: Vrc6Pulse,
+    pulse2: Vrc6Pulse,
+    sawtooth: Vrc6Sawtooth,
+    frequency_control: u8,
+}
+
+impl Vrc6Audio {
+    pub fn new() -> Self {
+        Self {
+            pulse1: Vrc6Pulse::new(),
+            pulse2: Vrc6Pulse::new(),
+            sawtooth: Vrc6Sawtooth::new(),
+            frequency_control: 0,
+        }
+    }
+
+    fn period_shift(&self) -> u8 {
+        if self.frequency_control & 0b100 != 0 {
+            8
+        } else if self.frequency_control & 0b010 != 0 {
+            4
+        } else {
+            0
+        }
+    }
+
+    /// Writes a register at $9000-$9003, $A000-$A002 or $B000-$B002.
+    pub fn write(&mut self, address: u16, value: u8) {
+        let register = address & 0b11;
+        match address & 0xF003 {
+            0x9000..=0x9002 => self.pulse1.write(register, value),
+            0x9003 => self.frequency_control = value,
+            0xA000..=0xA002 => self.pulse2.write(register, value),
+            0xB000..=0xB002 => self.sawtooth.write(register, value),
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) {
+        // bit 0 halts all channels
+        if self.frequency_control & 1 == 0 {
+            let period_shift = self.period_shift();
+            self.pulse1.tick(period_shift);
+            self.pulse2.tick(period_shift);
+            self.sawtooth.tick(period_shift);
+        }
+    }
+
+    pub fn output(&self) -> i32 {
+        let output = self.pulse1.output() + self.pulse2.output() + self.sawtooth.output();
+        output as i32 * VRC6_MIX_STEP
+    }
+}
+
+impl SaveState for Vrc6Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.volume);
+        state.write_u8(self.duty);
+        state.write_bool(self.ignore_duty);
+        state.write_u16(self.period);
+        state.write_bool(self.enabled);
+        state.write_u16(self.timer);
+        state.write_u8(self.step);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.volume = state.read_u8()? & 0b1111;
+        self.duty = state.read_u8()? & 0b111;
+        self.ignore_duty = state.read_bool()?;
+        self.period = state.read_u16()? & 0x0FFF;
+        self.enabled = state.read_bool()?;
+        self.timer = state.read_u16()?;
+        self.step = state.read_u8()? & 0b1111;
+        Ok(())
+    }
+}
+
+impl SaveState for Vrc6Sawtooth {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.rate);
+        state.write_u16(self.period);
+        state.write_bool(self.enabled);
+        state.write_u16(self.timer);
+        state.write_u8(self.step);
+        state.write_u8(self.accumulator);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.rate = state.read_u8()? & 0b111111;
+        self.period = state.read_u16()? & 0x0FFF;
+        self.enabled = state.read_bool()?;
+        self.timer = state.read_u16()?;
+        self.step = state.read_u8()? % 14;
+        self.accumulator = state.read_u8()?;
+        Ok(())
+    }
+}
+
+impl SaveState for Vrc6Audio {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.sawtooth.save_state(state);
+        state.write_u8(self.frequency_control);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.sawtooth.load_state(state)?;
+        self.frequency_control = state.read_u8()?;
+        Ok(())
+    }
+}
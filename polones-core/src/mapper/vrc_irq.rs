@@ -0,0 +1,95 @@
+use crate::cpu::Cpu;
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// IRQ counter of Konami VRC4, VRC6 and VRC7. Counts CPU cycles or, through
+/// a prescaler, scanlines.
+pub struct VrcIrq {
+    pub latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enabled_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    pub fn new() -> Self {
+        Self {
+            latch: 0,
+            counter: 0,
+            prescaler: 341,
+            enabled: false,
+            enabled_after_ack: false,
+            cycle_mode: false,
+            pending: false,
+        }
+    }
+
+    pub fn write_control(&mut self, value: u8) {
+        self.enabled_after_ack = value & 0b001 != 0;
+        self.enabled = value & 0b010 != 0;
+        self.cycle_mode = value & 0b100 != 0;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = 341;
+        }
+        self.pending = false;
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enabled_after_ack;
+    }
+
+    /// Runs one CPU cycle.
+    pub fn tick(&mut self, cpu: &mut Cpu) {
+        if self.enabled {
+            if self.cycle_mode {
+                self.clock_counter();
+            } else {
+                // 341 PPU dots per scanline, 3 dots per CPU cycle
+                self.prescaler -= 3;
+                if self.prescaler <= 0 {
+                    self.prescaler += 341;
+                    self.clock_counter();
+                }
+            }
+        }
+        if self.pending {
+            cpu.irq();
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
+
+impl SaveState for VrcIrq {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.latch);
+        state.write_u8(self.counter);
+        state.write_u16(self.prescaler as u16);
+        state.write_bool(self.enabled);
+        state.write_bool(self.enabled_after_ack);
+        state.write_bool(self.cycle_mode);
+        state.write_bool(self.pending);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.latch = state.read_u8()?;
+        self.counter = state.read_u8()?;
+        self.prescaler = state.read_u16()? as i16;
+        self.enabled = state.read_bool()?;
+        self.enabled_after_ack = state.read_bool()?;
+        self.cycle_mode = state.read_bool()?;
+        self.pending = state.read_bool()?;
+        Ok(())
+    }
+}
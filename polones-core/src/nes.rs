@@ -1,14 +1,86 @@
 use crate::apu::Apu;
 use crate::cpu::Cpu;
+use crate::disk_file::DiskFile;
 use crate::game_file::GameFile;
 use crate::io::Io;
-use crate::mapper::{mapper_from_game_file, Mapper};
+use crate::mapper::{
+    mapper_from_disk_file, mapper_from_game_file, mapper_from_nsf_file, Mapper, NametableSource,
+};
+use crate::nsf_file::NsfFile;
+use crate::palette::Palette;
 use crate::ppu::Ppu;
 use crate::ram::Ram;
+use crate::save_state::{SaveState, StateReader, StateWriter, MAGIC, VERSION};
 
 pub type Frame = [[(u8, u8, u8); 256]; 240];
 pub type Sample = u16;
 
+/// TV system of the console. It decides clock rates, frame timing, audio
+/// periods and colours.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl Region {
+    /// CPU cycles per second.
+    pub fn cpu_clock_rate(self) -> u32 {
+        match self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+            Region::Dendy => 1_773_448,
+        }
+    }
+
+    /// Number of scanlines in a frame, including the pre-render scanline.
+    pub fn scanlines(self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal | Region::Dendy => 312,
+        }
+    }
+
+    /// Scanline on which vblank starts.
+    pub fn vblank_scanline(self) -> u16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// Converts emphasis bits of PPUMASK into a number whose bits 0, 1 and 2
+    /// emphasize red, green and blue. PAL and Dendy PPUs have the red and green
+    /// bits swapped.
+    pub fn emphasis(self, mask: u8) -> u8 {
+        let emphasis = mask >> 5;
+        match self {
+            Region::Ntsc => emphasis,
+            Region::Pal | Region::Dendy => {
+                (emphasis & 0b100) | ((emphasis & 0b001) << 1) | ((emphasis & 0b010) >> 1)
+            }
+        }
+    }
+}
+
+impl SaveState for Region {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(*self as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        *self = match state.read_u8()? {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::Dendy,
+            _ => return Err("Save state: Invalid region"),
+        };
+        Ok(())
+    }
+}
+
 pub struct Display {
     pub frame: Box<Frame>,
     pub cpu_cycle: u64,
@@ -110,11 +182,18 @@ pub struct Nes {
     pub ppu: Ppu,
     pub mapper: Box<dyn Mapper>,
     pub cpu_ram: Ram<{ 2 * 1024 }>,
-    pub ppu_nametable_ram: Ram<{ 2 * 1024 }>,
+    /// Console's 2 KiB CIRAM followed by 2 KiB of four-screen cartridge VRAM.
+    pub ppu_nametable_ram: Ram<{ 4 * 1024 }>,
     pub ppu_palette_ram: Ram<32>,
+    /// RAM holding the trainer for mappers without PRG RAM at $7000.
+    pub trainer_ram: Option<Ram<512>>,
     pub display: Display,
     pub input: Input,
     pub audio: Audio,
+    region: Region,
+    /// Counts CPU cycles on PAL consoles, where the PPU runs 16 dots every
+    /// 5 CPU cycles.
+    pal_ppu_phase: u8,
 }
 
 pub struct OamDma {
@@ -140,27 +219,95 @@ impl OamDma {
     }
 }
 
+impl SaveState for OamDma {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.page.is_some());
+        state.write_u8(self.page.unwrap_or(0));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        let pending = state.read_bool()?;
+        let page = state.read_u8()?;
+        self.page = pending.then_some(page);
+        Ok(())
+    }
+}
+
 impl Nes {
     pub fn new(game: GameFile) -> Result<Self, &'static str> {
+        let region = game.region.unwrap_or_default();
+        let trainer = game.trainer().map(|trainer| trainer.to_vec());
+        let mapper = mapper_from_game_file(game)?;
+        Ok(Self::with_mapper(mapper, region, trainer))
+    }
+
+    /// Creates a Famicom Disk System with the first side of the disk inserted.
+    /// The BIOS is the 8 KiB ROM of the Disk System's RAM adapter.
+    pub fn new_fds(disk: &DiskFile, bios: &[u8]) -> Result<Self, &'static str> {
+        let mapper = mapper_from_disk_file(disk, bios)?;
+        Ok(Self::with_mapper(mapper, Region::Ntsc, None))
+    }
+
+    /// Creates a console playing NSF music, starting with the NSF's starting
+    /// song.
+    pub fn new_nsf(nsf: &NsfFile) -> Result<Self, &'static str> {
+        let region = nsf.region.unwrap_or_default();
+        let mapper = mapper_from_nsf_file(nsf)?;
+        let mut nes = Self::with_mapper(mapper, region, None);
+        nes.select_song(nsf.starting_song)?;
+        Ok(nes)
+    }
+
+    fn with_mapper(mapper: Box<dyn Mapper>, region: Region, trainer: Option<Vec<u8>>) -> Self {
         let mut nes = Self {
-            mapper: mapper_from_game_file(game)?,
+            mapper,
             cpu: Cpu::new(),
             cpu_ram: Ram::new(),
             oam_dma: OamDma::new(),
             ppu: Ppu::new(),
             ppu_nametable_ram: Ram::new(),
             ppu_palette_ram: Ram::new(),
+            trainer_ram: None,
             apu: Apu::new(),
             io: Io::new(),
             display: Display::new(),
             input: Input::new(),
             audio: Audio::new(),
+            region: Region::Ntsc,
+            pal_ppu_phase: 0,
         };
+        nes.set_region(region);
+
+        if let Some(trainer) = trainer {
+            if !nes.mapper.load_trainer(&trainer) {
+                let mut ram = Ram::new();
+                ram.as_mut_slice().copy_from_slice(&trainer);
+                nes.trainer_ram = Some(ram);
+            }
+        }
 
         let (cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
         cpu.reset(&mut cpu_bus);
 
-        Ok(nes)
+        nes
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Changes the TV system of the console. By default it's taken from the
+    /// game file header.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.pal_ppu_phase = 0;
+        self.ppu.region = region;
+        self.apu.region = region;
+    }
+
+    /// Changes colours used to draw frames. Takes effect immediately.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.ppu.palette = palette;
     }
 
     pub fn run_one_cpu_tick(&mut self) {
@@ -172,11 +319,14 @@ impl Nes {
             ppu,
             ppu_nametable_ram,
             ppu_palette_ram,
+            trainer_ram,
             apu,
             io,
             display,
             input,
             audio,
+            region,
+            pal_ppu_phase,
         } = self;
 
         let mut peripherals = Peripherals {
@@ -193,12 +343,14 @@ impl Nes {
             cpu_ram,
             ppu_nametable_ram,
             ppu_palette_ram,
+            trainer_ram,
         };
 
         cpu.tick(&mut cpu_bus);
         cpu_bus.oam_dma.tick(cpu);
         cpu_bus.io.tick(cpu, &mut peripherals);
-        cpu_bus.apu.tick(cpu, &mut peripherals);
+        let expansion_audio = cpu_bus.mapper.audio_output();
+        cpu_bus.apu.tick(cpu, &mut peripherals, expansion_audio);
 
         let mut ppu_bus = PpuBus {
             mapper,
@@ -208,6 +360,13 @@ impl Nes {
         ppu.tick(cpu, &mut ppu_bus, &mut peripherals);
         ppu.tick(cpu, &mut ppu_bus, &mut peripherals);
         ppu.tick(cpu, &mut ppu_bus, &mut peripherals);
+        if *region == Region::Pal {
+            *pal_ppu_phase += 1;
+            if *pal_ppu_phase == 5 {
+                *pal_ppu_phase = 0;
+                ppu.tick(cpu, &mut ppu_bus, &mut peripherals);
+            }
+        }
         ppu_bus.mapper.tick(cpu);
     }
 
@@ -221,6 +380,141 @@ impl Nes {
         }
     }
 
+    /// Captures the state of the console. Display, input and audio output
+    /// are not part of the state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.write_bytes(&MAGIC);
+        state.write_u16(VERSION);
+        self.save_components(&mut state);
+        state.into_bytes()
+    }
+
+    /// Restores a state captured with `save_state`. The state must come from
+    /// the same game. If the state can't be loaded, the console is left
+    /// unchanged.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        let mut state = StateReader::new(data);
+        let mut magic = [0; 4];
+        state.read_bytes(&mut magic)?;
+        if magic != MAGIC {
+            return Err("Save state: Invalid magic number");
+        }
+        if state.read_u16()? != VERSION {
+            return Err("Save state: Unsupported version");
+        }
+
+        let mut backup = StateWriter::new();
+        self.save_components(&mut backup);
+
+        let result = self.load_components(&mut state).and_then(|()| {
+            if state.is_at_end() {
+                Ok(())
+            } else {
+                Err("Save state: Unexpected data after end of state")
+            }
+        });
+        if result.is_err() {
+            self.load_components(&mut StateReader::new(&backup.into_bytes()))
+                .expect("Restoring backup state failed");
+        }
+        result
+    }
+
+    /// Returns battery-backed memory of the cartridge, if it has any. For Disk
+    /// System games it's the contents of the disk. Frontends should store it
+    /// between sessions.
+    pub fn battery_ram(&self) -> Option<&[u8]> {
+        self.mapper.battery_ram()
+    }
+
+    /// Restores battery-backed memory returned earlier by `battery_ram`.
+    pub fn load_battery_ram(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        let ram = self
+            .mapper
+            .battery_ram_mut()
+            .ok_or("Battery RAM: Game has no battery-backed memory")?;
+        if ram.len() != data.len() {
+            return Err("Battery RAM: Unexpected size");
+        }
+        ram.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Number of disk sides of a Disk System game, 0 for cartridges.
+    pub fn disk_side_count(&self) -> usize {
+        self.mapper.disk_side_count()
+    }
+
+    /// Disk side in the drive, None if the drive is empty.
+    pub fn disk_side(&self) -> Option<usize> {
+        self.mapper.disk_side()
+    }
+
+    /// Ejects the disk and, if `side` is given, inserts that side about half a
+    /// second later, so the game notices the change.
+    pub fn insert_disk_side(&mut self, side: Option<usize>) -> Result<(), &'static str> {
+        if side.is_some_and(|side| side >= self.mapper.disk_side_count()) {
+            return Err("Disk System: No such disk side");
+        }
+        self.mapper.insert_disk_side(side);
+        Ok(())
+    }
+
+    /// Number of songs of NSF music, 0 for games.
+    pub fn song_count(&self) -> usize {
+        self.mapper.song_count()
+    }
+
+    /// Song being played, counted from 0. None for games.
+    pub fn song(&self) -> Option<usize> {
+        self.mapper.song()
+    }
+
+    /// Starts playing a song of NSF music from the beginning. The play rate
+    /// follows the current region.
+    pub fn select_song(&mut self, song: usize) -> Result<(), &'static str> {
+        if song >= self.mapper.song_count() {
+            return Err("NSF: No such song");
+        }
+        self.mapper.select_song(song, self.region);
+        let (cpu, mut cpu_bus) = self.split_into_cpu_and_bus();
+        cpu.reset(&mut cpu_bus);
+        Ok(())
+    }
+
+    fn save_components(&self, state: &mut StateWriter) {
+        self.region.save_state(state);
+        state.write_u8(self.pal_ppu_phase);
+        self.cpu.save_state(state);
+        self.oam_dma.save_state(state);
+        self.apu.save_state(state);
+        self.io.save_state(state);
+        self.ppu.save_state(state);
+        self.cpu_ram.save_state(state);
+        self.ppu_nametable_ram.save_state(state);
+        self.ppu_palette_ram.save_state(state);
+        self.trainer_ram.save_state(state);
+        self.mapper.save_state(state);
+    }
+
+    fn load_components(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        let mut region = self.region;
+        region.load_state(state)?;
+        self.set_region(region);
+        self.pal_ppu_phase = state.read_u8()?;
+        self.cpu.load_state(state)?;
+        self.oam_dma.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.io.load_state(state)?;
+        self.ppu.load_state(state)?;
+        self.cpu_ram.load_state(state)?;
+        self.ppu_nametable_ram.load_state(state)?;
+        self.ppu_palette_ram.load_state(state)?;
+        self.trainer_ram.load_state(state)?;
+        self.mapper.load_state(state)
+    }
+
     pub fn split_into_cpu_and_bus(&mut self) -> (&mut Cpu, CpuBus) {
         let Nes {
             cpu,
@@ -232,6 +526,7 @@ impl Nes {
             cpu_ram,
             ppu_nametable_ram,
             ppu_palette_ram,
+            trainer_ram,
             ..
         } = self;
         (
@@ -245,6 +540,7 @@ impl Nes {
                 cpu_ram,
                 ppu_nametable_ram,
                 ppu_palette_ram,
+                trainer_ram,
             },
         )
     }
@@ -257,8 +553,9 @@ pub struct CpuBus<'a> {
     pub ppu: &'a mut Ppu,
     pub mapper: &'a mut Box<dyn Mapper>,
     pub cpu_ram: &'a mut Ram<{ 2 * 1024 }>,
-    pub ppu_nametable_ram: &'a mut Ram<{ 2 * 1024 }>,
+    pub ppu_nametable_ram: &'a mut Ram<{ 4 * 1024 }>,
     pub ppu_palette_ram: &'a mut Ram<32>,
+    pub trainer_ram: &'a mut Option<Ram<512>>,
 }
 
 impl<'a> CpuBus<'a> {
@@ -290,6 +587,9 @@ impl<'a> CpuBus<'a> {
             0x4014 => self.oam_dma.read(address),
             0x4000..=0x4015 => self.apu.read(address),
             0x4016..=0x4017 => self.io.read(address),
+            0x7000..=0x71FF if self.trainer_ram.is_some() => {
+                self.trainer_ram.as_ref().unwrap().read(address as usize)
+            }
             address if self.mapper.cpu_address_mapped(address) => self.mapper.cpu_read(address),
             _ => {
                 eprintln!(
@@ -311,6 +611,10 @@ impl<'a> CpuBus<'a> {
             0x4014 => self.oam_dma.write(address, value),
             0x4016 => self.io.write(address, value),
             0x4000..=0x4017 => self.apu.write(address, value),
+            0x7000..=0x71FF if self.trainer_ram.is_some() => {
+                let ram = self.trainer_ram.as_mut().unwrap();
+                ram.write(address as usize, value);
+            }
             address if self.mapper.cpu_address_mapped(address) => {
                 self.mapper.cpu_write(address, value)
             }
@@ -326,7 +630,7 @@ impl<'a> CpuBus<'a> {
 
 pub struct PpuBus<'a> {
     pub mapper: &'a mut Box<dyn Mapper>,
-    pub ppu_nametable_ram: &'a mut Ram<{ 2 * 1024 }>,
+    pub ppu_nametable_ram: &'a mut Ram<{ 4 * 1024 }>,
     pub ppu_palette_ram: &'a mut Ram<32>,
 }
 
@@ -335,9 +639,12 @@ impl<'a> PpuBus<'a> {
         match address & 0x3FFF {
             0x3F00..=0x3FFF => self.ppu_palette_ram.read(address as usize),
             _a if self.mapper.ppu_address_mapped(address) => self.mapper.ppu_read(address),
-            0x2000..=0x3EFF => self
-                .ppu_nametable_ram
-                .read(self.mapper.ppu_nametable_address_mapped(address) as usize),
+            0x0000..=0x3EFF => match self.mapper.nametable_read_source(address) {
+                NametableSource::Vram(page) => self
+                    .ppu_nametable_ram
+                    .read((page as usize) << 10 | (address as usize & 0x03FF)),
+                NametableSource::Mapper => self.mapper.ppu_read(address),
+            },
             _ => unreachable!(),
         }
     }
@@ -356,10 +663,12 @@ impl<'a> PpuBus<'a> {
                 }
             }
             _a if self.mapper.ppu_address_mapped(address) => self.mapper.ppu_write(address, value),
-            0x2000..=0x3EFF => self.ppu_nametable_ram.write(
-                self.mapper.ppu_nametable_address_mapped(address) as usize,
-                value,
-            ),
+            0x0000..=0x3EFF => match self.mapper.nametable_source(address) {
+                NametableSource::Vram(page) => self
+                    .ppu_nametable_ram
+                    .write((page as usize) << 10 | (address as usize & 0x03FF), value),
+                NametableSource::Mapper => self.mapper.ppu_write(address, value),
+            },
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,364 @@
+use crate::nes::Region;
+
+/// Size of the NSF header. Program data follows it.
+const HEADER_SIZE: usize = 0x80;
+
+/// Expansion audio chips an NSF writes to, from byte $7B of the header.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExpansionChips {
+    pub vrc6: bool,
+    pub vrc7: bool,
+    pub fds: bool,
+    pub mmc5: bool,
+    pub n163: bool,
+    pub sunsoft_5b: bool,
+}
+
+impl ExpansionChips {
+    fn from_byte(byte: u8) -> Self {
+        Self {
+            vrc6: byte & 0x01 != 0,
+            vrc7: byte & 0x02 != 0,
+            fds: byte & 0x04 != 0,
+            mmc5: byte & 0x08 != 0,
+            n163: byte & 0x10 != 0,
+            sunsoft_5b: byte & 0x20 != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NsfFormat {
+    Nsf,
+    /// NSF with metadata chunks after program data.
+    Nsf2,
+    /// Chunked format, everything is stored in chunks.
+    Nsfe,
+}
+
+/// Song information from NSFe and NSF2 metadata.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NsfTrack {
+    pub label: Option<String>,
+    /// Milliseconds the song plays before it fades out. None if unknown.
+    pub length: Option<u32>,
+    /// Milliseconds of fading out after `length`. None if unknown, players
+    /// pick their own.
+    pub fade: Option<u32>,
+}
+
+/// Music ripped from a game in the NSF, NSF2 or NSFe format: the game's sound
+/// code and data with addresses of routines initializing and playing songs.
+pub struct NsfFile {
+    pub name: String,
+    /// Program data, loaded at `load_address`.
+    data: Vec<u8>,
+    pub format: NsfFormat,
+    pub song_count: usize,
+    /// Song played first, counted from 0.
+    pub starting_song: usize,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    /// Person who ripped the music, known only from metadata.
+    pub ripper: String,
+    /// Microseconds between calls of the play routine on NTSC consoles.
+    pub ntsc_speed: u16,
+    /// Microseconds between calls of the play routine on PAL consoles.
+    pub pal_speed: u16,
+    /// Initial 4 KiB banks at $8000-$FFFF. None if the music doesn't switch
+    /// banks.
+    pub banks: Option<[u8; 8]>,
+    /// TV system the music was made for, None if it plays on both.
+    pub region: Option<Region>,
+    pub expansion_chips: ExpansionChips,
+    /// One entry for every song. Empty information for plain NSF files.
+    pub tracks: Vec<NsfTrack>,
+    /// Songs in the order they should be played, None if the music has no
+    /// playlist.
+    pub playlist: Option<Vec<usize>>,
+}
+
+impl std::fmt::Debug for NsfFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("NsfFile");
+        s.field("name", &self.name);
+        s.field("data", &("length", self.data.len()));
+        s.field("format", &self.format);
+        s.field("song_count", &self.song_count);
+        s.field("starting_song", &self.starting_song);
+        s.field("load_address", &self.load_address);
+        s.field("init_address", &self.init_address);
+        s.field("play_address", &self.play_address);
+        s.field("title", &self.title);
+        s.field("artist", &self.artist);
+        s.field("copyright", &self.copyright);
+        s.field("ripper", &self.ripper);
+        s.field("ntsc_speed", &self.ntsc_speed);
+        s.field("pal_speed", &self.pal_speed);
+        s.field("banks", &self.banks);
+        s.field("region", &self.region);
+        s.field("expansion_chips", &self.expansion_chips);
+        s.field("tracks", &self.tracks);
+        s.field("playlist", &self.playlist);
+        s.finish()
+    }
+}
+
+/// Reads a null-terminated string from a fixed-size header field.
+fn header_string(field: &[u8]) -> String {
+    let length = field.iter().take_while(|&&byte| byte != 0).count();
+    String::from_utf8_lossy(&field[..length]).into_owned()
+}
+
+/// Splits a chunk into null-terminated strings.
+fn chunk_strings(chunk: &[u8]) -> impl Iterator<Item = String> + '_ {
+    let chunk = chunk.strip_suffix(&[0]).unwrap_or(chunk);
+    chunk
+        .split(|&byte| byte == 0)
+        .map(|string| String::from_utf8_lossy(string).into_owned())
+}
+
+/// Splits a chunk into milliseconds. Negative values mean unknown.
+fn chunk_times(chunk: &[u8]) -> impl Iterator<Item = Option<u32>> + '_ {
+    chunk.chunks_exact(4).map(|bytes| {
+        let time = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        u32::try_from(time).ok()
+    })
+}
+
+type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits data into chunks made of a 4-byte length, a 4-byte ID and data,
+/// ending with the NEND chunk.
+fn read_chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>, &'static str> {
+    let mut chunks = Vec::new();
+    loop {
+        if data.len() < 8 {
+            return Err("NSF file: Missing NEND chunk");
+        }
+        let length = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let id = [data[4], data[5], data[6], data[7]];
+        // the length can overflow usize on 32-bit targets
+        let end = 8usize
+            .checked_add(length)
+            .ok_or("NSF file: Chunk longer than file")?;
+        let chunk = data.get(8..end).ok_or("NSF file: Chunk longer than file")?;
+        if &id == b"NEND" {
+            return Ok(chunks);
+        }
+        chunks.push((id, chunk));
+        data = &data[end..];
+    }
+}
+
+impl NsfFile {
+    pub fn read(name: String, data: Vec<u8>) -> Result<Self, &'static str> {
+        let (mut nsf, metadata) = if data.starts_with(b"NSFE") {
+            Self::read_nsfe(name, &data)?
+        } else {
+            Self::read_nsf(name, &data)?
+        };
+
+        if nsf.data.is_empty() {
+            return Err("NSF file: No program data");
+        }
+        if nsf.song_count == 0 {
+            return Err("NSF file: No songs");
+        }
+        // Disk System music can be loaded to RAM at $6000-$7FFF.
+        let lowest_load_address = match nsf.expansion_chips.fds {
+            true => 0x6000,
+            false => 0x8000,
+        };
+        if nsf.load_address < lowest_load_address {
+            return Err("NSF file: Invalid load address");
+        }
+        nsf.starting_song = nsf.starting_song.min(nsf.song_count - 1);
+
+        nsf.tracks = vec![NsfTrack::default(); nsf.song_count];
+        for (id, chunk) in metadata {
+            nsf.read_metadata_chunk(&id, chunk)?;
+        }
+        Ok(nsf)
+    }
+
+    fn read_nsf(name: String, data: &[u8]) -> Result<(Self, Vec<Chunk<'_>>), &'static str> {
+        if data.len() < HEADER_SIZE {
+            return Err("NSF file: Header too short");
+        }
+        if &data[0..5] != b"NESM\x1A" {
+            return Err("NSF file: Invalid magic number");
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let mut banks = [0; 8];
+        banks.copy_from_slice(&data[0x70..0x78]);
+
+        // NSF2 stores the length of program data, metadata follows it
+        let version = data[5];
+        let data_length = u32::from_le_bytes([data[0x7D], data[0x7E], data[0x7F], 0]) as usize;
+        let (program, metadata) = match (version, data_length) {
+            (2, 1..) => {
+                let end = HEADER_SIZE + data_length;
+                if data.len() < end {
+                    return Err("NSF file: Program data missing");
+                }
+                (&data[HEADER_SIZE..end], read_chunks(&data[end..])?)
+            }
+            _ => (&data[HEADER_SIZE..], Vec::new()),
+        };
+
+        let nsf = Self {
+            name,
+            data: program.to_vec(),
+            format: match version {
+                2 => NsfFormat::Nsf2,
+                _ => NsfFormat::Nsf,
+            },
+            song_count: data[6] as usize,
+            // songs in the header are counted from 1
+            starting_song: (data[7] as usize).saturating_sub(1),
+            load_address: word(0x08),
+            init_address: word(0x0A),
+            play_address: word(0x0C),
+            title: header_string(&data[0x0E..0x2E]),
+            artist: header_string(&data[0x2E..0x4E]),
+            copyright: header_string(&data[0x4E..0x6E]),
+            ripper: String::new(),
+            ntsc_speed: word(0x6E),
+            pal_speed: word(0x78),
+            banks: banks.iter().any(|&bank| bank != 0).then_some(banks),
+            region: Self::region_from_byte(data[0x7A]),
+            expansion_chips: ExpansionChips::from_byte(data[0x7B]),
+            tracks: Vec::new(),
+            playlist: None,
+        };
+        Ok((nsf, metadata))
+    }
+
+    fn read_nsfe(name: String, data: &[u8]) -> Result<(Self, Vec<Chunk<'_>>), &'static str> {
+        let mut nsf = Self {
+            name,
+            data: Vec::new(),
+            format: NsfFormat::Nsfe,
+            song_count: 0,
+            starting_song: 0,
+            load_address: 0,
+            init_address: 0,
+            play_address: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ripper: String::new(),
+            ntsc_speed: 0,
+            pal_speed: 0,
+            banks: None,
+            region: None,
+            expansion_chips: ExpansionChips::default(),
+            tracks: Vec::new(),
+            playlist: None,
+        };
+        let mut info_found = false;
+        let mut metadata = Vec::new();
+
+        for (id, chunk) in read_chunks(&data[4..])? {
+            let word = |offset: usize| match chunk.get(offset..offset + 2) {
+                Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]),
+                None => 0,
+            };
+            match &id {
+                b"INFO" => {
+                    if chunk.len() < 8 {
+                        return Err("NSF file: INFO chunk too short");
+                    }
+                    info_found = true;
+                    nsf.load_address = word(0);
+                    nsf.init_address = word(2);
+                    nsf.play_address = word(4);
+                    nsf.region = Self::region_from_byte(chunk[6]);
+                    nsf.expansion_chips = ExpansionChips::from_byte(chunk[7]);
+                    // optional fields, the starting song counts from 0 here
+                    nsf.song_count = chunk.get(8).map_or(1, |&songs| songs as usize);
+                    nsf.starting_song = chunk.get(9).map_or(0, |&song| song as usize);
+                }
+                b"DATA" => nsf.data = chunk.to_vec(),
+                b"BANK" => {
+                    let mut banks = [0; 8];
+                    let length = chunk.len().min(8);
+                    banks[..length].copy_from_slice(&chunk[..length]);
+                    nsf.banks = Some(banks);
+                }
+                b"RATE" => {
+                    nsf.ntsc_speed = word(0);
+                    nsf.pal_speed = word(2);
+                }
+                _ => metadata.push((id, chunk)),
+            }
+        }
+
+        if !info_found {
+            return Err("NSF file: Missing INFO chunk");
+        }
+        Ok((nsf, metadata))
+    }
+
+    fn region_from_byte(byte: u8) -> Option<Region> {
+        match byte & 0b11 {
+            0 => Some(Region::Ntsc),
+            1 => Some(Region::Pal),
+            _ => None,
+        }
+    }
+
+    /// Reads a chunk of NSFe or NSF2 metadata. Chunks with IDs starting with
+    /// a capital letter can't be skipped by players that don't know them.
+    fn read_metadata_chunk(&mut self, id: &[u8; 4], chunk: &[u8]) -> Result<(), &'static str> {
+        match id {
+            b"auth" => {
+                let mut strings = chunk_strings(chunk);
+                let fields = [
+                    &mut self.title,
+                    &mut self.artist,
+                    &mut self.copyright,
+                    &mut self.ripper,
+                ];
+                for (field, string) in fields.into_iter().zip(&mut strings) {
+                    *field = string;
+                }
+            }
+            b"tlbl" => {
+                for (track, label) in self.tracks.iter_mut().zip(chunk_strings(chunk)) {
+                    track.label = Some(label);
+                }
+            }
+            b"time" => {
+                for (track, length) in self.tracks.iter_mut().zip(chunk_times(chunk)) {
+                    track.length = length;
+                }
+            }
+            b"fade" => {
+                for (track, fade) in self.tracks.iter_mut().zip(chunk_times(chunk)) {
+                    track.fade = fade;
+                }
+            }
+            b"plst" => {
+                let song_count = self.song_count;
+                let songs = chunk.iter().map(|&song| song as usize);
+                self.playlist = Some(songs.filter(|&song| song < song_count).collect());
+            }
+            // NSF2 metadata may repeat what the header says
+            b"INFO" | b"DATA" | b"BANK" | b"RATE" => {}
+            [b'A'..=b'Z', ..] => return Err("NSF file: Unsupported chunk"),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
@@ -1,3 +1,125 @@
+/// Colours of the PPU palette for every colour index and emphasis
+/// combination. Colours are indexed by `emphasis << 6 | color`, where bits 0,
+/// 1 and 2 of emphasis stand for red, green and blue.
+#[derive(Clone)]
+pub struct Palette {
+    colors: Box<[(u8, u8, u8); 512]>,
+}
+
+/// Names of palettes accepted by `Palette::builtin`.
+pub const BUILTIN_PALETTES: [&str; 2] = ["polones", "2c02"];
+
+impl Palette {
+    /// Returns one of the palettes shipped with the emulator.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "polones" => Some(Self::from_colors(&PALLETTE)),
+            "2c02" => Some(Self::generate_2c02()),
+            _ => None,
+        }
+    }
+
+    /// Extends a 64-colour palette with emphasis. Emphasis dims the other
+    /// channels.
+    pub fn from_colors(palette: &[(u8, u8, u8); 64]) -> Self {
+        let mut colors = Box::new([(0, 0, 0); 512]);
+        for emphasis in 0..8 {
+            let dim = |value: u8, channel: usize| {
+                let emphasized = emphasis & (1 << channel) > 0;
+                if emphasis != 0 && (!emphasized || emphasis == 0b111) {
+                    (value as u16 * 209 / 256) as u8
+                } else {
+                    value
+                }
+            };
+            for (color, (r, g, b)) in palette.iter().enumerate() {
+                colors[emphasis << 6 | color] = (dim(*r, 0), dim(*g, 1), dim(*b, 2));
+            }
+        }
+        Self { colors }
+    }
+
+    /// Reads a .pal file with 64 colours (192 bytes) or with 64 colours for
+    /// each emphasis combination (1536 bytes).
+    pub fn from_pal_file(data: &[u8]) -> Result<Self, &'static str> {
+        let rgb = |chunk: &[u8]| (chunk[0], chunk[1], chunk[2]);
+        match data.len() {
+            192 => {
+                let mut colors = [(0, 0, 0); 64];
+                for (color, chunk) in colors.iter_mut().zip(data.chunks_exact(3)) {
+                    *color = rgb(chunk);
+                }
+                Ok(Self::from_colors(&colors))
+            }
+            1536 => {
+                let mut colors = Box::new([(0, 0, 0); 512]);
+                for (color, chunk) in colors.iter_mut().zip(data.chunks_exact(3)) {
+                    *color = rgb(chunk);
+                }
+                Ok(Self { colors })
+            }
+            _ => Err("Palette: Unexpected .pal file size"),
+        }
+    }
+
+    pub fn colors(&self) -> &[(u8, u8, u8); 512] {
+        &self.colors
+    }
+
+    /// Decodes the composite video signal of the NTSC 2C02 PPU. Signal levels
+    /// come from measurements of real hardware, normalized so that black is 0
+    /// and white is 1.
+    fn generate_2c02() -> Self {
+        const LOW_LEVELS: [f32; 4] = [-0.117, 0.000, 0.308, 0.715];
+        const HIGH_LEVELS: [f32; 4] = [0.399, 0.684, 1.000, 1.000];
+        const EMPHASIS_ATTENUATION: f32 = 0.746;
+        // rotates hues to line up with the colour burst, in 1/12 of a cycle
+        const HUE_OFFSET: f32 = 4.0;
+
+        let mut colors = Box::new([(0, 0, 0); 512]);
+        for (index, rgb) in colors.iter_mut().enumerate() {
+            let emphasis = index >> 6;
+            let hue = index & 0x0F;
+            // $xE and $xF are black
+            let level = if hue > 13 { 1 } else { (index >> 4) & 0b11 };
+            let low = LOW_LEVELS[level];
+            let high = if hue > 12 { low } else { HIGH_LEVELS[level] };
+            let low = if hue == 0 { high } else { low };
+
+            // The signal is a square wave, high for half of the 12 phases.
+            let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+            for phase in 0..12 {
+                let in_phase = |hue: usize| (hue + phase) % 12 < 6;
+                let mut signal = if in_phase(hue) { high } else { low };
+                if hue < 14
+                    && ((emphasis & 1 > 0 && in_phase(0))
+                        || (emphasis & 2 > 0 && in_phase(4))
+                        || (emphasis & 4 > 0 && in_phase(8)))
+                {
+                    signal *= EMPHASIS_ATTENUATION;
+                }
+                let angle = std::f32::consts::PI * (phase as f32 + HUE_OFFSET) / 6.0;
+                y += signal / 12.0;
+                i += signal * angle.cos() / 12.0;
+                q += signal * angle.sin() / 12.0;
+            }
+
+            let to_u8 = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+            *rgb = (
+                to_u8(y + 0.946882 * i + 0.623557 * q),
+                to_u8(y - 0.274788 * i - 0.635691 * q),
+                to_u8(y - 1.108545 * i + 1.709007 * q),
+            );
+        }
+        Self { colors }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::from_colors(&PALLETTE)
+    }
+}
 
 pub static PALLETTE: [(u8, u8, u8); 64] = [
     (0x65, 0x65, 0x65),
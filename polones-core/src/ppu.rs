@@ -1,73 +1,9 @@
 use crate::cpu::Cpu;
-use crate::nes::{Frame, Peripherals, PpuBus};
+use crate::mapper::PpuEvent;
+use crate::nes::{Frame, Peripherals, PpuBus, Region};
+use crate::palette::Palette;
 use crate::ram::Ram;
-
-pub static PALLETTE: [(u8, u8, u8); 64] = [
-    (0x65, 0x65, 0x65),
-    (0x00, 0x2D, 0x69),
-    (0x13, 0x1F, 0x7F),
-    (0x3C, 0x13, 0x7C),
-    (0x60, 0x0B, 0x62),
-    (0x73, 0x0A, 0x37),
-    (0x71, 0x0F, 0x07),
-    (0x5A, 0x1A, 0x00),
-    (0x34, 0x28, 0x00),
-    (0x0B, 0x34, 0x00),
-    (0x00, 0x3C, 0x00),
-    (0x00, 0x3D, 0x10),
-    (0x00, 0x38, 0x40),
-    (0x00, 0x00, 0x00),
-    (0x00, 0x00, 0x00),
-    (0x00, 0x00, 0x00),
-    (0xAE, 0xAE, 0xAE),
-    (0x0F, 0x63, 0xB3),
-    (0x40, 0x51, 0xD0),
-    (0x78, 0x41, 0xCC),
-    (0xA7, 0x36, 0xA9),
-    (0xC0, 0x34, 0x70),
-    (0xBD, 0x3C, 0x30),
-    (0x9F, 0x4A, 0x00),
-    (0x6D, 0x5C, 0x00),
-    (0x36, 0x6D, 0x00),
-    (0x07, 0x77, 0x04),
-    (0x00, 0x79, 0x3D),
-    (0x00, 0x72, 0x7D),
-    (0x00, 0x00, 0x00),
-    (0x00, 0x00, 0x00),
-    (0x00, 0x00, 0x00),
-    (0xFE, 0xFE, 0xFF),
-    (0x5D, 0xB3, 0xFF),
-    (0x8F, 0xA1, 0xFF),
-    (0xC8, 0x90, 0xFF),
-    (0xF7, 0x85, 0xFA),
-    (0xFF, 0x83, 0xC0),
-    (0xFF, 0x8B, 0x7F),
-    (0xEF, 0x9A, 0x49),
-    (0xBD, 0xAC, 0x2C),
-    (0x85, 0xBC, 0x2F),
-    (0x55, 0xC7, 0x53),
-    (0x3C, 0xC9, 0x8C),
-    (0x3E, 0xC2, 0xCD),
-    (0x4E, 0x4E, 0x4E),
-    (0x00, 0x00, 0x00),
-    (0x00, 0x00, 0x00),
-    (0xFE, 0xFE, 0xFF),
-    (0xBC, 0xDF, 0xFF),
-    (0xD1, 0xD8, 0xFF),
-    (0xE8, 0xD1, 0xFF),
-    (0xFB, 0xCD, 0xFD),
-    (0xFF, 0xCC, 0xE5),
-    (0xFF, 0xCF, 0xCA),
-    (0xF8, 0xD5, 0xB4),
-    (0xE4, 0xDC, 0xA8),
-    (0xCC, 0xE3, 0xA9),
-    (0xB9, 0xE8, 0xB8),
-    (0xAE, 0xE8, 0xD0),
-    (0xAF, 0xE5, 0xEA),
-    (0xB6, 0xB6, 0xB6),
-    (0x00, 0x00, 0x00),
-    (0x00, 0x00, 0x00),
-];
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
 #[derive(Clone, Copy, Default)]
 #[repr(transparent)]
@@ -169,6 +105,8 @@ impl Loopy {
 }
 
 pub struct Ppu {
+    pub region: Region,
+    pub palette: Palette,
     pub scanline: u16,
     pub dot: u16,
 
@@ -220,6 +158,8 @@ pub struct Ppu {
 impl Ppu {
     pub fn new() -> Self {
         Self {
+            region: Region::Ntsc,
+            palette: Palette::default(),
             scanline: 0,
             dot: 0,
 
@@ -382,7 +322,15 @@ impl Ppu {
                         palette_ram.read(0x10 | (palette << 2) as usize | color as usize)
                     }
                 };
-                let get_rgb = |color: u8| PALLETTE[color as usize];
+                // Greyscale keeps only the brightness bits of the colour.
+                let color_mask = if self.mask_register.get_greyscale() {
+                    0b00110000
+                } else {
+                    0b00111111
+                };
+                let emphasis = self.region.emphasis(self.mask_register.0) as usize;
+                let colors = &self.palette.colors()[emphasis << 6..];
+                let get_rgb = |color: u8| colors[(color & color_mask) as usize];
 
                 let rgb = match (foreground, background) {
                     (
@@ -595,6 +543,10 @@ impl Ppu {
         //               261 +-----------------+----------+--------+------+
         //                   |        C1       |    C2    |   C3   |  C4  |
         //                   +-----------------+----------+--------+------+
+        //
+        // PAL and Dendy frames have 50 more scanlines in region B1, so the
+        // pre-render scanline (C) is 311 instead of 261. Scanline numbers in
+        // comments refer to NTSC.
 
         if self.scanline < 240 {
             if self.dot < 257 {
@@ -604,6 +556,12 @@ impl Ppu {
                         self.clear_secondary_oam();
                     } else if self.dot == 256 {
                         self.evaluate_sprites();
+                    } else if self.dot == 1 {
+                        ppu_bus.mapper.ppu_event(if self.rendering_enabled() {
+                            PpuEvent::ScanlineStart(self.scanline)
+                        } else {
+                            PpuEvent::RenderingStopped
+                        });
                     }
                     draw_pixel!();
                     fetch_background_tiles!();
@@ -619,10 +577,16 @@ impl Ppu {
                     fetch_sprites_8_64!();
                 } else if self.dot == 257 {
                     update_scroll_horizontal!();
+                    if self.rendering_enabled() {
+                        ppu_bus.mapper.ppu_event(PpuEvent::SpriteFetches);
+                    }
                 }
                 self.dot += 1;
             } else if self.dot < 337 {
                 // region A3
+                if self.dot == 321 && self.rendering_enabled() {
+                    ppu_bus.mapper.ppu_event(PpuEvent::BackgroundFetches);
+                }
                 fetch_background_tiles!();
                 rotate_pattern_and_attribute_shift_registers!();
                 load_pattern_and_attribute_shift_registers!();
@@ -639,10 +603,12 @@ impl Ppu {
                     self.dot += 1;
                 }
             }
-        } else if self.scanline < 261 {
+        } else if self.scanline < self.region.scanlines() - 1 {
             // region B1
-            if self.scanline == 241 && self.dot == 1 {
+            if self.scanline == self.region.vblank_scanline() && self.dot == 1 {
                 start_vblank!();
+            } else if self.scanline == 240 && self.dot == 1 {
+                ppu_bus.mapper.ppu_event(PpuEvent::RenderingStopped);
             }
             if self.dot == 340 {
                 self.dot = 0;
@@ -651,7 +617,7 @@ impl Ppu {
                 self.dot += 1;
             }
         } else
-        /* self.scanline == 261 */
+        /* self.scanline == pre-render scanline */
         {
             // region C1
             if self.dot < 257 {
@@ -672,12 +638,18 @@ impl Ppu {
                     fetch_sprites_8_64!();
                 } else if self.dot == 257 {
                     update_scroll_horizontal!();
+                    if self.rendering_enabled() {
+                        ppu_bus.mapper.ppu_event(PpuEvent::SpriteFetches);
+                    }
                 } else if self.dot >= 280 && self.dot <= 304 {
                     update_scroll_vertical!();
                 }
                 self.dot += 1;
             } else if self.dot < 337 {
                 // region C3
+                if self.dot == 321 && self.rendering_enabled() {
+                    ppu_bus.mapper.ppu_event(PpuEvent::BackgroundFetches);
+                }
                 fetch_background_tiles!();
                 rotate_pattern_and_attribute_shift_registers!();
                 load_pattern_and_attribute_shift_registers!();
@@ -691,8 +663,8 @@ impl Ppu {
                     self.scanline = 0;
                     // On odd frames scanline 261 dot 340 should be skipped.
                     // However, it's easier to run it normally and then skip
-                    // to scanline 0 dot 1
-                    self.dot = self.odd as u16;
+                    // to scanline 0 dot 1. PAL and Dendy PPUs don't skip.
+                    self.dot = (self.odd && self.region == Region::Ntsc) as u16;
                     self.odd = !self.odd;
                 } else {
                     self.dot += 1;
@@ -747,6 +719,8 @@ impl Ppu {
             0x2000 => {
                 self.control_register = ControlRegister(value);
                 self.t.set_nametable_select(value & 0b11);
+                let event = PpuEvent::LargeSprites(self.control_register.get_sprite_height());
+                ppu_bus.mapper.ppu_event(event);
                 // TODO implement early NMI trigger bug
             }
             0x2001 => self.mask_register = MaskRegister(value),
@@ -892,6 +866,10 @@ impl Ppu {
         self.sprites_next_line = sprites_found;
     }
 
+    fn rendering_enabled(&self) -> bool {
+        self.mask_register.get_show_background() || self.mask_register.get_show_sprites()
+    }
+
     fn clear_secondary_oam(&mut self) {
         for byte in &mut self.sprite_secondary_oam[..] {
             *byte = 0xFF;
@@ -961,6 +939,103 @@ impl Ppu {
     }
 }
 
+impl SaveState for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.scanline);
+        state.write_u16(self.dot);
+        state.write_u8(self.control_register.0);
+        state.write_u8(self.mask_register.0);
+        state.write_u8(self.status_register.0);
+        state.write_bool(self.vblank);
+        state.write_u8(self.oam_address);
+        state.write_u8(self.ppu_read_buffer);
+        state.write_u8(self.horizontal_scroll);
+        state.write_u8(self.vertical_scroll);
+        state.write_u8(self.vertical_scroll_next_frame);
+        for row in self.buffer.iter() {
+            for pixel in row {
+                state.write_bytes(&[pixel.0, pixel.1, pixel.2]);
+            }
+        }
+        state.write_u32(self.buffer_index as u32);
+        state.write_bytes(&self.oam);
+        state.write_bool(self.odd);
+        state.write_u16(self.v.0);
+        state.write_u16(self.t.0);
+        state.write_u8(self.x);
+        state.write_bool(self.w);
+        state.write_u16(self.pattern_low_shift_register);
+        state.write_u16(self.pattern_high_shift_register);
+        state.write_u16(self.attribute_low_shift_register);
+        state.write_u16(self.attribute_high_shift_register);
+        state.write_u8(self.nametable_byte);
+        state.write_u8(self.attribute);
+        state.write_u8(self.bg_tile_byte_low);
+        state.write_u8(self.bg_tile_byte_high);
+        state.write_u8(self.sprite_limit as u8);
+        state.write_bytes(&self.sprite_secondary_oam);
+        state.write_bytes(&self.sprite_patterns_low);
+        state.write_bytes(&self.sprite_patterns_high);
+        state.write_bytes(&self.sprite_attributes);
+        state.write_bytes(&self.sprite_counters);
+        state.write_bool(self.sprite_0_next_scanline);
+        state.write_bool(self.sprite_0_current_scanline);
+        state.write_u8(self.sprites_next_line as u8);
+        state.write_u8(self.sprites_current_line as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        self.scanline = state.read_u16()?;
+        self.dot = state.read_u16()?;
+        self.control_register = ControlRegister(state.read_u8()?);
+        self.mask_register = MaskRegister(state.read_u8()?);
+        self.status_register = StatusRegister(state.read_u8()?);
+        self.vblank = state.read_bool()?;
+        self.oam_address = state.read_u8()?;
+        self.ppu_read_buffer = state.read_u8()?;
+        self.horizontal_scroll = state.read_u8()?;
+        self.vertical_scroll = state.read_u8()?;
+        self.vertical_scroll_next_frame = state.read_u8()?;
+        for row in self.buffer.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel = (state.read_u8()?, state.read_u8()?, state.read_u8()?);
+            }
+        }
+        self.buffer_index = state.read_u32()? as usize;
+        if self.buffer_index >= 256 * 240 {
+            return Err("Save state: Invalid PPU buffer index");
+        }
+        state.read_bytes(&mut self.oam)?;
+        self.odd = state.read_bool()?;
+        self.v = Loopy(state.read_u16()?);
+        self.t = Loopy(state.read_u16()?);
+        self.x = state.read_u8()?;
+        self.w = state.read_bool()?;
+        self.pattern_low_shift_register = state.read_u16()?;
+        self.pattern_high_shift_register = state.read_u16()?;
+        self.attribute_low_shift_register = state.read_u16()?;
+        self.attribute_high_shift_register = state.read_u16()?;
+        self.nametable_byte = state.read_u8()?;
+        self.attribute = state.read_u8()?;
+        self.bg_tile_byte_low = state.read_u8()?;
+        self.bg_tile_byte_high = state.read_u8()?;
+        self.sprite_limit = state.read_u8()? as usize;
+        state.read_bytes(&mut self.sprite_secondary_oam)?;
+        state.read_bytes(&mut self.sprite_patterns_low)?;
+        state.read_bytes(&mut self.sprite_patterns_high)?;
+        state.read_bytes(&mut self.sprite_attributes)?;
+        state.read_bytes(&mut self.sprite_counters)?;
+        self.sprite_0_next_scanline = state.read_bool()?;
+        self.sprite_0_current_scanline = state.read_bool()?;
+        self.sprites_next_line = state.read_u8()? as usize;
+        self.sprites_current_line = state.read_u8()? as usize;
+        if self.sprite_limit > 64 || self.sprites_next_line > 64 || self.sprites_current_line > 64 {
+            return Err("Save state: Invalid PPU sprite count");
+        }
+        Ok(())
+    }
+}
+
 #[inline(always)]
 fn between_exc<T: PartialOrd>(start: T, end: T, value: T) -> bool {
     value >= start && value < end
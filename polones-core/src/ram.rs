@@ -1,3 +1,5 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
 /// Random access memory with SIZE bytes of data. Uses SIZE-1 as address mask.
 pub struct Ram<const SIZE: usize> {
     data: [u8; SIZE],
@@ -13,4 +15,20 @@ impl<const SIZE: usize> Ram<SIZE> {
     pub fn write(&mut self, address: usize, value: u8) {
         self.data[address & (SIZE - 1)] = value;
     }
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl<const SIZE: usize> SaveState for Ram<SIZE> {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        state.read_bytes(&mut self.data)
+    }
 }
@@ -0,0 +1,135 @@
+//! Binary save-state format.
+//!
+//! A save state starts with a magic number and a format version. The state of
+//! every console component follows in a fixed order. Components write and read
+//! their fields in the same order, so the format stores no field names or
+//! lengths. All numbers are little-endian.
+
+pub const MAGIC: [u8; 4] = *b"PLSS";
+pub const VERSION: u16 = 9;
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+        let mut bytes = [0; N];
+        self.read_bytes(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, &'static str> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err("Save state: Invalid boolean value"),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, &'static str> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    /// Fills `target` with the next `target.len()` bytes of the state.
+    pub fn read_bytes(&mut self, target: &mut [u8]) -> Result<(), &'static str> {
+        let end = self.position + target.len();
+        if end > self.data.len() {
+            return Err("Save state: Unexpected end of data");
+        }
+        target.copy_from_slice(&self.data[self.position..end]);
+        self.position = end;
+        Ok(())
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.position == self.data.len()
+    }
+}
+
+/// Implemented by every part of the console that holds emulation state.
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str>;
+}
+
+/// Optional components (e.g. cartridge RAM) write a presence flag. Loading
+/// fails if the flag does not match, since that means the state was saved
+/// with a different game.
+impl<T: SaveState> SaveState for Option<T> {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.is_some());
+        if let Some(inner) = self {
+            inner.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), &'static str> {
+        match (self, state.read_bool()?) {
+            (Some(inner), true) => inner.load_state(state),
+            (None, false) => Ok(()),
+            _ => Err("Save state: Component presence mismatch"),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// NROM game that plays a one-byte DMC sample over and over, restarting it
+/// from the DMC IRQ handler.
+fn dmc_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($C000)
+        0x78,                   // SEI
+        0xA9, 0x40,             // LDA #$40
+        0x8D, 0x17, 0x40,       // STA $4017
+        0xA9, 0x8F,             // LDA #$8F
+        0x8D, 0x10, 0x40,       // STA $4010
+        0xA9, 0x00,             // LDA #$00
+        0x8D, 0x12, 0x40,       // STA $4012
+        0x8D, 0x13, 0x40,       // STA $4013
+        0xA9, 0x10,             // LDA #$10
+        0x8D, 0x15, 0x40,       // STA $4015
+        0x58,                   // CLI
+        // main loop ($C019)
+        0xAD, 0x15, 0x40,       // LDA $4015
+        0x85, 0x01,             // STA $01
+        0x4C, 0x19, 0xC0,       // JMP $C019
+        // irq ($C021)
+        0xE6, 0x00,             // INC $00
+        0xA9, 0x10,             // LDA #$10
+        0x8D, 0x15, 0x40,       // STA $4015
+        0x40,                   // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x28, 0xC0, 0x00, 0xC0, 0x21, 0xC0]);
+
+    Header::ines(0).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn dmc_fetches_samples_and_raises_irq() {
+    let mut nes = Nes::new(dmc_game()).unwrap();
+    for _ in 0..2000 {
+        nes.run_one_cpu_instruction();
+    }
+
+    // The sample buffer empties once every 8 output clocks (8 * 54 cycles),
+    // so a new byte is fetched and a new IRQ raised at that rate.
+    let irqs = nes.cpu_ram.read(0);
+    assert!(
+        (12..=20).contains(&irqs),
+        "expected about 16 DMC IRQs, got {irqs}"
+    );
+}
+
+#[test]
+fn dmc_keeps_fetching_after_jam() {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        0xA9, 0x4F,             // LDA #$4F
+        0x8D, 0x10, 0x40,       // STA $4010
+        0xA9, 0x10,             // LDA #$10
+        0x8D, 0x15, 0x40,       // STA $4015
+        0x02,                   // JAM
+    ];
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+    let game = Header::ines(0).game(&prg_rom, &[0; 8 * 1024]);
+
+    // the looping sample is fetched while the CPU is jammed, stepping over
+    // instructions must not wait for DMA forever
+    let mut nes = Nes::new(game).unwrap();
+    for _ in 0..1000 {
+        nes.run_one_cpu_instruction();
+    }
+    assert!(nes.cpu.halted());
+    assert_eq!(nes.cpu.program_counter, 0xC00A);
+}
@@ -0,0 +1,229 @@
+use polones_core::game_file::GameFile;
+use polones_core::mapper::NametableSource::Vram;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// Bandai FCG game in the Nes 2.0 format with battery-backed memory of
+/// `nvram_shift` size. Each 16 KiB PRG bank and each 1 KiB CHR bank starts
+/// with its number. The last PRG bank starts a program that enables an IRQ
+/// after 1000 cycles and counts IRQs at $00.
+fn game(mapper: u16, submapper: u8, prg_banks: u8, chr_banks: u8, nvram_shift: u8) -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($C000)
+        0x78,                       // SEI
+        0xA9, 0x40,                 // LDA #$40
+        0x8D, 0x17, 0x40,           // STA $4017
+        0xA9, 0xE8,                 // LDA #$E8
+        0x8D, 0x0B, 0x80,           // STA $800B
+        0xA9, 0x03,                 // LDA #$03
+        0x8D, 0x0C, 0x80,           // STA $800C
+        0xA9, 0x01,                 // LDA #$01
+        0x8D, 0x0A, 0x80,           // STA $800A
+        0x58,                       // CLI
+        0x4C, 0x16, 0xC0,           // JMP $C016
+        // irq ($C019)
+        0xA9, 0x00,                 // LDA #$00
+        0x8D, 0x0A, 0x80,           // STA $800A
+        0xE6, 0x00,                 // INC $00
+        0x40,                       // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; prg_banks as usize * 16 * 1024];
+    for bank in 0..prg_banks as usize {
+        prg_rom[bank * 16 * 1024] = bank as u8;
+    }
+    let last_bank = prg_rom.len() - 16 * 1024;
+    prg_rom[last_bank..last_bank + program.len()].copy_from_slice(program);
+    let vectors = prg_rom.len() - 6;
+    prg_rom[vectors..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x19, 0xC0]);
+    let mut chr_rom = vec![0; chr_banks as usize * 1024];
+    for bank in 0..chr_banks as usize {
+        chr_rom[bank * 1024] = bank as u8;
+    }
+
+    let header = Header {
+        battery: true,
+        prg_nvram_shift: nvram_shift,
+        chr_ram_shift: if chr_banks == 0 { 7 } else { 0 },
+        ..Header::nes20(mapper, submapper)
+    };
+    header.game(&prg_rom, &chr_rom)
+}
+
+/// Game driving an EEPROM through $800D and reading it at $6000.
+struct I2c<'a> {
+    nes: &'a mut Nes,
+    msb_first: bool,
+}
+
+impl I2c<'_> {
+    fn lines(&mut self, scl: u8, sda: u8) {
+        self.nes.mapper.cpu_write(0x800D, scl << 5 | sda << 6);
+    }
+
+    fn start(&mut self) {
+        self.lines(0, 1);
+        self.lines(1, 1);
+        self.lines(1, 0);
+        self.lines(0, 0);
+    }
+
+    fn stop(&mut self) {
+        self.lines(0, 0);
+        self.lines(1, 0);
+        self.lines(1, 1);
+    }
+
+    fn send_bit(&mut self, bit: u8) {
+        self.lines(0, bit);
+        self.lines(1, bit);
+        self.lines(0, bit);
+    }
+
+    fn receive_bit(&mut self) -> u8 {
+        self.lines(0, 1);
+        self.lines(1, 1);
+        let bit = (self.nes.mapper.cpu_read(0x6000) >> 4) & 1;
+        self.lines(0, 1);
+        bit
+    }
+
+    fn shift(&self, i: u8) -> u8 {
+        if self.msb_first {
+            7 - i
+        } else {
+            i
+        }
+    }
+
+    /// Returns true if the EEPROM acknowledged the byte.
+    fn send(&mut self, byte: u8) -> bool {
+        for i in 0..8 {
+            self.send_bit((byte >> self.shift(i)) & 1);
+        }
+        self.receive_bit() == 0
+    }
+
+    fn receive(&mut self, acknowledge: bool) -> u8 {
+        let mut byte = 0;
+        for i in 0..8 {
+            byte |= self.receive_bit() << self.shift(i);
+        }
+        self.send_bit(!acknowledge as u8);
+        byte
+    }
+}
+
+#[test]
+fn fcg_banks() {
+    let mut nes = Nes::new(game(16, 4, 8, 128, 0)).unwrap();
+    nes.mapper.cpu_write(0x6008, 3);
+    nes.mapper.cpu_write(0x6003, 5);
+    nes.mapper.cpu_write(0x6009, 1);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 3);
+    // the last bank, starting with the program
+    assert_eq!(nes.mapper.cpu_read(0xC000), 0x78);
+    assert_eq!(nes.mapper.ppu_read(0x0C00), 5);
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(1));
+
+    // submapper 4 has no registers at $8000
+    nes.mapper.cpu_write(0x8008, 4);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 3);
+    assert!(nes.battery_ram().is_none());
+}
+
+#[test]
+fn lz93d50_irq() {
+    let mut nes = Nes::new(game(16, 5, 8, 128, 2)).unwrap();
+    for _ in 0..900 {
+        nes.run_one_cpu_tick();
+    }
+    assert_eq!(nes.cpu_ram.read(0), 0);
+    for _ in 0..10000 {
+        nes.run_one_cpu_tick();
+    }
+    assert_eq!(nes.cpu_ram.read(0), 1);
+}
+
+#[test]
+fn eeprom_24c02() {
+    let mut nes = Nes::new(game(16, 5, 8, 128, 2)).unwrap();
+    let mut i2c = I2c {
+        nes: &mut nes,
+        msb_first: true,
+    };
+
+    i2c.start();
+    assert!(i2c.send(0xA0));
+    assert!(i2c.send(0x10));
+    assert!(i2c.send(0x5A));
+    assert!(i2c.send(0xC3));
+    i2c.stop();
+
+    // random read
+    i2c.start();
+    assert!(i2c.send(0xA0));
+    assert!(i2c.send(0x10));
+    i2c.start();
+    assert!(i2c.send(0xA1));
+    assert_eq!(i2c.receive(true), 0x5A);
+    assert_eq!(i2c.receive(false), 0xC3);
+    i2c.stop();
+
+    // other devices on the bus are not acknowledged
+    i2c.start();
+    assert!(!i2c.send(0x40));
+    i2c.stop();
+
+    let eeprom = nes.battery_ram().unwrap();
+    assert_eq!(eeprom.len(), 256);
+    assert_eq!(eeprom[0x10..0x12], [0x5A, 0xC3]);
+}
+
+#[test]
+fn eeprom_24c01() {
+    let mut nes = Nes::new(game(159, 0, 8, 128, 1)).unwrap();
+    nes.load_battery_ram(&[0x77; 128]).unwrap();
+    let mut i2c = I2c {
+        nes: &mut nes,
+        msb_first: false,
+    };
+
+    i2c.start();
+    assert!(i2c.send(0x05));
+    assert!(i2c.send(0x3C));
+    i2c.stop();
+
+    i2c.start();
+    assert!(i2c.send(0x80 | 0x05));
+    assert_eq!(i2c.receive(true), 0x3C);
+    assert_eq!(i2c.receive(false), 0x77);
+    i2c.stop();
+
+    assert_eq!(nes.battery_ram().unwrap()[0x05], 0x3C);
+}
+
+#[test]
+fn sram_and_outer_bank() {
+    let mut nes = Nes::new(game(153, 0, 32, 0, 7)).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0xC000), 15);
+    nes.mapper.cpu_write(0x8000, 1);
+    nes.mapper.cpu_write(0x8008, 2);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 18);
+    // the program overwrote the bank number
+    assert_eq!(nes.mapper.cpu_read(0xC000), 0x78);
+    nes.mapper.cpu_write(0x8000, 0);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 2);
+
+    // SRAM works only when enabled
+    nes.mapper.cpu_write(0x6000, 0x12);
+    nes.mapper.cpu_write(0x800D, 0x20);
+    assert_eq!(nes.mapper.cpu_read(0x6000), 0);
+    nes.mapper.cpu_write(0x6000, 0x12);
+    assert_eq!(nes.mapper.cpu_read(0x6000), 0x12);
+    assert_eq!(nes.battery_ram().unwrap().len(), 8 * 1024);
+}
@@ -0,0 +1,57 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// Single-bank MMC1 game that stores a byte at $6000 and loops.
+fn mmc1_game(battery: bool) -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        0xAD, 0x00, 0x60,       // LDA $6000
+        0x18,                   // CLC
+        0x69, 0x01,             // ADC #$01
+        0x8D, 0x00, 0x60,       // STA $6000
+        0x4C, 0x09, 0xC0,       // JMP $C009
+    ];
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    let header = Header {
+        battery,
+        ..Header::ines(1)
+    };
+    header.game(&prg_rom, &[])
+}
+
+fn run_ticks(nes: &mut Nes, ticks: usize) {
+    for _ in 0..ticks {
+        nes.run_one_cpu_tick();
+    }
+}
+
+#[test]
+fn battery_ram_survives_restart() {
+    let mut nes = Nes::new(mmc1_game(true)).unwrap();
+    run_ticks(&mut nes, 1000);
+    let saved = nes.battery_ram().unwrap().to_vec();
+    assert_eq!(saved.len(), 8 * 1024);
+    assert_eq!(saved[0], 1);
+
+    let mut nes = Nes::new(mmc1_game(true)).unwrap();
+    nes.load_battery_ram(&saved).unwrap();
+    run_ticks(&mut nes, 1000);
+    assert_eq!(nes.battery_ram().unwrap()[0], 2);
+
+    assert!(nes.load_battery_ram(&saved[1..]).is_err());
+}
+
+#[test]
+fn games_without_battery_have_no_battery_ram() {
+    let mut nes = Nes::new(mmc1_game(false)).unwrap();
+    assert!(nes.battery_ram().is_none());
+    assert!(nes.load_battery_ram(&[0; 8 * 1024]).is_err());
+}
@@ -0,0 +1,51 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// Game in the Nes 2.0 format with PRG-ROM filled with $05. Each 16 KiB PRG
+/// bank and each 8 KiB CHR bank starts with its number. CNROM gets 32 KiB of
+/// PRG-ROM, other boards 128 KiB.
+fn game(mapper: u8, submapper: u8) -> GameFile {
+    let prg_banks = if mapper == 3 { 2 } else { 8 };
+    let mut prg_rom = vec![0x05; prg_banks * 16 * 1024];
+    for bank in 0..prg_banks {
+        prg_rom[bank * 16 * 1024] = bank as u8;
+    }
+    let mut chr_rom = vec![0; 32 * 1024];
+    for bank in 0..4 {
+        chr_rom[bank * 8 * 1024] = bank as u8;
+    }
+
+    Header::nes20(mapper as u16, submapper).game(&prg_rom, &chr_rom)
+}
+
+/// Writes 3 over ROM byte $05. With bus conflicts the board gets 1.
+fn selected_bank(mapper: u8, submapper: u8) -> u8 {
+    let mut nes = Nes::new(game(mapper, submapper)).unwrap();
+    nes.mapper.cpu_write(0xC001, 3);
+    match mapper {
+        2 => nes.mapper.cpu_read(0x8000),
+        3 => nes.mapper.ppu_read(0x0000),
+        _ => nes.mapper.cpu_read(0x8000) / 2,
+    }
+}
+
+#[test]
+fn submappers() {
+    for mapper in [2, 3, 7] {
+        assert_eq!(selected_bank(mapper, 1), 3, "mapper {mapper}");
+        assert_eq!(selected_bank(mapper, 2), 1, "mapper {mapper}");
+    }
+    // BNROM, submapper 1 is NINA-001 which has no register in ROM
+    assert_eq!(selected_bank(34, 2), 1);
+}
+
+#[test]
+fn board_defaults() {
+    for mapper in [2, 3, 7] {
+        assert_eq!(selected_bank(mapper, 0), 1, "mapper {mapper}");
+    }
+}
@@ -0,0 +1,83 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+/// Header of a game made up by a test.
+#[derive(Default)]
+pub struct Header {
+    pub mapper: u16,
+    pub submapper: u8,
+    /// Writes a Nes 2.0 header instead of an iNES one.
+    pub nes20: bool,
+    pub vertical_mirroring: bool,
+    pub battery: bool,
+    pub four_screen: bool,
+    /// Nes 2.0 RAM sizes as shift counts, 64 << shift bytes or 0 for none.
+    pub prg_ram_shift: u8,
+    pub prg_nvram_shift: u8,
+    pub chr_ram_shift: u8,
+}
+
+impl Header {
+    pub fn ines(mapper: u16) -> Self {
+        Self {
+            mapper,
+            ..Self::default()
+        }
+    }
+
+    /// Nes 2.0 header without RAM.
+    pub fn nes20(mapper: u16, submapper: u8) -> Self {
+        Self {
+            mapper,
+            submapper,
+            nes20: true,
+            ..Self::default()
+        }
+    }
+
+    /// Reads a game with this header. PRG-ROM must be made of 16 KiB banks,
+    /// CHR-ROM of 8 KiB banks.
+    pub fn game(&self, prg_rom: &[u8], chr_rom: &[u8]) -> GameFile {
+        let mut data = vec![b'N', b'E', b'S', 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        data[4] = (prg_rom.len() / (16 * 1024)) as u8;
+        data[5] = (chr_rom.len() / (8 * 1024)) as u8;
+        data[6] = (self.mapper as u8 & 0x0F) << 4
+            | (self.four_screen as u8) << 3
+            | (self.battery as u8) << 1
+            | self.vertical_mirroring as u8;
+        data[7] = self.mapper as u8 & 0xF0;
+        if self.nes20 {
+            data[7] |= 0x08;
+            data[8] = self.submapper << 4 | (self.mapper >> 8) as u8;
+            data[10] = self.prg_nvram_shift << 4 | self.prg_ram_shift;
+            data[11] = self.chr_ram_shift;
+        }
+        data.extend_from_slice(prg_rom);
+        data.extend_from_slice(chr_rom);
+        GameFile::read("test.nes".into(), data).unwrap()
+    }
+}
+
+/// Saves the state of `nes` and runs it for `ticks` CPU ticks, checking that
+/// a console of `game` loaded from the state plays the same audio. Returns
+/// the audio output after each tick. Mapper tests pin the sum of the outputs,
+/// so changes to the expansion audio chips show up.
+pub fn audio_after_save_state(nes: &mut Nes, game: GameFile, ticks: usize) -> Vec<i32> {
+    let audio = |nes: &mut Nes| {
+        (0..ticks)
+            .map(|_| {
+                nes.run_one_cpu_tick();
+                nes.mapper.audio_output()
+            })
+            .collect::<Vec<_>>()
+    };
+    let state = nes.save_state();
+    let outputs = audio(nes);
+    let mut loaded = Nes::new(game).unwrap();
+    loaded.load_state(&state).unwrap();
+    assert_eq!(audio(&mut loaded), outputs);
+    outputs
+}
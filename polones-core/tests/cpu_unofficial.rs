@@ -0,0 +1,50 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// NROM game exercising a few unofficial opcodes, ending with JAM.
+fn unofficial_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        0xA9, 0x5A,             // LDA #$5A
+        0x85, 0x10,             // STA $10
+        0xA7, 0x10,             // LAX $10
+        0xA9, 0x0F,             // LDA #$0F
+        0x87, 0x11,             // SAX $11
+        0xA9, 0x05,             // LDA #$05
+        0x85, 0x12,             // STA $12
+        0xC7, 0x12,             // DCP $12
+        0x08,                   // PHP
+        0x68,                   // PLA
+        0x85, 0x13,             // STA $13
+        0x02,                   // JAM
+    ];
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    Header::ines(0).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn unofficial_opcodes_and_jam() {
+    let mut nes = Nes::new(unofficial_game()).unwrap();
+    for _ in 0..100 {
+        nes.run_one_cpu_instruction();
+    }
+
+    // LAX loaded $5A into X, SAX stored A & X
+    assert_eq!(nes.cpu.x_index, 0x5A);
+    assert_eq!(nes.cpu_ram.read(0x11), 0x0A);
+    // DCP decremented memory to 4 and compared it with A = 5
+    assert_eq!(nes.cpu_ram.read(0x12), 0x04);
+    assert_eq!(nes.cpu_ram.read(0x13) & 0x01, 0x01);
+    assert_eq!(nes.cpu_ram.read(0x13) & 0x02, 0x00);
+
+    assert!(nes.cpu.halted());
+    assert_eq!(nes.cpu.program_counter, 0xC014);
+}
@@ -0,0 +1,127 @@
+use polones_core::game_file::GameFile;
+use polones_core::mapper::NametableSource::Vram;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// Game in the Nes 2.0 format. Each 16 KiB PRG bank and each 4 KiB CHR bank
+/// starts with its number, the rest of PRG-ROM is $FF to avoid bus conflicts.
+/// The game has 8 KiB of PRG RAM, and CHR-RAM if it has no CHR-ROM.
+fn game(mapper: u16, submapper: u8, prg_banks: u8, chr_banks: u8) -> GameFile {
+    let mut prg_rom = vec![0xFF; prg_banks as usize * 16 * 1024];
+    for bank in 0..prg_banks as usize {
+        prg_rom[bank * 16 * 1024] = bank as u8;
+    }
+    let mut chr_rom = vec![0; chr_banks as usize * 4 * 1024];
+    for bank in 0..chr_banks as usize {
+        chr_rom[bank * 4 * 1024] = bank as u8;
+    }
+
+    let header = Header {
+        prg_ram_shift: 7,
+        chr_ram_shift: if chr_banks == 0 { 7 } else { 0 },
+        ..Header::nes20(mapper, submapper)
+    };
+    header.game(&prg_rom, &chr_rom)
+}
+
+#[test]
+fn color_dreams() {
+    let mut nes = Nes::new(game(11, 0, 8, 32)).unwrap();
+    nes.mapper.cpu_write(0x8001, 0x31);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 2);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 3);
+    assert_eq!(nes.mapper.ppu_read(0x1000), 7);
+}
+
+#[test]
+fn bnrom() {
+    let mut nes = Nes::new(game(34, 2, 8, 0)).unwrap();
+    nes.mapper.cpu_write(0x8001, 3);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 6);
+    nes.mapper.ppu_write(0x1234, 0x56);
+    assert_eq!(nes.mapper.ppu_read(0x1234), 0x56);
+}
+
+#[test]
+fn nina_001() {
+    let mut nes = Nes::new(game(34, 1, 4, 16)).unwrap();
+    nes.mapper.cpu_write(0x7FFD, 1);
+    nes.mapper.cpu_write(0x7FFE, 9);
+    nes.mapper.cpu_write(0x7FFF, 4);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 2);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 9);
+    assert_eq!(nes.mapper.ppu_read(0x1000), 4);
+    // the registers are also written to RAM
+    assert_eq!(nes.mapper.cpu_read(0x7FFE), 9);
+}
+
+#[test]
+fn gxrom_and_jaleco_jf_11() {
+    for (mapper, address, chr_bank) in [(66, 0x8001, 1), (140, 0x6000, 5)] {
+        let mut nes = Nes::new(game(mapper, 0, 8, 16)).unwrap();
+        nes.mapper.cpu_write(address, 0x10 | chr_bank);
+        assert_eq!(nes.mapper.cpu_read(0xC000), 3, "mapper {mapper}");
+        assert_eq!(nes.mapper.ppu_read(0x0000), chr_bank * 2, "mapper {mapper}");
+    }
+}
+
+#[test]
+fn camerica() {
+    let mut nes = Nes::new(game(71, 1, 8, 0)).unwrap();
+    nes.mapper.cpu_write(0xC000, 2);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 2);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 7);
+
+    nes.mapper.cpu_write(0x9000, 0x10);
+    assert_eq!(nes.mapper.nametable_source(0x2000), Vram(1));
+}
+
+#[test]
+fn nina_03_06() {
+    let mut nes = Nes::new(game(79, 0, 4, 16)).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    cpu_bus.write(0x5F00, 0x0A);
+    assert_eq!(cpu_bus.read(0x8000), 2);
+    // address bit 8 is clear
+    cpu_bus.write(0x4200, 0);
+    assert_eq!(cpu_bus.read(0x8000), 2);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 4);
+}
+
+#[test]
+fn jaleco_jf_09() {
+    let mut nes = Nes::new(game(87, 0, 2, 8)).unwrap();
+    // bits are swapped
+    nes.mapper.cpu_write(0x6000, 1);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 4);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 1);
+}
+
+#[test]
+fn un1rom() {
+    let mut nes = Nes::new(game(94, 0, 8, 0)).unwrap();
+    nes.mapper.cpu_write(0x8001, 3 << 2);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 3);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 7);
+}
+
+#[test]
+fn bandai_single_screen() {
+    let mut nes = Nes::new(game(152, 0, 8, 32)).unwrap();
+    nes.mapper.cpu_write(0x8001, 0x80 | 0x20 | 0x05);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 2);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 7);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 10);
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(1));
+}
+
+#[test]
+fn unrom_fixed_first_bank() {
+    let mut nes = Nes::new(game(180, 0, 8, 0)).unwrap();
+    nes.mapper.cpu_write(0x8001, 5);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 0);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 5);
+}
@@ -0,0 +1,165 @@
+use polones_core::disk_file::{DiskFile, DISK_SIDE_SIZE};
+use polones_core::nes::Nes;
+
+/// Disk image without the fwNES header. Each side has the disk info block and
+/// the file amount block.
+fn disk_image(sides: usize) -> Vec<u8> {
+    let mut data = vec![0; sides * DISK_SIDE_SIZE];
+    for side in 0..sides {
+        let start = side * DISK_SIDE_SIZE;
+        data[start..start + 15].copy_from_slice(b"\x01*NINTENDO-HVC*");
+        data[start + 22] = side as u8;
+        data[start + 56] = 2;
+        data[start + 57] = 0;
+    }
+    data
+}
+
+fn disk(sides: usize) -> DiskFile {
+    DiskFile::read("test.fds".into(), disk_image(sides)).unwrap()
+}
+
+/// BIOS looping forever at $E000.
+fn bios() -> Vec<u8> {
+    let mut bios = vec![0; 8 * 1024];
+    bios[0..3].copy_from_slice(&[0x4C, 0x00, 0xE0]);
+    bios[0x1FFC] = 0x00;
+    bios[0x1FFD] = 0xE0;
+    bios
+}
+
+#[test]
+fn disk_file_formats() {
+    let disk = DiskFile::read("test.fds".into(), disk_image(2)).unwrap();
+    assert_eq!(disk.side_count(), 2);
+    assert_eq!(disk.side(1)[22], 1);
+
+    let mut data = vec![b'F', b'D', b'S', 0x1A, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    data.extend_from_slice(&disk_image(2));
+    let disk = DiskFile::read("test.fds".into(), data).unwrap();
+    assert_eq!(disk.side_count(), 2);
+    assert_eq!(disk.side(1)[22], 1);
+
+    assert!(DiskFile::read("test.fds".into(), vec![0; DISK_SIDE_SIZE]).is_err());
+    assert!(DiskFile::read("test.fds".into(), disk_image(1)[1..].to_vec()).is_err());
+}
+
+#[test]
+fn bios_size() {
+    assert!(Nes::new_fds(&disk(1), &[0; 4 * 1024]).is_err());
+}
+
+#[test]
+fn disk_read() {
+    let mut nes = Nes::new_fds(&disk(1), &bios()).unwrap();
+    nes.mapper.cpu_write(0x4023, 0x01);
+    // motor on, read mode, ready
+    nes.mapper.cpu_write(0x4025, 0x65);
+
+    let mut bytes = Vec::new();
+    for _ in 0..1_000_000 {
+        nes.mapper.tick(&mut nes.cpu);
+        if nes.mapper.cpu_read(0x4030) & 0x02 != 0 {
+            bytes.push(nes.mapper.cpu_read(0x4031));
+            if bytes.len() == 4 {
+                break;
+            }
+        }
+    }
+    // the block start mark, then the disk info block
+    assert_eq!(bytes, [0x80, 0x01, b'*', b'N']);
+}
+
+#[test]
+fn disk_write() {
+    let mut nes = Nes::new_fds(&disk(2), &bios()).unwrap();
+    assert_eq!(nes.battery_ram().unwrap().len() % 2, 0);
+    let side_length = nes.battery_ram().unwrap().len() / 2;
+
+    nes.mapper.cpu_write(0x4023, 0x01);
+    nes.mapper.cpu_write(0x4024, 0xAB);
+    // motor on, write mode, ready
+    nes.mapper.cpu_write(0x4025, 0x41);
+    for _ in 0..60_000 {
+        nes.mapper.tick(&mut nes.cpu);
+    }
+
+    let disk = nes.battery_ram().unwrap();
+    assert_eq!(disk[0], 0xAB);
+    assert_eq!(disk[side_length], 0);
+}
+
+#[test]
+fn timer_irq() {
+    let mut nes = Nes::new_fds(&disk(1), &bios()).unwrap();
+    nes.mapper.cpu_write(0x4023, 0x01);
+    nes.mapper.cpu_write(0x4020, 9);
+    nes.mapper.cpu_write(0x4021, 0);
+    nes.mapper.cpu_write(0x4022, 0x03);
+
+    let mut irqs = 0;
+    for _ in 0..100 {
+        nes.mapper.tick(&mut nes.cpu);
+        if nes.mapper.cpu_read(0x4030) & 0x01 != 0 {
+            irqs += 1;
+        }
+    }
+    assert_eq!(irqs, 10);
+
+    // without repeat the timer stops after the first IRQ
+    nes.mapper.cpu_write(0x4022, 0x02);
+    irqs = 0;
+    for _ in 0..100 {
+        nes.mapper.tick(&mut nes.cpu);
+        if nes.mapper.cpu_read(0x4030) & 0x01 != 0 {
+            irqs += 1;
+        }
+    }
+    assert_eq!(irqs, 1);
+}
+
+#[test]
+fn disk_side_switching() {
+    let mut nes = Nes::new_fds(&disk(2), &bios()).unwrap();
+    assert_eq!(nes.disk_side_count(), 2);
+    assert_eq!(nes.disk_side(), Some(0));
+    assert!(nes.insert_disk_side(Some(2)).is_err());
+
+    nes.insert_disk_side(Some(1)).unwrap();
+    assert_eq!(nes.disk_side(), None);
+    assert_eq!(nes.mapper.cpu_read(0x4032) & 0x01, 0x01);
+    for _ in 0..1_000_000 {
+        nes.mapper.tick(&mut nes.cpu);
+    }
+    assert_eq!(nes.disk_side(), Some(1));
+    assert_eq!(nes.mapper.cpu_read(0x4032) & 0x01, 0x00);
+}
+
+#[test]
+fn audio() {
+    let mut nes = Nes::new_fds(&disk(1), &bios()).unwrap();
+    nes.mapper.cpu_write(0x4023, 0x02);
+    // square wave
+    nes.mapper.cpu_write(0x4089, 0x80);
+    for i in 0..64 {
+        let level = if i < 32 { 0x3F } else { 0 };
+        nes.mapper.cpu_write(0x4040 + i, level);
+    }
+    nes.mapper.cpu_write(0x4089, 0x00);
+    nes.mapper.cpu_write(0x4080, 0x80 | 32);
+    nes.mapper.cpu_write(0x4082, 0x00);
+    nes.mapper.cpu_write(0x4083, 0x08);
+
+    let mut outputs = Vec::new();
+    for _ in 0..4096 {
+        nes.mapper.tick(&mut nes.cpu);
+        outputs.push(nes.mapper.audio_output());
+    }
+    assert!(outputs.contains(&0));
+    assert!(outputs.iter().any(|output| *output > 0));
+
+    // sound registers disabled
+    nes.mapper.cpu_write(0x4023, 0x01);
+    nes.mapper.cpu_write(0x4080, 0x80);
+    assert_eq!(nes.mapper.cpu_read(0x4090) & 0x3F, 32);
+}
@@ -0,0 +1,124 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::{audio_after_save_state, Header};
+
+/// FME-7 game that starts the IRQ counter at 1000 and counts IRQs at $00.
+/// Each 8 KiB PRG bank starts with its number.
+fn fme7_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($E000)
+        0x78,                       // SEI
+        0xA9, 0x40,                 // LDA #$40
+        0x8D, 0x17, 0x40,           // STA $4017
+        0xA9, 0x0E,                 // LDA #$0E
+        0x8D, 0x00, 0x80,           // STA $8000
+        0xA9, 0xE8,                 // LDA #$E8
+        0x8D, 0x00, 0xA0,           // STA $A000
+        0xA9, 0x0F,                 // LDA #$0F
+        0x8D, 0x00, 0x80,           // STA $8000
+        0xA9, 0x03,                 // LDA #$03
+        0x8D, 0x00, 0xA0,           // STA $A000
+        0xA9, 0x0D,                 // LDA #$0D
+        0x8D, 0x00, 0x80,           // STA $8000
+        0xA9, 0x81,                 // LDA #$81
+        0x8D, 0x00, 0xA0,           // STA $A000
+        0x58,                       // CLI
+        0x4C, 0x25, 0xE0,           // JMP $E025
+        // irq ($E028)
+        0x8D, 0x00, 0xA0,           // STA $A000
+        0xE6, 0x00,                 // INC $00
+        0x40,                       // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 64 * 1024];
+    for bank in 0..8 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    prg_rom[0xE000..0xE000 + program.len()].copy_from_slice(program);
+    prg_rom[0xFFFA..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x28, 0xE0]);
+
+    Header::ines(69).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn prg_banks() {
+    let mut nes = Nes::new(fme7_game()).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    for (command, bank) in [(0x8, 1), (0x9, 2), (0xA, 3), (0xB, 5)] {
+        cpu_bus.write(0x8000, command);
+        cpu_bus.write(0xA000, bank);
+    }
+    assert_eq!(cpu_bus.read(0x6000), 1);
+    assert_eq!(cpu_bus.read(0x8000), 2);
+    assert_eq!(cpu_bus.read(0xA000), 3);
+    assert_eq!(cpu_bus.read(0xC000), 5);
+    assert_eq!(cpu_bus.read(0xE000), 0x78);
+
+    // RAM selected and enabled
+    cpu_bus.write(0x8000, 0x8);
+    cpu_bus.write(0xA000, 0xC0);
+    cpu_bus.write(0x6000, 0x12);
+    assert_eq!(cpu_bus.read(0x6000), 0x12);
+}
+
+#[test]
+fn irq_counts_cycles() {
+    let mut nes = Nes::new(fme7_game()).unwrap();
+    for _ in 0..10000 {
+        nes.run_one_cpu_tick();
+    }
+    // the counter wraps to $FFFF after the first IRQ
+    assert_eq!(nes.cpu_ram.read(0), 1);
+}
+
+#[test]
+fn expansion_audio() {
+    let mut nes = Nes::new(fme7_game()).unwrap();
+    let write_register = |nes: &mut Nes, register: u8, value: u8| {
+        nes.mapper.cpu_write(0xC000, register);
+        nes.mapper.cpu_write(0xE000, value);
+    };
+    // channel A tone only, full volume
+    write_register(&mut nes, 0x00, 0x10);
+    write_register(&mut nes, 0x07, 0b111110);
+    write_register(&mut nes, 0x08, 0x0F);
+
+    let mut outputs = Vec::new();
+    for _ in 0..1024 {
+        nes.run_one_cpu_tick();
+        outputs.push(nes.mapper.audio_output());
+    }
+    // square wave with a period of 2 * 16 * 16 cycles
+    assert_eq!(outputs.iter().min(), Some(&0));
+    assert_eq!(outputs.iter().max(), Some(&8000));
+    assert!(outputs[..512].contains(&0) && outputs[..512].contains(&8000));
+}
+
+#[test]
+fn audio_after_loading_state() {
+    let mut nes = Nes::new(fme7_game()).unwrap();
+    // tones on A and B, noise on C, C using the envelope
+    for (register, value) in [
+        (0x00, 0x10),
+        (0x02, 0x38),
+        (0x06, 0x04),
+        (0x07, 0b011100),
+        (0x08, 0x0F),
+        (0x09, 0x08),
+        (0x0A, 0x10),
+        (0x0B, 0x20),
+        (0x0D, 0x0E),
+    ] {
+        nes.mapper.cpu_write(0xC000, register);
+        nes.mapper.cpu_write(0xE000, value);
+    }
+    for _ in 0..1000 {
+        nes.run_one_cpu_tick();
+    }
+    let outputs = audio_after_save_state(&mut nes, fme7_game(), 4000);
+    assert_eq!(outputs.iter().sum::<i32>(), 17262304);
+}
@@ -0,0 +1,160 @@
+use polones_core::game_file::GameFile;
+use polones_core::mapper::NametableSource::{Mapper, Vram};
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// Game with CHR-RAM in the iNES format, with header flags 6 set to `flags`.
+/// Each 16 KiB PRG bank starts with its number, the rest of PRG-ROM is $FF.
+fn game(mapper: u8, prg_banks: u8, flags: u8) -> GameFile {
+    let mut prg_rom = vec![0xFF; prg_banks as usize * 16 * 1024];
+    for bank in 0..prg_banks as usize {
+        prg_rom[bank * 16 * 1024] = bank as u8;
+    }
+
+    let header = Header {
+        vertical_mirroring: flags & 0b0001 != 0,
+        battery: flags & 0b0010 != 0,
+        four_screen: flags & 0b1000 != 0,
+        ..Header::ines(mapper as u16)
+    };
+    header.game(&prg_rom, &[])
+}
+
+/// Writes to UNROM 512 flash through the PRG bank at $8000.
+fn unrom512_flash_write(nes: &mut Nes, address: usize, value: u8) {
+    nes.mapper.cpu_write(0xC000, (address >> 14) as u8);
+    let address = 0x8000 | (address & 0x3FFF) as u16;
+    nes.mapper.cpu_write(address, value);
+}
+
+#[test]
+fn unrom512_banks() {
+    let mut nes = Nes::new(game(30, 8, 0)).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0xC000), 7);
+    nes.mapper.cpu_write(0x8001, 0x23);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 3);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 7);
+
+    nes.mapper.ppu_write(0x0000, 0x11);
+    nes.mapper.cpu_write(0x8001, 0x03);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 0);
+    nes.mapper.cpu_write(0x8001, 0x23);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 0x11);
+
+    // without flash there is nothing to save
+    assert!(nes.battery_ram().is_none());
+}
+
+#[test]
+fn unrom512_flash() {
+    let mut nes = Nes::new(game(30, 8, 0b0010)).unwrap();
+
+    // program a byte
+    unrom512_flash_write(&mut nes, 0x5555, 0xAA);
+    unrom512_flash_write(&mut nes, 0x2AAA, 0x55);
+    unrom512_flash_write(&mut nes, 0x5555, 0xA0);
+    unrom512_flash_write(&mut nes, 0x8010, 0x42);
+    assert_eq!(nes.mapper.cpu_read(0x8010), 0x42);
+    assert_eq!(nes.battery_ram().unwrap()[0x8010], 0x42);
+
+    // without the unlock sequence writes are ignored
+    unrom512_flash_write(&mut nes, 0x8011, 0x42);
+    assert_eq!(nes.mapper.cpu_read(0x8011), 0xFF);
+
+    // erase the 4 KiB sector
+    for (address, value) in [(0x5555, 0xAA), (0x2AAA, 0x55), (0x5555, 0x80)] {
+        unrom512_flash_write(&mut nes, address, value);
+    }
+    for (address, value) in [(0x5555, 0xAA), (0x2AAA, 0x55), (0x8000, 0x30)] {
+        unrom512_flash_write(&mut nes, address, value);
+    }
+    nes.mapper.cpu_write(0xC000, 2);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 0xFF);
+    assert_eq!(nes.mapper.cpu_read(0x8010), 0xFF);
+    assert_eq!(nes.battery_ram().unwrap()[0x8000], 0xFF);
+    assert_eq!(nes.battery_ram().unwrap()[0xC000], 3);
+
+    // software ID
+    unrom512_flash_write(&mut nes, 0x5555, 0xAA);
+    unrom512_flash_write(&mut nes, 0x2AAA, 0x55);
+    unrom512_flash_write(&mut nes, 0x5555, 0x90);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 0xBF);
+    assert_eq!(nes.mapper.cpu_read(0x8001), 0xB7);
+    nes.mapper.cpu_write(0x8000, 0xF0);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 1);
+}
+
+#[test]
+fn unrom512_nametables() {
+    let mut nes = Nes::new(game(30, 8, 0b1000)).unwrap();
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(0));
+    nes.mapper.cpu_write(0x8001, 0x80);
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(1));
+
+    // four screens in the last CHR-RAM bank
+    let mut nes = Nes::new(game(30, 8, 0b1001)).unwrap();
+    assert_eq!(nes.mapper.nametable_source(0x2C00), Mapper);
+    nes.mapper.ppu_write(0x2C00, 5);
+    nes.mapper.cpu_write(0x8001, 0x60);
+    assert_eq!(nes.mapper.ppu_read(0x0C00), 5);
+}
+
+#[test]
+fn gtrom() {
+    let mut nes = Nes::new(game(111, 8, 0)).unwrap();
+    nes.mapper.cpu_write(0x5000, 0x02);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 4);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 5);
+
+    nes.mapper.ppu_write(0x0000, 9);
+    nes.mapper.ppu_write(0x2400, 7);
+    nes.mapper.cpu_write(0x7000, 0x32);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 0);
+    assert_eq!(nes.mapper.ppu_read(0x2400), 0);
+    nes.mapper.cpu_write(0x7000, 0x02);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 9);
+    assert_eq!(nes.mapper.ppu_read(0x2400), 7);
+
+    // flash commands use the PRG bank too
+    for (address, value) in [(0xD555, 0xAA), (0xAAAA, 0x55), (0xD555, 0xA0)] {
+        nes.mapper.cpu_write(0x5000, 0);
+        nes.mapper.cpu_write(address, value);
+    }
+    nes.mapper.cpu_write(0x5000, 3);
+    nes.mapper.cpu_write(0x8001, 0x24);
+    assert_eq!(nes.battery_ram().unwrap()[0x18001], 0x24);
+}
+
+#[test]
+fn action53() {
+    let mut nes = Nes::new(game(28, 16, 0)).unwrap();
+    // the last bank at power on
+    assert_eq!(nes.mapper.cpu_read(0xC000), 15);
+
+    // 128 KiB UNROM game in the second half of the cartridge
+    for (register, value) in [(0x80, 0x2F), (0x81, 7), (0x01, 2)] {
+        nes.mapper.cpu_write(0x5000, register);
+        nes.mapper.cpu_write(0x8000, value);
+    }
+    assert_eq!(nes.mapper.cpu_read(0x8000), 10);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 15);
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(1));
+
+    // NROM game with one screen mirroring
+    for (register, value) in [(0x80, 0x00), (0x81, 2)] {
+        nes.mapper.cpu_write(0x5000, register);
+        nes.mapper.cpu_write(0x8000, value);
+    }
+    assert_eq!(nes.mapper.cpu_read(0x8000), 4);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 5);
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(0));
+    nes.mapper.cpu_write(0x5000, 0x00);
+    nes.mapper.cpu_write(0x8000, 0x13);
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(1));
+    nes.mapper.ppu_write(0x0000, 0x11);
+    nes.mapper.cpu_write(0x8000, 0x10);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 0);
+}
@@ -0,0 +1,81 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// MMC1 game with CHR RAM in the Nes 2.0 format. Each 16 KiB PRG bank starts
+/// with its number.
+fn game(mapper: u16, prg_banks: u8, ram_sizes: u8) -> GameFile {
+    let mut prg_rom = vec![0xEA; prg_banks as usize * 16 * 1024];
+    for bank in 0..prg_banks as usize {
+        prg_rom[bank * 16 * 1024] = bank as u8;
+    }
+
+    let header = Header {
+        battery: true,
+        prg_ram_shift: ram_sizes & 0x0F,
+        prg_nvram_shift: ram_sizes >> 4,
+        chr_ram_shift: 7,
+        ..Header::nes20(mapper, 0)
+    };
+    header.game(&prg_rom, &[])
+}
+
+/// Shifts a value into an MMC1 register, one write per CPU cycle.
+fn write_register(nes: &mut Nes, address: u16, value: u8) {
+    for bit in 0..5 {
+        nes.mapper.cpu_write(address, value >> bit & 1);
+        nes.mapper.tick(&mut nes.cpu);
+    }
+}
+
+#[test]
+fn surom_outer_prg_bank() {
+    let mut nes = Nes::new(game(1, 32, 0x07)).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0xC000), 15);
+
+    write_register(&mut nes, 0xA000, 0x10);
+    write_register(&mut nes, 0xE000, 2);
+    assert_eq!(nes.mapper.cpu_read(0x8000), 18);
+    assert_eq!(nes.mapper.cpu_read(0xC000), 31);
+}
+
+#[test]
+fn sxrom_ram_banks() {
+    // 32 KiB of battery-backed RAM
+    let mut nes = Nes::new(game(1, 16, 0x90)).unwrap();
+    for bank in 0..4 {
+        write_register(&mut nes, 0xA000, bank << 2);
+        nes.mapper.cpu_write(0x6000, 0x10 + bank);
+    }
+    let ram = nes.mapper.battery_ram().unwrap();
+    assert_eq!(ram.len(), 32 * 1024);
+    assert_eq!(ram[0x0000], 0x10);
+    assert_eq!(ram[0x2000], 0x11);
+    assert_eq!(ram[0x6000], 0x13);
+}
+
+#[test]
+fn ram_disable() {
+    // MMC1A ignores the RAM disable bit
+    for (mapper, byte) in [(1, 0), (155, 0x12)] {
+        let mut nes = Nes::new(game(mapper, 16, 0x07)).unwrap();
+        nes.mapper.cpu_write(0x6000, 0x12);
+        write_register(&mut nes, 0xE000, 0x10);
+        assert_eq!(nes.mapper.cpu_read(0x6000), byte, "mapper {mapper}");
+    }
+}
+
+#[test]
+fn consecutive_writes_ignored() {
+    let mut nes = Nes::new(game(1, 16, 0x07)).unwrap();
+    for bit in [1, 1, 0, 0, 0] {
+        // like the two writes of a read-modify-write instruction
+        nes.mapper.cpu_write(0xE000, bit);
+        nes.mapper.cpu_write(0xE000, 0);
+        nes.mapper.tick(&mut nes.cpu);
+    }
+    assert_eq!(nes.mapper.cpu_read(0x8000), 3);
+}
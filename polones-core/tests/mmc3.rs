@@ -0,0 +1,119 @@
+use polones_core::game_file::GameFile;
+use polones_core::mapper::NametableSource::Vram;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// MMC3 game in the Nes 2.0 format that enables the IRQ with latch 0 and
+/// counts IRQs at $00. Each 1 KiB CHR bank starts with its number.
+fn mmc3_game(mapper: u16, submapper: u8) -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($E000)
+        0x78,                       // SEI
+        0xA9, 0x40,                 // LDA #$40
+        0x8D, 0x17, 0x40,           // STA $4017
+        0xA9, 0x00,                 // LDA #$00
+        0x8D, 0x00, 0xC0,           // STA $C000
+        0x8D, 0x01, 0xC0,           // STA $C001
+        0x8D, 0x01, 0xE0,           // STA $E001
+        0x58,                       // CLI
+        0x4C, 0x12, 0xE0,           // JMP $E012
+        // irq ($E015)
+        0x8D, 0x00, 0xE0,           // STA $E000
+        0x8D, 0x01, 0xE0,           // STA $E001
+        0xE6, 0x00,                 // INC $00
+        0x40,                       // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 32 * 1024];
+    prg_rom[0x6000..0x6000 + program.len()].copy_from_slice(program);
+    prg_rom[0x7FFA..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x15, 0xE0]);
+    let mut chr_rom = vec![0; 64 * 1024];
+    for bank in 0..64 {
+        chr_rom[bank * 1024] = bank as u8;
+    }
+
+    // 8 KiB of PRG RAM
+    let header = Header {
+        prg_ram_shift: 7,
+        ..Header::nes20(mapper, submapper)
+    };
+    header.game(&prg_rom, &chr_rom)
+}
+
+#[test]
+fn irq_revisions() {
+    // MMC3C raises an IRQ on every clock with latch 0, MMC3A only after reload
+    for (submapper, irqs) in [(0, 5), (4, 1)] {
+        let mut nes = Nes::new(mmc3_game(4, submapper)).unwrap();
+        for _ in 0..100 {
+            nes.run_one_cpu_tick();
+        }
+        for _ in 0..5 {
+            nes.mapper.ppu_read(0x1000);
+            for _ in 0..100 {
+                nes.run_one_cpu_tick();
+            }
+        }
+        assert_eq!(nes.cpu_ram.read(0), irqs, "submapper {submapper}");
+    }
+}
+
+#[test]
+fn mmc6_ram_protection() {
+    let mut nes = Nes::new(mmc3_game(4, 1)).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    // ignored while RAM is disabled
+    cpu_bus.write(0xA001, 0xF0);
+    cpu_bus.write(0x7000, 0x12);
+    assert_eq!(cpu_bus.read(0x7000), 0x00);
+
+    // enable RAM, lower half writable, upper half disabled
+    cpu_bus.write(0x8000, 0x20);
+    cpu_bus.write(0xA001, 0x30);
+    cpu_bus.write(0x7000, 0x12);
+    cpu_bus.write(0x7200, 0x34);
+    assert_eq!(cpu_bus.read(0x7000), 0x12);
+    assert_eq!(cpu_bus.read(0x7400), 0x12);
+    assert_eq!(cpu_bus.read(0x7200), 0x00);
+
+    // lower half read only
+    cpu_bus.write(0xA001, 0xE0);
+    cpu_bus.write(0x7000, 0x56);
+    cpu_bus.write(0x7200, 0x34);
+    assert_eq!(cpu_bus.read(0x7000), 0x12);
+    assert_eq!(cpu_bus.read(0x7200), 0x34);
+}
+
+#[test]
+fn txsrom_nametables() {
+    let mut nes = Nes::new(mmc3_game(118, 0)).unwrap();
+    nes.mapper.cpu_write(0x8000, 0);
+    nes.mapper.cpu_write(0x8001, 0x80);
+    nes.mapper.cpu_write(0x8000, 1);
+    nes.mapper.cpu_write(0x8001, 0x02);
+    assert_eq!(nes.mapper.nametable_source(0x2400), Vram(1));
+    assert_eq!(nes.mapper.nametable_source(0x2800), Vram(0));
+    assert_eq!(nes.mapper.ppu_read(0x0800), 0x02);
+
+    // with inverted CHR A12, R2-R5 select the nametables
+    nes.mapper.cpu_write(0x8000, 0x85);
+    nes.mapper.cpu_write(0x8001, 0x80);
+    assert_eq!(nes.mapper.nametable_source(0x2C00), Vram(1));
+    assert_eq!(nes.mapper.nametable_source(0x2000), Vram(0));
+}
+
+#[test]
+fn tqrom_chr_ram() {
+    let mut nes = Nes::new(mmc3_game(119, 0)).unwrap();
+    nes.mapper.cpu_write(0x8000, 2);
+    nes.mapper.cpu_write(0x8001, 0x41);
+    nes.mapper.cpu_write(0x8000, 3);
+    nes.mapper.cpu_write(0x8001, 0x05);
+    nes.mapper.ppu_write(0x1000, 0x55);
+    assert_eq!(nes.mapper.ppu_read(0x1000), 0x55);
+    assert_eq!(nes.mapper.ppu_read(0x1400), 0x05);
+}
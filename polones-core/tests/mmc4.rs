@@ -0,0 +1,64 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// MMC2 or MMC4 game. Each 8 KiB PRG bank and each 4 KiB CHR bank starts
+/// with its number.
+fn game(mapper: u8) -> GameFile {
+    let mut prg_rom = vec![0xEA; 128 * 1024];
+    for bank in 0..16 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    let mut chr_rom = vec![0; 128 * 1024];
+    for bank in 0..32 {
+        chr_rom[bank * 4 * 1024] = bank as u8;
+    }
+
+    let header = Header {
+        battery: true,
+        ..Header::ines(mapper as u16)
+    };
+    header.game(&prg_rom, &chr_rom)
+}
+
+#[test]
+fn prg_banks_and_ram() {
+    let mut nes = Nes::new(game(10)).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    cpu_bus.write(0xA000, 3);
+    assert_eq!(cpu_bus.read(0x8000), 6);
+    assert_eq!(cpu_bus.read(0xA000), 7);
+    assert_eq!(cpu_bus.read(0xC000), 14);
+
+    cpu_bus.write(0x6000, 0x12);
+    assert_eq!(cpu_bus.read(0x6000), 0x12);
+    assert!(nes.mapper.battery_ram().is_some());
+}
+
+#[test]
+fn chr_latches() {
+    for mapper in [9, 10] {
+        let mut nes = Nes::new(game(mapper)).unwrap();
+        nes.mapper.cpu_write(0xB000, 1);
+        nes.mapper.cpu_write(0xC000, 2);
+        nes.mapper.cpu_write(0xD000, 3);
+        nes.mapper.cpu_write(0xE000, 4);
+        assert_eq!(nes.mapper.ppu_read(0x0000), 1);
+        assert_eq!(nes.mapper.ppu_read(0x1000), 3);
+
+        nes.mapper.ppu_read(0x1FEB);
+        assert_eq!(nes.mapper.ppu_read(0x1000), 4);
+
+        // MMC2 reacts only to $0FE8 in the left pattern table
+        nes.mapper.ppu_read(0x0FEB);
+        let bank = if mapper == 9 { 1 } else { 2 };
+        assert_eq!(nes.mapper.ppu_read(0x0000), bank);
+        nes.mapper.ppu_read(0x0FE8);
+        assert_eq!(nes.mapper.ppu_read(0x0000), 2);
+        nes.mapper.ppu_read(0x0FD8);
+        assert_eq!(nes.mapper.ppu_read(0x0000), 1);
+    }
+}
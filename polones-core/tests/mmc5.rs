@@ -0,0 +1,135 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::{audio_after_save_state, Header};
+
+const CPU_TICKS_PER_FRAME: usize = 29781;
+
+/// MMC5 game with 4 PRG banks that requests a scanline IRQ on every frame
+/// and counts IRQs at $00. Each PRG bank starts with its number.
+fn mmc5_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($E000)
+        0x78,                   // SEI
+        0xA9, 0x40,             // LDA #$40
+        0x8D, 0x17, 0x40,       // STA $4017
+        0xA9, 0x08,             // LDA #$08
+        0x8D, 0x01, 0x20,       // STA $2001
+        0xA9, 0x20,             // LDA #$20
+        0x8D, 0x03, 0x52,       // STA $5203
+        0xA9, 0x80,             // LDA #$80
+        0x8D, 0x04, 0x52,       // STA $5204
+        0x58,                   // CLI
+        0x4C, 0x16, 0xE0,       // JMP $E016
+        // irq ($E019)
+        0xAD, 0x04, 0x52,       // LDA $5204
+        0xE6, 0x00,             // INC $00
+        0x40,                   // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 32 * 1024];
+    for bank in 0..4 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    prg_rom[0x6000..0x6000 + program.len()].copy_from_slice(program);
+    prg_rom[0x7FFA..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x19, 0xE0]);
+
+    Header::ines(5).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn prg_banks_and_multiplier() {
+    let mut nes = Nes::new(mmc5_game()).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    assert_eq!(cpu_bus.read(0xE000), 0x78);
+
+    // 8 KiB banks
+    cpu_bus.write(0x5114, 0x82);
+    cpu_bus.write(0x5116, 0x81);
+    assert_eq!(cpu_bus.read(0x8000), 2);
+    assert_eq!(cpu_bus.read(0xC000), 1);
+
+    // 32 KiB bank
+    cpu_bus.write(0x5100, 0);
+    assert_eq!(cpu_bus.read(0x8000), 0);
+    assert_eq!(cpu_bus.read(0xC000), 2);
+
+    // PRG RAM is write-protected until $5102 and $5103 are set
+    cpu_bus.write(0x6000, 0x42);
+    assert_eq!(cpu_bus.read(0x6000), 0);
+    cpu_bus.write(0x5102, 0b10);
+    cpu_bus.write(0x5103, 0b01);
+    cpu_bus.write(0x6000, 0x42);
+    assert_eq!(cpu_bus.read(0x6000), 0x42);
+
+    cpu_bus.write(0x5205, 200);
+    cpu_bus.write(0x5206, 100);
+    assert_eq!(cpu_bus.read(0x5205), (20000 & 0xFF) as u8);
+    assert_eq!(cpu_bus.read(0x5206), (20000 >> 8) as u8);
+}
+
+#[test]
+fn ex_ram_and_fill_mode_nametables() {
+    let mut nes = Nes::new(mmc5_game()).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    cpu_bus.write(0x5104, 2);
+    cpu_bus.write(0x5C05, 0x33);
+    assert_eq!(cpu_bus.read(0x5C05), 0x33);
+    cpu_bus.write(0x5104, 0);
+    cpu_bus.write(0x5105, 0b11_10_01_00);
+    cpu_bus.write(0x5106, 0xAB);
+    cpu_bus.write(0x5107, 0b10);
+
+    let (_ppu, mut ppu_bus) = cpu_bus.split_into_ppu_and_bus();
+    ppu_bus.write(0x2005, 1);
+    ppu_bus.write(0x2405, 2);
+    assert_eq!(ppu_bus.read(0x2005), 1);
+    assert_eq!(ppu_bus.read(0x2405), 2);
+    assert_eq!(ppu_bus.read(0x2805), 0x33);
+    assert_eq!(ppu_bus.read(0x2C05), 0xAB);
+    assert_eq!(ppu_bus.read(0x2FC5), 0b10101010);
+}
+
+#[test]
+fn scanline_irq_fires_once_per_frame() {
+    let mut nes = Nes::new(mmc5_game()).unwrap();
+    for _ in 0..10 * CPU_TICKS_PER_FRAME {
+        nes.run_one_cpu_tick();
+    }
+    // rendering starts in the middle of the first frame
+    assert!((9..=10).contains(&nes.cpu_ram.read(0)));
+}
+
+#[test]
+fn pcm_is_mixed_into_audio() {
+    let mut nes = Nes::new(mmc5_game()).unwrap();
+    assert_eq!(nes.mapper.audio_output(), 0);
+    nes.mapper.cpu_write(0x5011, 0x80);
+    assert!(nes.mapper.audio_output() > 0);
+}
+
+#[test]
+fn audio_after_loading_state() {
+    let mut nes = Nes::new(mmc5_game()).unwrap();
+    // pulses at volumes 15 and 8 with different duties and periods, and PCM
+    for (address, byte) in [
+        (0x5015, 0x03),
+        (0x5000, 0xBF),
+        (0x5002, 0x40),
+        (0x5003, 0x08),
+        (0x5004, 0x78),
+        (0x5006, 0x90),
+        (0x5007, 0x08),
+        (0x5011, 0x40),
+    ] {
+        nes.mapper.cpu_write(address, byte);
+    }
+    for _ in 0..1000 {
+        nes.run_one_cpu_tick();
+    }
+    let outputs = audio_after_save_state(&mut nes, mmc5_game(), 4000);
+    assert_eq!(outputs.iter().sum::<i32>(), 73729940);
+}
@@ -0,0 +1,127 @@
+use polones_core::game_file::GameFile;
+use polones_core::mapper::NametableSource;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::{audio_after_save_state, Header};
+
+/// Namco 163 game whose 8 KiB PRG banks start with their numbers.
+fn n163_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($E000)
+        0x78,                       // SEI
+        0x4C, 0x01, 0xE0,           // JMP $E001
+    ];
+
+    let mut prg_rom = vec![0xEA; 64 * 1024];
+    for bank in 0..8 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    prg_rom[0xE000..0xE000 + program.len()].copy_from_slice(program);
+    prg_rom[0xFFFA..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x00, 0xE0]);
+
+    Header::ines(19).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn prg_banks_and_ram_protection() {
+    let mut nes = Nes::new(n163_game()).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    cpu_bus.write(0xE000, 2);
+    cpu_bus.write(0xE800, 3);
+    cpu_bus.write(0xF000, 5);
+    assert_eq!(cpu_bus.read(0x8000), 2);
+    assert_eq!(cpu_bus.read(0xA000), 3);
+    assert_eq!(cpu_bus.read(0xC000), 5);
+    assert_eq!(cpu_bus.read(0xE000), 0x78);
+
+    cpu_bus.write(0x6000, 0x12);
+    assert_eq!(cpu_bus.read(0x6000), 0x00);
+    // writes enabled, $6800-$6FFF protected
+    cpu_bus.write(0xF800, 0x42);
+    cpu_bus.write(0x6000, 0x12);
+    cpu_bus.write(0x6800, 0x34);
+    assert_eq!(cpu_bus.read(0x6000), 0x12);
+    assert_eq!(cpu_bus.read(0x6800), 0x00);
+}
+
+#[test]
+fn ciram_as_pattern_table() {
+    let mut nes = Nes::new(n163_game()).unwrap();
+    nes.mapper.cpu_write(0x8000, 0xE1);
+    nes.mapper.cpu_write(0xC000, 0x05);
+    assert!(!nes.mapper.ppu_address_mapped(0x0005));
+    assert_eq!(
+        nes.mapper.nametable_source(0x0005),
+        NametableSource::Vram(1)
+    );
+    assert_eq!(nes.mapper.nametable_source(0x2005), NametableSource::Mapper);
+
+    // $E800 bit 6 keeps CHR-ROM at $0000-$0FFF
+    nes.mapper.cpu_write(0xE800, 0x40);
+    assert!(nes.mapper.ppu_address_mapped(0x0005));
+}
+
+#[test]
+fn irq_counter() {
+    let mut nes = Nes::new(n163_game()).unwrap();
+    nes.mapper.cpu_write(0x5000, 0xF0);
+    nes.mapper.cpu_write(0x5800, 0xFF);
+    for _ in 0..100 {
+        nes.run_one_cpu_tick();
+    }
+    // the counter stops at $7FFF
+    assert_eq!(nes.mapper.cpu_read(0x5000), 0xFF);
+    assert_eq!(nes.mapper.cpu_read(0x5800), 0xFF);
+}
+
+#[test]
+fn wavetable_audio() {
+    let mut nes = Nes::new(n163_game()).unwrap();
+    // wave of 32 samples at 15, starting at sound RAM $00
+    nes.mapper.cpu_write(0xF800, 0x80);
+    for _ in 0..16 {
+        nes.mapper.cpu_write(0x4800, 0xFF);
+    }
+    // channel 7 registers at $78-$7F, one channel enabled
+    nes.mapper.cpu_write(0xF800, 0xF8);
+    for byte in [0x00, 0x00, 0x10, 0x00, 0xE0, 0x00, 0x00, 0x0F] {
+        nes.mapper.cpu_write(0x4800, byte);
+    }
+    assert_eq!(nes.mapper.audio_output(), 0);
+    for _ in 0..15 {
+        nes.run_one_cpu_tick();
+    }
+    assert!(nes.mapper.audio_output() > 0);
+
+    // $E000 bit 6 silences the chip
+    nes.mapper.cpu_write(0xE000, 0x40);
+    assert_eq!(nes.mapper.audio_output(), 0);
+}
+
+#[test]
+fn audio_after_loading_state() {
+    let mut nes = Nes::new(n163_game()).unwrap();
+    // ramp of 16 samples repeated twice, starting at sound RAM $00
+    nes.mapper.cpu_write(0xF800, 0x80);
+    for sample in (0..32).step_by(2) {
+        let byte = (sample % 16) | (sample % 16 + 1) << 4;
+        nes.mapper.cpu_write(0x4800, byte);
+    }
+    // channel 6 plays 16 samples at volume 8, channel 7 all 32 at volume 15,
+    // two channels enabled
+    nes.mapper.cpu_write(0xF800, 0xF0);
+    for byte in [
+        0x00, 0x00, 0x30, 0x00, 0xF0, 0x00, 0x00, 0x08, // channel 6
+        0x00, 0x00, 0x10, 0x00, 0xE0, 0x00, 0x00, 0x1F, // channel 7
+    ] {
+        nes.mapper.cpu_write(0x4800, byte);
+    }
+    for _ in 0..1000 {
+        nes.run_one_cpu_tick();
+    }
+    let outputs = audio_after_save_state(&mut nes, n163_game(), 4000);
+    assert_eq!(outputs.iter().sum::<i32>(), -2760672);
+}
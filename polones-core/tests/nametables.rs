@@ -0,0 +1,43 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+fn nrom_game(flags_6: u8) -> GameFile {
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    let header = Header {
+        vertical_mirroring: flags_6 & 0b0001 != 0,
+        four_screen: flags_6 & 0b1000 != 0,
+        ..Header::ines(0)
+    };
+    header.game(&prg_rom, &[0; 8 * 1024])
+}
+
+/// Writes a different byte to each nametable and reads them back.
+fn nametable_contents(nes: &mut Nes) -> [u8; 4] {
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    let (_ppu, mut ppu_bus) = cpu_bus.split_into_ppu_and_bus();
+    for (i, address) in [0x2000, 0x2400, 0x2800, 0x2C00].into_iter().enumerate() {
+        ppu_bus.write(address + 5, i as u8 + 1);
+    }
+    [0x2000, 0x2400, 0x2800, 0x2C00].map(|address| ppu_bus.read(address + 5))
+}
+
+#[test]
+fn four_screen_games_have_four_nametables() {
+    let mut nes = Nes::new(nrom_game(0b1000)).unwrap();
+    assert_eq!(nametable_contents(&mut nes), [1, 2, 3, 4]);
+}
+
+#[test]
+fn mirrored_games_have_two_nametables() {
+    let mut nes = Nes::new(nrom_game(0b0001)).unwrap();
+    assert_eq!(nametable_contents(&mut nes), [3, 4, 3, 4]);
+
+    let mut nes = Nes::new(nrom_game(0b0000)).unwrap();
+    assert_eq!(nametable_contents(&mut nes), [2, 2, 4, 4]);
+}
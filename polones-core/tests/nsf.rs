@@ -0,0 +1,320 @@
+use polones_core::nes::{Nes, Region};
+use polones_core::nsf_file::{NsfFile, NsfFormat, NsfTrack};
+
+/// NSF with `songs` songs, starting with song 2. The init routine at $8000
+/// stores the song at $00 and the region at $01, the play routine at $8010
+/// counts calls at $02. `banks` go to the bank switching bytes.
+fn nsf_data(songs: u8, banks: [u8; 8], chips: u8) -> Vec<u8> {
+    let mut data = vec![0; 0x80];
+    data[0..5].copy_from_slice(b"NESM\x1A");
+    data[5] = 1;
+    data[6] = songs;
+    data[7] = 2;
+    data[0x08..0x0E].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x10, 0x80]);
+    data[0x0E..0x13].copy_from_slice(b"Title");
+    data[0x2E..0x34].copy_from_slice(b"Artist");
+    // 60 Hz
+    data[0x6E..0x70].copy_from_slice(&16666u16.to_le_bytes());
+    data[0x70..0x78].copy_from_slice(&banks);
+    data[0x78..0x7A].copy_from_slice(&20000u16.to_le_bytes());
+    data[0x7A] = 0b10;
+    data[0x7B] = chips;
+
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // init ($8000)
+        0x85, 0x00,                 // STA $00
+        0x86, 0x01,                 // STX $01
+        0x60,                       // RTS
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // play ($8010)
+        0xE6, 0x02,                 // INC $02
+        0x60,                       // RTS
+    ];
+    let mut prg = vec![0; 3 * 0x1000];
+    prg[..program.len()].copy_from_slice(program);
+    // each bank ends with its number
+    for bank in 0..3 {
+        prg[bank * 0x1000 + 0xFFF] = bank as u8;
+    }
+    data.extend_from_slice(&prg);
+    data
+}
+
+fn nsf(songs: u8, banks: [u8; 8], chips: u8) -> NsfFile {
+    NsfFile::read("test.nsf".into(), nsf_data(songs, banks, chips)).unwrap()
+}
+
+fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(data);
+    chunk
+}
+
+/// Metadata of 3 songs: labels, lengths of the first two, a fade of the
+/// first one and a playlist with a song that doesn't exist.
+fn metadata() -> Vec<u8> {
+    let mut times = Vec::new();
+    for time in [90_000, 5_000, -1] {
+        times.extend_from_slice(&i32::to_le_bytes(time));
+    }
+    let mut data = Vec::new();
+    data.extend(chunk(b"auth", b"Title 2\0Artist 2\0Copyright\0Ripper\0"));
+    data.extend(chunk(b"tlbl", b"Intro\0Level\0Ending\0"));
+    data.extend(chunk(b"time", &times));
+    data.extend(chunk(b"fade", &i32::to_le_bytes(3_000)));
+    data.extend(chunk(b"plst", &[2, 0, 7, 1]));
+    data.extend(chunk(b"xtra", b"skipped"));
+    data.extend(chunk(b"NEND", &[]));
+    data
+}
+
+fn assert_metadata(nsf: &NsfFile) {
+    assert_eq!(nsf.title, "Title 2");
+    assert_eq!(nsf.artist, "Artist 2");
+    assert_eq!(nsf.copyright, "Copyright");
+    assert_eq!(nsf.ripper, "Ripper");
+    assert_eq!(
+        nsf.tracks,
+        [
+            NsfTrack {
+                label: Some("Intro".into()),
+                length: Some(90_000),
+                fade: Some(3_000),
+            },
+            NsfTrack {
+                label: Some("Level".into()),
+                length: Some(5_000),
+                fade: None,
+            },
+            NsfTrack {
+                label: Some("Ending".into()),
+                length: None,
+                fade: None,
+            },
+        ]
+    );
+    assert_eq!(nsf.playlist, Some(vec![2, 0, 1]));
+}
+
+fn run(nes: &mut Nes, cycles: usize) {
+    for _ in 0..cycles {
+        nes.run_one_cpu_tick();
+    }
+}
+
+#[test]
+fn nsf_header() {
+    let nsf = nsf(3, [0; 8], 0);
+    assert_eq!(nsf.song_count, 3);
+    assert_eq!(nsf.starting_song, 1);
+    assert_eq!(nsf.init_address, 0x8000);
+    assert_eq!(nsf.play_address, 0x8010);
+    assert_eq!(nsf.title, "Title");
+    assert_eq!(nsf.artist, "Artist");
+    assert_eq!(nsf.copyright, "");
+    assert_eq!(nsf.banks, None);
+    assert_eq!(nsf.region, None);
+    assert_eq!(nsf.format, NsfFormat::Nsf);
+    assert_eq!(nsf.tracks, vec![NsfTrack::default(); 3]);
+    assert_eq!(nsf.playlist, None);
+
+    let mut data = nsf_data(3, [0; 8], 0);
+    data[0] = b'X';
+    assert!(NsfFile::read("test.nsf".into(), data).is_err());
+    let mut data = nsf_data(3, [0; 8], 0);
+    data[6] = 0;
+    assert!(NsfFile::read("test.nsf".into(), data).is_err());
+    assert!(NsfFile::read("test.nsf".into(), nsf_data(3, [0; 8], 0)[..0x80].to_vec()).is_err());
+}
+
+#[test]
+fn init_and_play() {
+    let mut nes = Nes::new_nsf(&nsf(3, [0; 8], 0)).unwrap();
+    assert_eq!(nes.song_count(), 3);
+    assert_eq!(nes.song(), Some(1));
+
+    // the driver clears RAM for about 12000 cycles, then play is called
+    // every 29829 cycles
+    run(&mut nes, 110_000);
+    assert_eq!(nes.cpu_ram.read(0x00), 1);
+    assert_eq!(nes.cpu_ram.read(0x01), 0);
+    assert_eq!(nes.cpu_ram.read(0x02), 3);
+
+    nes.set_region(Region::Pal);
+    nes.select_song(2).unwrap();
+    // every 33252 cycles on PAL
+    run(&mut nes, 120_000);
+    assert_eq!(nes.song(), Some(2));
+    assert_eq!(nes.cpu_ram.read(0x00), 2);
+    assert_eq!(nes.cpu_ram.read(0x01), 1);
+    assert_eq!(nes.cpu_ram.read(0x02), 3);
+
+    assert!(nes.select_song(3).is_err());
+}
+
+#[test]
+fn bank_switching() {
+    let mut nes = Nes::new_nsf(&nsf(1, [0, 1, 2, 0, 0, 0, 0, 0], 0)).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0x9FFF), 1);
+    assert_eq!(nes.mapper.cpu_read(0xAFFF), 2);
+    nes.mapper.cpu_write(0x5FF8, 2);
+    assert_eq!(nes.mapper.cpu_read(0x8FFF), 2);
+
+    // banks go back to the header values with every song
+    nes.select_song(0).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0x8FFF), 0);
+
+    // without bank switching the data is loaded at $8000-$AFFF
+    let mut nes = Nes::new_nsf(&nsf(1, [0; 8], 0)).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0xAFFF), 2);
+    assert_eq!(nes.mapper.cpu_read(0xBFFF), 0);
+}
+
+#[test]
+fn expansion_audio() {
+    let mut nes = Nes::new_nsf(&nsf(1, [0; 8], 0)).unwrap();
+    nes.mapper.cpu_write(0x9000, 0x8F);
+    nes.mapper.cpu_write(0x9002, 0x80);
+    run(&mut nes, 100);
+    assert_eq!(nes.mapper.audio_output(), 0);
+
+    // VRC6 pulse at full volume, ignoring the duty cycle
+    let mut nes = Nes::new_nsf(&nsf(1, [0; 8], 0x01)).unwrap();
+    nes.mapper.cpu_write(0x9000, 0x8F);
+    nes.mapper.cpu_write(0x9002, 0x80);
+    run(&mut nes, 100);
+    assert!(nes.mapper.audio_output() > 0);
+
+    // VRC7 ports don't reach VRC6 pulse 1
+    let mut nes = Nes::new_nsf(&nsf(1, [0; 8], 0x03)).unwrap();
+    nes.mapper.cpu_write(0x9010, 0x8F);
+    nes.mapper.cpu_write(0x9030, 0x8F);
+    nes.mapper.cpu_write(0x9002, 0x80);
+    run(&mut nes, 100);
+    assert_eq!(nes.mapper.audio_output(), 0);
+}
+
+#[test]
+fn mmc5() {
+    let nes = Nes::new_nsf(&nsf(1, [0; 8], 0)).unwrap();
+    assert!(!nes.mapper.cpu_address_mapped(0x5205));
+    assert!(!nes.mapper.cpu_address_mapped(0x5C00));
+
+    let mut nes = Nes::new_nsf(&nsf(1, [0; 8], 0x08)).unwrap();
+    assert!(nes.mapper.cpu_address_mapped(0x5000));
+    assert!(nes.mapper.cpu_address_mapped(0x5205));
+    assert!(nes.mapper.cpu_address_mapped(0x5FF5));
+    nes.mapper.cpu_write(0x5205, 200);
+    nes.mapper.cpu_write(0x5206, 200);
+    assert_eq!(nes.mapper.cpu_read(0x5205), 0x40);
+    assert_eq!(nes.mapper.cpu_read(0x5206), 0x9C);
+    nes.mapper.cpu_write(0x5C00, 0x42);
+    assert_eq!(nes.mapper.cpu_read(0x5C00), 0x42);
+
+    nes.mapper.cpu_write(0x5011, 0xFF);
+    run(&mut nes, 100);
+    assert!(nes.mapper.audio_output() > 0);
+
+    // song changes reset the chip
+    nes.select_song(0).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0x5C00), 0);
+    assert_eq!(nes.mapper.audio_output(), 0);
+}
+
+#[test]
+fn n163() {
+    let nes = Nes::new_nsf(&nsf(1, [0; 8], 0)).unwrap();
+    assert!(!nes.mapper.cpu_address_mapped(0x4800));
+
+    let mut nes = Nes::new_nsf(&nsf(1, [0; 8], 0x10)).unwrap();
+    assert!(nes.mapper.cpu_address_mapped(0x4800));
+    nes.mapper.cpu_write(0xF800, 0x80);
+    nes.mapper.cpu_write(0x4800, 0x12);
+    nes.mapper.cpu_write(0x4800, 0x34);
+    nes.mapper.cpu_write(0xF800, 0x80);
+    assert_eq!(nes.mapper.cpu_read(0x4800), 0x12);
+    assert_eq!(nes.mapper.cpu_read(0x4800), 0x34);
+
+    // one channel at full volume playing a wave of 4 loud samples
+    nes.mapper.cpu_write(0xF800, 0x80);
+    nes.mapper.cpu_write(0x4800, 0xFF);
+    nes.mapper.cpu_write(0x4800, 0xFF);
+    nes.mapper.cpu_write(0xF800, 0xFC);
+    for byte in [0xFC, 0x00, 0x00, 0x0F] {
+        nes.mapper.cpu_write(0x4800, byte);
+    }
+    run(&mut nes, 100);
+    assert!(nes.mapper.audio_output() > 0);
+}
+
+#[test]
+fn disk_system_ram() {
+    let mut nes = Nes::new_nsf(&nsf(1, [0, 1, 2, 0, 0, 0, 0, 0], 0x04)).unwrap();
+    assert_eq!(nes.mapper.cpu_read(0xAFFF), 2);
+    nes.mapper.cpu_write(0xAFFF, 0x42);
+    assert_eq!(nes.mapper.cpu_read(0xAFFF), 0x42);
+    // banks are copied to RAM
+    nes.mapper.cpu_write(0x5FFA, 1);
+    assert_eq!(nes.mapper.cpu_read(0xAFFF), 1);
+}
+
+#[test]
+fn nsf2_metadata() {
+    let mut data = nsf_data(3, [0; 8], 0);
+    let program_length = data.len() as u32 - 0x80;
+    data[5] = 2;
+    data[0x7D..0x80].copy_from_slice(&program_length.to_le_bytes()[..3]);
+    data.extend(metadata());
+    let nsf = NsfFile::read("test.nsf".into(), data.clone()).unwrap();
+    assert_eq!(nsf.format, NsfFormat::Nsf2);
+    assert_eq!(nsf.data().len(), 3 * 0x1000);
+    assert_metadata(&nsf);
+
+    // chunks starting with a capital letter must be understood
+    let end = data.len() - 8;
+    data.splice(end..end, chunk(b"XTRA", &[]));
+    assert!(NsfFile::read("test.nsf".into(), data.clone()).is_err());
+    data.truncate(end);
+    assert!(NsfFile::read("test.nsf".into(), data).is_err());
+}
+
+#[test]
+fn nsfe() {
+    let nsf_data = nsf_data(3, [0; 8], 0);
+    let mut info = nsf_data[0x08..0x0E].to_vec();
+    info.extend_from_slice(&[0x01, 0x00, 3, 2]);
+    let mut data = b"NSFE".to_vec();
+    data.extend(chunk(b"INFO", &info));
+    data.extend(chunk(b"BANK", &[0, 1, 2]));
+    data.extend(chunk(b"RATE", &16000u16.to_le_bytes()));
+    data.extend(chunk(b"DATA", &nsf_data[0x80..]));
+    data.extend(metadata());
+    let nsf = NsfFile::read("test.nsfe".into(), data).unwrap();
+    assert_eq!(nsf.format, NsfFormat::Nsfe);
+    assert_eq!(nsf.song_count, 3);
+    assert_eq!(nsf.starting_song, 2);
+    assert_eq!(nsf.init_address, 0x8000);
+    assert_eq!(nsf.play_address, 0x8010);
+    assert_eq!(nsf.region, Some(Region::Pal));
+    assert_eq!(nsf.ntsc_speed, 16000);
+    assert_eq!(nsf.pal_speed, 0);
+    assert_eq!(nsf.banks, Some([0, 1, 2, 0, 0, 0, 0, 0]));
+    assert_metadata(&nsf);
+
+    let mut nes = Nes::new_nsf(&nsf).unwrap();
+    assert_eq!(nes.song(), Some(2));
+    assert_eq!(nes.mapper.cpu_read(0xAFFF), 2);
+
+    // INFO is required
+    let mut data = b"NSFE".to_vec();
+    data.extend(chunk(b"DATA", &nsf_data[0x80..]));
+    data.extend(chunk(b"NEND", &[]));
+    assert!(NsfFile::read("test.nsfe".into(), data).is_err());
+
+    let mut data = b"NSFE".to_vec();
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    data.extend_from_slice(b"INFO");
+    assert!(NsfFile::read("test.nsfe".into(), data).is_err());
+}
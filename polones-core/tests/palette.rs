@@ -0,0 +1,30 @@
+use polones_core::palette::{Palette, BUILTIN_PALETTES, PALLETTE};
+
+#[test]
+fn pal_files_are_read() {
+    let data: Vec<u8> = (0..192).map(|i| i as u8).collect();
+    let palette = Palette::from_pal_file(&data).unwrap();
+    assert_eq!(palette.colors()[0x01], (3, 4, 5));
+    // emphasis is derived from the base colours
+    assert!(palette.colors()[1 << 6 | 0x3F].1 < 191);
+
+    let data: Vec<u8> = (0..1536).map(|i| (i / 3) as u8).collect();
+    let palette = Palette::from_pal_file(&data).unwrap();
+    assert_eq!(palette.colors()[0x45], (0x45, 0x45, 0x45));
+
+    assert!(Palette::from_pal_file(&[0; 191]).is_err());
+}
+
+#[test]
+fn builtin_palettes_exist() {
+    for name in BUILTIN_PALETTES {
+        let palette = Palette::builtin(name).unwrap();
+        assert_eq!(palette.colors()[0x0F], (0, 0, 0));
+        assert!(palette.colors()[0x30].0 > 0xE0);
+    }
+    assert_eq!(
+        Palette::builtin("polones").unwrap().colors()[0x16],
+        PALLETTE[0x16]
+    );
+    assert!(Palette::builtin("unknown").is_none());
+}
@@ -0,0 +1,63 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+use polones_core::palette::PALLETTE;
+
+mod common;
+
+use common::Header;
+
+const CPU_TICKS_PER_FRAME: usize = 29781;
+
+/// NROM game that fills the screen with backdrop colour $16 and writes the
+/// given value to PPUMASK.
+fn backdrop_game(mask: u8) -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        0x2C, 0x02, 0x20,       // BIT $2002
+        0x10, 0xFB,             // BPL -5
+        0xA9, 0x3F,             // LDA #$3F
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA9, 0x00,             // LDA #$00
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA9, 0x16,             // LDA #$16
+        0x8D, 0x07, 0x20,       // STA $2007
+        0xA9, 0x00,             // LDA #$00
+        0x8D, 0x06, 0x20,       // STA $2006
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA9, mask,             // LDA #mask
+        0x8D, 0x01, 0x20,       // STA $2001
+        0x4C, 0x21, 0xC0,       // JMP $C021
+    ];
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    Header::ines(0).game(&prg_rom, &[0; 8 * 1024])
+}
+
+fn backdrop_color(mask: u8) -> (u8, u8, u8) {
+    let mut nes = Nes::new(backdrop_game(mask)).unwrap();
+    for _ in 0..3 * CPU_TICKS_PER_FRAME {
+        nes.run_one_cpu_tick();
+    }
+    nes.display.frame[120][128]
+}
+
+#[test]
+fn greyscale_and_emphasis_change_output_colors() {
+    let plain = backdrop_color(0x00);
+    assert_eq!(plain, PALLETTE[0x16]);
+
+    assert_eq!(backdrop_color(0x01), PALLETTE[0x10]);
+
+    // red emphasis dims green and blue
+    let red = backdrop_color(0x20);
+    assert_eq!(red.0, plain.0);
+    assert!(red.1 < plain.1 || plain.1 == 0);
+    assert!(red.2 < plain.2 || plain.2 == 0);
+
+    // all emphasis bits dim everything
+    let all = backdrop_color(0xE0);
+    assert!(all.0 < plain.0);
+}
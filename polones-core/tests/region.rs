@@ -0,0 +1,62 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::{Nes, Region};
+
+/// NES 2.0 NROM game with the given timing mode that counts NMIs.
+fn nmi_counter_game(timing_mode: u8) -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($C000)
+        0xA9, 0x80,             // LDA #$80
+        0x8D, 0x00, 0x20,       // STA $2000
+        0x4C, 0x05, 0xC0,       // JMP $C005
+        // nmi ($C008)
+        0xE6, 0x00,             // INC $00
+        0xD0, 0x02,             // BNE +2
+        0xE6, 0x01,             // INC $01
+        0x40,                   // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x08, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    let mut data = vec![
+        b'N', b'E', b'S', 0x1A, 1, 1, 0, 0x08, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    data[12] = timing_mode;
+    data.extend_from_slice(&prg_rom);
+    data.extend_from_slice(&[0; 8 * 1024]);
+    GameFile::read("test.nes".into(), data).unwrap()
+}
+
+/// Runs the console for one emulated second and returns the number of NMIs.
+fn nmis_per_second(nes: &mut Nes) -> u16 {
+    for _ in 0..nes.region().cpu_clock_rate() {
+        nes.run_one_cpu_tick();
+    }
+    u16::from_le_bytes([nes.cpu_ram.read(0), nes.cpu_ram.read(1)])
+}
+
+#[test]
+fn region_is_read_from_header() {
+    assert_eq!(nmi_counter_game(0).region, Some(Region::Ntsc));
+    assert_eq!(nmi_counter_game(1).region, Some(Region::Pal));
+    assert_eq!(nmi_counter_game(3).region, Some(Region::Dendy));
+    assert_eq!(Nes::new(nmi_counter_game(1)).unwrap().region(), Region::Pal);
+}
+
+#[test]
+fn frame_rate_depends_on_region() {
+    let mut nes = Nes::new(nmi_counter_game(0)).unwrap();
+    assert_eq!(nmis_per_second(&mut nes), 60);
+
+    let mut nes = Nes::new(nmi_counter_game(1)).unwrap();
+    assert_eq!(nmis_per_second(&mut nes), 50);
+
+    let mut nes = Nes::new(nmi_counter_game(3)).unwrap();
+    assert_eq!(nmis_per_second(&mut nes), 50);
+
+    let mut nes = Nes::new(nmi_counter_game(0)).unwrap();
+    nes.set_region(Region::Pal);
+    assert_eq!(nmis_per_second(&mut nes), 50);
+}
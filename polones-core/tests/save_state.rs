@@ -0,0 +1,113 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+const CPU_TICKS_PER_FRAME: usize = 29781;
+
+/// NROM game that changes the backdrop color and pulse pitch every frame.
+fn test_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($C000)
+        0x78,                   // SEI
+        0xD8,                   // CLD
+        0xA2, 0xFF,             // LDX #$FF
+        0x9A,                   // TXS
+        0x2C, 0x02, 0x20,       // BIT $2002
+        0x10, 0xFB,             // BPL -5
+        0x2C, 0x02, 0x20,       // BIT $2002
+        0x10, 0xFB,             // BPL -5
+        0xA9, 0x3F,             // LDA #$3F
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA9, 0x00,             // LDA #$00
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA9, 0x0F,             // LDA #$0F
+        0x8D, 0x07, 0x20,       // STA $2007
+        0xA9, 0x30,             // LDA #$30
+        0x8D, 0x07, 0x20,       // STA $2007
+        0xA9, 0xBF,             // LDA #$BF
+        0x8D, 0x00, 0x40,       // STA $4000
+        0xA9, 0x01,             // LDA #$01
+        0x8D, 0x15, 0x40,       // STA $4015
+        0xA9, 0x80,             // LDA #$80
+        0x8D, 0x00, 0x20,       // STA $2000
+        0xA9, 0x1E,             // LDA #$1E
+        0x8D, 0x01, 0x20,       // STA $2001
+        // main loop ($C037)
+        0xE6, 0x00,             // INC $00
+        0xA5, 0x00,             // LDA $00
+        0x8D, 0x02, 0x40,       // STA $4002
+        0x4C, 0x37, 0xC0,       // JMP $C037
+        0xEA,                   // NOP
+        // nmi ($C042)
+        0xE6, 0x01,             // INC $01
+        0xA9, 0x3F,             // LDA #$3F
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA9, 0x00,             // LDA #$00
+        0x8D, 0x06, 0x20,       // STA $2006
+        0xA5, 0x01,             // LDA $01
+        0x29, 0x3F,             // AND #$3F
+        0x8D, 0x07, 0x20,       // STA $2007
+        0xA9, 0x00,             // LDA #$00
+        0x8D, 0x05, 0x20,       // STA $2005
+        0x8D, 0x05, 0x20,       // STA $2005
+        0x8D, 0x06, 0x20,       // STA $2006
+        0x8D, 0x06, 0x20,       // STA $2006
+        0x8D, 0x03, 0x40,       // STA $4003
+        0x40,                   // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[..program.len()].copy_from_slice(program);
+    prg_rom[0x3FFA..].copy_from_slice(&[0x42, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+
+    let chr_rom: Vec<u8> = (0..8 * 1024).map(|i| (i * 37 % 251) as u8).collect();
+
+    Header::ines(0).game(&prg_rom, &chr_rom)
+}
+
+fn run_ticks(nes: &mut Nes, ticks: usize) {
+    for _ in 0..ticks {
+        nes.run_one_cpu_tick();
+    }
+}
+
+#[test]
+fn loaded_state_runs_identically() {
+    let mut nes = Nes::new(test_game()).unwrap();
+    run_ticks(&mut nes, 10 * CPU_TICKS_PER_FRAME + 1234);
+    assert!(nes.cpu_ram.read(1) > 0, "NMI handler did not run");
+    let state = nes.save_state();
+
+    run_ticks(&mut nes, 5 * CPU_TICKS_PER_FRAME);
+    let frame = nes.display.frame.clone();
+    let state_after = nes.save_state();
+
+    let mut other = Nes::new(test_game()).unwrap();
+    other.load_state(&state).unwrap();
+    assert_eq!(other.save_state(), state);
+
+    run_ticks(&mut other, 5 * CPU_TICKS_PER_FRAME);
+    assert!(other.display.frame == frame);
+    assert_eq!(other.save_state(), state_after);
+}
+
+#[test]
+fn invalid_state_leaves_console_unchanged() {
+    let mut nes = Nes::new(test_game()).unwrap();
+    run_ticks(&mut nes, 3 * CPU_TICKS_PER_FRAME);
+    let state = nes.save_state();
+
+    run_ticks(&mut nes, CPU_TICKS_PER_FRAME);
+    let current = nes.save_state();
+
+    assert!(nes.load_state(b"garbage").is_err());
+    assert!(nes.load_state(&state[..state.len() - 1]).is_err());
+    let mut trailing = state.clone();
+    trailing.push(0);
+    assert!(nes.load_state(&trailing).is_err());
+    assert_eq!(nes.save_state(), current);
+}
@@ -0,0 +1,49 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+/// Game whose reset vector points into a trainer that stores $42 at $0000.
+fn trainer_game(mapper: u8) -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        0xA9, 0x42,             // LDA #$42
+        0x85, 0x00,             // STA $00
+        0x4C, 0x04, 0x70,       // JMP $7004
+    ];
+
+    let mut trainer = vec![0; 512];
+    trainer[..program.len()].copy_from_slice(program);
+
+    let mut prg_rom = vec![0xEA; 16 * 1024];
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0x70, 0x00, 0x70, 0x00, 0x70]);
+
+    let flags_6 = (mapper << 4) | 0b0100;
+    let mut data = vec![
+        b'N', b'E', b'S', 0x1A, 1, 1, flags_6, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    data.extend_from_slice(&trainer);
+    data.extend_from_slice(&prg_rom);
+    data.extend_from_slice(&[0; 8 * 1024]);
+    GameFile::read("test.nes".into(), data).unwrap()
+}
+
+#[test]
+fn trainer_runs_from_prg_ram() {
+    // MMC1 has PRG RAM at $6000-$7FFF
+    let mut nes = Nes::new(trainer_game(1)).unwrap();
+    for _ in 0..10 {
+        nes.run_one_cpu_instruction();
+    }
+    assert!(nes.trainer_ram.is_none());
+    assert_eq!(nes.cpu_ram.read(0), 0x42);
+}
+
+#[test]
+fn trainer_gets_ram_without_prg_ram() {
+    // NROM without PRG RAM
+    let mut nes = Nes::new(trainer_game(0)).unwrap();
+    for _ in 0..10 {
+        nes.run_one_cpu_instruction();
+    }
+    assert!(nes.trainer_ram.is_some());
+    assert_eq!(nes.cpu_ram.read(0), 0x42);
+}
@@ -0,0 +1,65 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// VRC2/VRC4 game in the Nes 2.0 format without PRG RAM. Each 8 KiB PRG bank
+/// and each 1 KiB CHR bank starts with its number.
+fn vrc4_game(mapper: u8, submapper: u8) -> GameFile {
+    let mut prg_rom = vec![0xEA; 128 * 1024];
+    for bank in 0..16 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    let mut chr_rom = vec![0; 256 * 1024];
+    for bank in 0..256 {
+        chr_rom[bank * 1024] = bank as u8;
+    }
+
+    Header::nes20(mapper as u16, submapper).game(&prg_rom, &chr_rom)
+}
+
+#[test]
+fn address_wiring() {
+    // mapper, submapper, CPU address lines of the chip's A0 and A1
+    for (mapper, submapper, a0, a1) in [
+        (21, 1, 0x02, 0x04),
+        (21, 2, 0x40, 0x80),
+        (23, 1, 0x01, 0x02),
+        (23, 2, 0x04, 0x08),
+        (25, 1, 0x02, 0x01),
+        (25, 2, 0x08, 0x04),
+    ] {
+        let board = format!("mapper {mapper}.{submapper}");
+        let mut nes = Nes::new(vrc4_game(mapper, submapper)).unwrap();
+        nes.mapper.cpu_write(0x8000, 3);
+        nes.mapper.cpu_write(0xA000, 4);
+        nes.mapper.cpu_write(0x9000 | a1, 0b10);
+        assert_eq!(nes.mapper.cpu_read(0x8000), 14, "{board}");
+        assert_eq!(nes.mapper.cpu_read(0xA000), 4, "{board}");
+        assert_eq!(nes.mapper.cpu_read(0xC000), 3, "{board}");
+
+        // CHR bank 1 is at $B002/$B003
+        nes.mapper.cpu_write(0xB000 | a1, 0x05);
+        nes.mapper.cpu_write(0xB000 | a1 | a0, 0x1A);
+        assert_eq!(nes.mapper.ppu_read(0x0400), 0xA5, "{board}");
+    }
+}
+
+#[test]
+fn vrc2() {
+    // VRC2a ignores the lowest CHR bank bit
+    let mut nes = Nes::new(vrc4_game(22, 0)).unwrap();
+    nes.mapper.cpu_write(0xB000, 0x05);
+    nes.mapper.cpu_write(0xB002, 0x01);
+    assert_eq!(nes.mapper.ppu_read(0x0000), 0x0A);
+
+    // boards without RAM have a one bit latch at $6000-$6FFF
+    let mut nes = Nes::new(vrc4_game(23, 3)).unwrap();
+    assert!(!nes.mapper.cpu_address_mapped(0x7000));
+    nes.mapper.cpu_write(0x6000, 0xFF);
+    assert_eq!(nes.mapper.cpu_read(0x6000) & 1, 1);
+    nes.mapper.cpu_write(0x6000, 0xFE);
+    assert_eq!(nes.mapper.cpu_read(0x6000) & 1, 0);
+}
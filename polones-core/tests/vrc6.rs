@@ -0,0 +1,115 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::{audio_after_save_state, Header};
+
+/// VRC6 game that sets up the IRQ counter with the given latch and control
+/// values and counts IRQs at $00. Each 8 KiB PRG bank starts with its number.
+fn vrc6_game(mapper: u8, irq_latch: u8, irq_control: u8) -> GameFile {
+    // mapper 26 swaps A0 and A1, so $F001 and $F002 trade places
+    let (control, acknowledge) = match mapper {
+        26 => (0x02, 0x01),
+        _ => (0x01, 0x02),
+    };
+
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($E000)
+        0x78,                       // SEI
+        0xA9, 0x40,                 // LDA #$40
+        0x8D, 0x17, 0x40,           // STA $4017
+        0xA9, irq_latch,            // LDA #irq_latch
+        0x8D, 0x00, 0xF0,           // STA $F000
+        0xA9, irq_control,          // LDA #irq_control
+        0x8D, control, 0xF0,        // STA $F001
+        0x58,                       // CLI
+        0x4C, 0x11, 0xE0,           // JMP $E011
+        // irq ($E014)
+        0x8D, acknowledge, 0xF0,    // STA $F002
+        0xE6, 0x00,                 // INC $00
+        0x40,                       // RTI
+    ];
+
+    let mut prg_rom = vec![0xEA; 64 * 1024];
+    for bank in 0..8 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    prg_rom[0xE000..0xE000 + program.len()].copy_from_slice(program);
+    prg_rom[0xFFFA..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x14, 0xE0]);
+
+    Header::ines(mapper as u16).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn prg_banks() {
+    let mut nes = Nes::new(vrc6_game(24, 0, 0)).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    assert_eq!(cpu_bus.read(0xE000), 0x78);
+    cpu_bus.write(0x8000, 2);
+    cpu_bus.write(0xC000, 3);
+    assert_eq!(cpu_bus.read(0x8000), 4);
+    assert_eq!(cpu_bus.read(0xA000), 5);
+    assert_eq!(cpu_bus.read(0xC000), 3);
+}
+
+#[test]
+fn expansion_audio() {
+    for (mapper, enable) in [(24, 0x9002), (26, 0x9001)] {
+        let mut nes = Nes::new(vrc6_game(mapper, 0, 0)).unwrap();
+        assert_eq!(nes.mapper.audio_output(), 0);
+        // pulse with constant output
+        nes.mapper.cpu_write(0x9000, 0x8F);
+        nes.mapper.cpu_write(enable, 0x80);
+        assert!(nes.mapper.audio_output() > 0);
+    }
+}
+
+#[test]
+fn irq_counts_cycles_and_scanlines() {
+    // cycle mode, IRQ every 256 CPU cycles
+    let mut nes = Nes::new(vrc6_game(24, 0x00, 0b111)).unwrap();
+    for _ in 0..10000 {
+        nes.run_one_cpu_tick();
+    }
+    assert!((38..=39).contains(&nes.cpu_ram.read(0)));
+
+    // scanline mode, IRQ every 20 scanlines
+    let mut nes = Nes::new(vrc6_game(26, 0xEC, 0b011)).unwrap();
+    for _ in 0..29781 {
+        nes.run_one_cpu_tick();
+    }
+    assert_eq!(nes.cpu_ram.read(0), 13);
+}
+
+#[test]
+fn audio_after_loading_state() {
+    for mapper in [24, 26] {
+        // mapper 26 swaps A0 and A1
+        let register = |address: u16| match mapper {
+            26 => address & !0b11 | (address & 1) << 1 | (address & 2) >> 1,
+            _ => address,
+        };
+        let mut nes = Nes::new(vrc6_game(mapper, 0, 0)).unwrap();
+        // pulses at volumes 15 and 8 with different duties, and the saw
+        for (address, byte) in [
+            (0x9000, 0x3F),
+            (0x9001, 0x40),
+            (0x9002, 0x80),
+            (0xA000, 0x58),
+            (0xA001, 0x90),
+            (0xA002, 0x80),
+            (0xB000, 0x10),
+            (0xB001, 0x80),
+            (0xB002, 0x80),
+        ] {
+            nes.mapper.cpu_write(register(address), byte);
+        }
+        for _ in 0..1000 {
+            nes.run_one_cpu_tick();
+        }
+        let outputs = audio_after_save_state(&mut nes, vrc6_game(mapper, 0, 0), 4000);
+        assert_eq!(outputs.iter().sum::<i32>(), 33744000, "mapper {mapper}");
+    }
+}
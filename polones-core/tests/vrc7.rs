@@ -0,0 +1,63 @@
+use polones_core::game_file::GameFile;
+use polones_core::nes::Nes;
+
+mod common;
+
+use common::Header;
+
+/// VRC7 game whose 8 KiB PRG banks start with their numbers.
+fn vrc7_game() -> GameFile {
+    #[rustfmt::skip]
+    let program: &[u8] = &[
+        // reset ($E000)
+        0x78,                       // SEI
+        0x4C, 0x01, 0xE0,           // JMP $E001
+    ];
+
+    let mut prg_rom = vec![0xEA; 64 * 1024];
+    for bank in 0..8 {
+        prg_rom[bank * 8 * 1024] = bank as u8;
+    }
+    prg_rom[0xE000..0xE000 + program.len()].copy_from_slice(program);
+    prg_rom[0xFFFA..].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE0, 0x00, 0xE0]);
+
+    Header::ines(85).game(&prg_rom, &[0; 8 * 1024])
+}
+
+#[test]
+fn prg_banks() {
+    let mut nes = Nes::new(vrc7_game()).unwrap();
+    let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
+    assert_eq!(cpu_bus.read(0xE000), 0x78);
+    cpu_bus.write(0x8000, 2);
+    cpu_bus.write(0x8010, 3);
+    cpu_bus.write(0x9000, 5);
+    assert_eq!(cpu_bus.read(0x8000), 2);
+    assert_eq!(cpu_bus.read(0xA000), 3);
+    assert_eq!(cpu_bus.read(0xC000), 5);
+}
+
+#[test]
+fn fm_audio() {
+    let mut nes = Nes::new(vrc7_game()).unwrap();
+    let write_register = |nes: &mut Nes, register: u8, value: u8| {
+        nes.mapper.cpu_write(0x9010, register);
+        nes.mapper.cpu_write(0x9030, value);
+    };
+    // instrument 1 at full volume, key on with block 4
+    write_register(&mut nes, 0x30, 0x10);
+    write_register(&mut nes, 0x10, 0xAC);
+    write_register(&mut nes, 0x20, 0x18);
+
+    let mut peak = 0;
+    for _ in 0..10000 {
+        nes.run_one_cpu_tick();
+        peak = peak.max(nes.mapper.audio_output().abs());
+    }
+    assert!(peak > 0);
+
+    // silencing the chip resets it
+    nes.mapper.cpu_write(0xE000, 0x40);
+    nes.run_one_cpu_tick();
+    assert_eq!(nes.mapper.audio_output(), 0);
+}
@@ -12,22 +12,22 @@ const DISASSEMBLY_TABLE: [(Operation, AddressingMode); 256] = {
     use Operation::*;
     use AddressingMode::*;
     [
-        /* 0 */ (BRK, Implied),   (ORA, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (ORA, Zeropage),         (ASL, Zeropage),         (XXX, Implied), (PHP, Implied), (ORA, Immediate),        (ASL, Accumulator), (XXX, Implied), (XXX, Implied),          (ORA, Absolute),         (ASL, Absolute),         (XXX, Implied),
-        /* 1 */ (BPL, Relative),  (ORA, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (ORA, ZeropageXIndexed), (ASL, ZeropageXIndexed), (XXX, Implied), (CLC, Implied), (ORA, AbsoluteYIndexed), (XXX, Implied),     (XXX, Implied), (XXX, Implied),          (ORA, AbsoluteXIndexed), (ASL, AbsoluteXIndexed), (XXX, Implied),
-        /* 2 */ (JSR, Absolute),  (AND, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (BIT, Zeropage),         (AND, Zeropage),         (ROL, Zeropage),         (XXX, Implied), (PLP, Implied), (AND, Immediate),        (ROL, Accumulator), (XXX, Implied), (BIT, Absolute),         (AND, Absolute),         (ROL, Absolute),         (XXX, Implied),
-        /* 3 */ (BMI, Relative),  (AND, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (AND, ZeropageXIndexed), (ROL, ZeropageXIndexed), (XXX, Implied), (SEC, Implied), (AND, AbsoluteYIndexed), (XXX, Implied),     (XXX, Implied), (XXX, Implied),          (AND, AbsoluteXIndexed), (ROL, AbsoluteXIndexed), (XXX, Implied),
-        /* 4 */ (RTI, Implied),   (EOR, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (EOR, Zeropage),         (LSR, Zeropage),         (XXX, Implied), (PHA, Implied), (EOR, Immediate),        (LSR, Accumulator), (XXX, Implied), (JMP, Absolute),         (EOR, Absolute),         (LSR, Absolute),         (XXX, Implied),
-        /* 5 */ (BVC, Relative),  (EOR, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (EOR, ZeropageXIndexed), (LSR, ZeropageXIndexed), (XXX, Implied), (CLI, Implied), (EOR, AbsoluteYIndexed), (XXX, Implied),     (XXX, Implied), (XXX, Implied),          (EOR, AbsoluteXIndexed), (LSR, AbsoluteXIndexed), (XXX, Implied),
-        /* 6 */ (RTS, Implied),   (ADC, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (ADC, Zeropage),         (ROR, Zeropage),         (XXX, Implied), (PLA, Implied), (ADC, Immediate),        (ROR, Accumulator), (XXX, Implied), (JMP, Indirect),         (ADC, Absolute),         (ROR, Absolute),         (XXX, Implied),
-        /* 7 */ (BVS, Relative),  (ADC, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (ADC, ZeropageXIndexed), (ROR, ZeropageXIndexed), (XXX, Implied), (SEI, Implied), (ADC, AbsoluteYIndexed), (XXX, Implied),     (XXX, Implied), (XXX, Implied),          (ADC, AbsoluteXIndexed), (ROR, AbsoluteXIndexed), (XXX, Implied),
-        /* 8 */ (XXX, Implied),   (STA, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (STY, Zeropage),         (STA, Zeropage),         (STX, Zeropage),         (XXX, Implied), (DEY, Implied), (XXX, Implied),          (TXA, Implied),     (XXX, Implied), (STY, Absolute),         (STA, Absolute),         (STX, Absolute),         (XXX, Implied),
-        /* 9 */ (BCC, Relative),  (STA, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (STY, ZeropageXIndexed), (STA, ZeropageXIndexed), (STX, ZeropageYIndexed), (XXX, Implied), (TYA, Implied), (STA, AbsoluteYIndexed), (TXS, Implied),     (XXX, Implied), (XXX, Implied),          (STA, AbsoluteXIndexed), (XXX, Implied),          (XXX, Implied),
-        /* A */ (LDY, Immediate), (LDA, XIndexedIndirect), (LDX, Immediate), (XXX, Implied), (LDY, Zeropage),         (LDA, Zeropage),         (LDX, Zeropage),         (XXX, Implied), (TAY, Implied), (LDA, Immediate),        (TAX, Implied),     (XXX, Implied), (LDY, Absolute),         (LDA, Absolute),         (LDX, Absolute),         (XXX, Implied),
-        /* B */ (BCS, Relative),  (LDA, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (LDY, ZeropageXIndexed), (LDA, ZeropageXIndexed), (LDX, ZeropageYIndexed), (XXX, Implied), (CLV, Implied), (LDA, AbsoluteYIndexed), (TSX, Implied),     (XXX, Implied), (LDY, AbsoluteXIndexed), (LDA, AbsoluteXIndexed), (LDX, AbsoluteYIndexed), (XXX, Implied),
-        /* C */ (CPY, Immediate), (CMP, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (CPY, Zeropage),         (CMP, Zeropage),         (DEC, Zeropage),         (XXX, Implied), (INY, Implied), (CMP, Immediate),        (DEX, Implied),     (XXX, Implied), (CPY, Absolute),         (CMP, Absolute),         (DEC, Absolute),         (XXX, Implied),
-        /* D */ (BNE, Relative),  (CMP, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (CMP, ZeropageXIndexed), (DEC, ZeropageXIndexed), (XXX, Implied), (CLD, Implied), (CMP, AbsoluteYIndexed), (XXX, Implied),     (XXX, Implied), (XXX, Implied),          (CMP, AbsoluteXIndexed), (DEC, AbsoluteXIndexed), (XXX, Implied),
-        /* E */ (CPX, Immediate), (SBC, XIndexedIndirect), (XXX, Implied),   (XXX, Implied), (CPX, Zeropage),         (SBC, Zeropage),         (INC, Zeropage),         (XXX, Implied), (INX, Implied), (SBC, Immediate),        (NOP, Implied),     (XXX, Implied), (CPX, Absolute),         (SBC, Absolute),         (INC, Absolute),         (XXX, Implied),
-        /* F */ (BEQ, Relative),  (SBC, IndirectYIndexed), (XXX, Implied),   (XXX, Implied), (XXX, Implied),          (SBC, ZeropageXIndexed), (INC, ZeropageXIndexed), (XXX, Implied), (SED, Implied), (SBC, AbsoluteYIndexed), (XXX, Implied),     (XXX, Implied), (XXX, Implied),          (SBC, AbsoluteXIndexed), (INC, AbsoluteXIndexed), (XXX, Implied),
+        /* 0 */ (BRK, Implied),   (ORA, XIndexedIndirect), (JAM, Implied),   (SLO, XIndexedIndirect), (NOP, Zeropage),         (ORA, Zeropage),         (ASL, Zeropage),         (SLO, Zeropage),         (PHP, Implied), (ORA, Immediate),        (ASL, Accumulator), (ANC, Immediate),        (NOP, Absolute),         (ORA, Absolute),         (ASL, Absolute),         (SLO, Absolute),
+        /* 1 */ (BPL, Relative),  (ORA, IndirectYIndexed), (JAM, Implied),   (SLO, IndirectYIndexed), (NOP, ZeropageXIndexed), (ORA, ZeropageXIndexed), (ASL, ZeropageXIndexed), (SLO, ZeropageXIndexed), (CLC, Implied), (ORA, AbsoluteYIndexed), (NOP, Implied),     (SLO, AbsoluteYIndexed), (NOP, AbsoluteXIndexed), (ORA, AbsoluteXIndexed), (ASL, AbsoluteXIndexed), (SLO, AbsoluteXIndexed),
+        /* 2 */ (JSR, Absolute),  (AND, XIndexedIndirect), (JAM, Implied),   (RLA, XIndexedIndirect), (BIT, Zeropage),         (AND, Zeropage),         (ROL, Zeropage),         (RLA, Zeropage),         (PLP, Implied), (AND, Immediate),        (ROL, Accumulator), (ANC, Immediate),        (BIT, Absolute),         (AND, Absolute),         (ROL, Absolute),         (RLA, Absolute),
+        /* 3 */ (BMI, Relative),  (AND, IndirectYIndexed), (JAM, Implied),   (RLA, IndirectYIndexed), (NOP, ZeropageXIndexed), (AND, ZeropageXIndexed), (ROL, ZeropageXIndexed), (RLA, ZeropageXIndexed), (SEC, Implied), (AND, AbsoluteYIndexed), (NOP, Implied),     (RLA, AbsoluteYIndexed), (NOP, AbsoluteXIndexed), (AND, AbsoluteXIndexed), (ROL, AbsoluteXIndexed), (RLA, AbsoluteXIndexed),
+        /* 4 */ (RTI, Implied),   (EOR, XIndexedIndirect), (JAM, Implied),   (SRE, XIndexedIndirect), (NOP, Zeropage),         (EOR, Zeropage),         (LSR, Zeropage),         (SRE, Zeropage),         (PHA, Implied), (EOR, Immediate),        (LSR, Accumulator), (ALR, Immediate),        (JMP, Absolute),         (EOR, Absolute),         (LSR, Absolute),         (SRE, Absolute),
+        /* 5 */ (BVC, Relative),  (EOR, IndirectYIndexed), (JAM, Implied),   (SRE, IndirectYIndexed), (NOP, ZeropageXIndexed), (EOR, ZeropageXIndexed), (LSR, ZeropageXIndexed), (SRE, ZeropageXIndexed), (CLI, Implied), (EOR, AbsoluteYIndexed), (NOP, Implied),     (SRE, AbsoluteYIndexed), (NOP, AbsoluteXIndexed), (EOR, AbsoluteXIndexed), (LSR, AbsoluteXIndexed), (SRE, AbsoluteXIndexed),
+        /* 6 */ (RTS, Implied),   (ADC, XIndexedIndirect), (JAM, Implied),   (RRA, XIndexedIndirect), (NOP, Zeropage),         (ADC, Zeropage),         (ROR, Zeropage),         (RRA, Zeropage),         (PLA, Implied), (ADC, Immediate),        (ROR, Accumulator), (ARR, Immediate),        (JMP, Indirect),         (ADC, Absolute),         (ROR, Absolute),         (RRA, Absolute),
+        /* 7 */ (BVS, Relative),  (ADC, IndirectYIndexed), (JAM, Implied),   (RRA, IndirectYIndexed), (NOP, ZeropageXIndexed), (ADC, ZeropageXIndexed), (ROR, ZeropageXIndexed), (RRA, ZeropageXIndexed), (SEI, Implied), (ADC, AbsoluteYIndexed), (NOP, Implied),     (RRA, AbsoluteYIndexed), (NOP, AbsoluteXIndexed), (ADC, AbsoluteXIndexed), (ROR, AbsoluteXIndexed), (RRA, AbsoluteXIndexed),
+        /* 8 */ (NOP, Immediate), (STA, XIndexedIndirect), (NOP, Immediate), (SAX, XIndexedIndirect), (STY, Zeropage),         (STA, Zeropage),         (STX, Zeropage),         (SAX, Zeropage),         (DEY, Implied), (NOP, Immediate),        (TXA, Implied),     (ANE, Immediate),        (STY, Absolute),         (STA, Absolute),         (STX, Absolute),         (SAX, Absolute),
+        /* 9 */ (BCC, Relative),  (STA, IndirectYIndexed), (JAM, Implied),   (SHA, IndirectYIndexed), (STY, ZeropageXIndexed), (STA, ZeropageXIndexed), (STX, ZeropageYIndexed), (SAX, ZeropageYIndexed), (TYA, Implied), (STA, AbsoluteYIndexed), (TXS, Implied),     (TAS, AbsoluteYIndexed), (SHY, AbsoluteXIndexed), (STA, AbsoluteXIndexed), (SHX, AbsoluteYIndexed), (SHA, AbsoluteYIndexed),
+        /* A */ (LDY, Immediate), (LDA, XIndexedIndirect), (LDX, Immediate), (LAX, XIndexedIndirect), (LDY, Zeropage),         (LDA, Zeropage),         (LDX, Zeropage),         (LAX, Zeropage),         (TAY, Implied), (LDA, Immediate),        (TAX, Implied),     (LXA, Immediate),        (LDY, Absolute),         (LDA, Absolute),         (LDX, Absolute),         (LAX, Absolute),
+        /* B */ (BCS, Relative),  (LDA, IndirectYIndexed), (JAM, Implied),   (LAX, IndirectYIndexed), (LDY, ZeropageXIndexed), (LDA, ZeropageXIndexed), (LDX, ZeropageYIndexed), (LAX, ZeropageYIndexed), (CLV, Implied), (LDA, AbsoluteYIndexed), (TSX, Implied),     (LAS, AbsoluteYIndexed), (LDY, AbsoluteXIndexed), (LDA, AbsoluteXIndexed), (LDX, AbsoluteYIndexed), (LAX, AbsoluteYIndexed),
+        /* C */ (CPY, Immediate), (CMP, XIndexedIndirect), (NOP, Immediate), (DCP, XIndexedIndirect), (CPY, Zeropage),         (CMP, Zeropage),         (DEC, Zeropage),         (DCP, Zeropage),         (INY, Implied), (CMP, Immediate),        (DEX, Implied),     (AXS, Immediate),        (CPY, Absolute),         (CMP, Absolute),         (DEC, Absolute),         (DCP, Absolute),
+        /* D */ (BNE, Relative),  (CMP, IndirectYIndexed), (JAM, Implied),   (DCP, IndirectYIndexed), (NOP, ZeropageXIndexed), (CMP, ZeropageXIndexed), (DEC, ZeropageXIndexed), (DCP, ZeropageXIndexed), (CLD, Implied), (CMP, AbsoluteYIndexed), (NOP, Implied),     (DCP, AbsoluteYIndexed), (NOP, AbsoluteXIndexed), (CMP, AbsoluteXIndexed), (DEC, AbsoluteXIndexed), (DCP, AbsoluteXIndexed),
+        /* E */ (CPX, Immediate), (SBC, XIndexedIndirect), (NOP, Immediate), (ISC, XIndexedIndirect), (CPX, Zeropage),         (SBC, Zeropage),         (INC, Zeropage),         (ISC, Zeropage),         (INX, Implied), (SBC, Immediate),        (NOP, Implied),     (SBC, Immediate),        (CPX, Absolute),         (SBC, Absolute),         (INC, Absolute),         (ISC, Absolute),
+        /* F */ (BEQ, Relative),  (SBC, IndirectYIndexed), (JAM, Implied),   (ISC, IndirectYIndexed), (NOP, ZeropageXIndexed), (SBC, ZeropageXIndexed), (INC, ZeropageXIndexed), (ISC, ZeropageXIndexed), (SED, Implied), (SBC, AbsoluteYIndexed), (NOP, Implied),     (ISC, AbsoluteYIndexed), (NOP, AbsoluteXIndexed), (SBC, AbsoluteXIndexed), (INC, AbsoluteXIndexed), (ISC, AbsoluteXIndexed),
     ]
 };
 
@@ -131,7 +131,27 @@ enum Operation {
     TXA,
     TXS,
     TYA,
-    XXX,
+    // unofficial
+    ALR,
+    ANC,
+    ANE,
+    ARR,
+    AXS,
+    DCP,
+    ISC,
+    JAM,
+    LAS,
+    LAX,
+    LXA,
+    RLA,
+    RRA,
+    SAX,
+    SHA,
+    SHX,
+    SHY,
+    SLO,
+    SRE,
+    TAS,
 }
 
 impl Operation {
@@ -140,16 +160,21 @@ impl Operation {
         match self {
             // load and store
             LDA | LDX | LDY | STA | STX | STY => Green,
+            LAX | LXA | SAX | SHA | SHX | SHY => Green,
             // transfer
             TAX | TAY | TSX | TXA | TXS | TYA => Yellow,
+            LAS | TAS => Yellow,
             // stack
             PHA | PHP | PLA | PLP => Blue,
             // shift
             ASL | LSR | ROL | ROR => Cyan,
+            ALR | ARR | RLA | RRA | SLO | SRE => Cyan,
             // logic
             AND | BIT | EOR | ORA => Cyan,
+            ANC | ANE => Cyan,
             // arithmetic
             ADC | CMP | CPX | CPY | SBC => White,
+            AXS | DCP | ISC => White,
             // increment and decrement
             DEC | DEX | DEY | INC | INX | INY => White,
             // control
@@ -159,7 +184,9 @@ impl Operation {
             // flags
             CLC | CLD | CLI | CLV | SEC | SED | SEI => Magenta,
             // nop
-            NOP | XXX => Red,
+            NOP => Red,
+            // halt
+            JAM => Red,
         }
     }
 }
@@ -537,12 +564,13 @@ impl SdlCpuDebugger {
                     let opcode = cpu_bus.read(position);
                     let (operation, mode) = DISASSEMBLY_TABLE[opcode as usize];
 
-                    if operation == Operation::XXX {
+                    self.disassembly[position as usize] = DisassemblyValue::Opcode(operation, mode);
+
+                    // CPU stops at JAM, there's nothing to disassemble after it.
+                    if operation == Operation::JAM {
                         break;
                     }
 
-                    self.disassembly[position as usize] = DisassemblyValue::Opcode(operation, mode);
-
                     use AddressingMode::*;
                     match mode {
                         Accumulator | Implied => {
@@ -745,11 +773,24 @@ fn irq_address(cpu_bus: &mut CpuBus<'_>) -> u16 {
 
 #[test]
 fn disassembly_table_has_unique_elements() {
+    // Unofficial opcodes that behave exactly like another opcode.
+    const DUPLICATES: [u8; 35] = [
+        0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2, // JAM
+        0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA, // NOP
+        0x82, 0x89, 0xC2, 0xE2, // NOP #i
+        0x44, 0x64, // NOP zp
+        0x34, 0x54, 0x74, 0xD4, 0xF4, // NOP zp,X
+        0x3C, 0x5C, 0x7C, 0xDC, 0xFC, // NOP abs,X
+        0x2B, // ANC #i
+        0xEB, // SBC #i
+    ];
+
     let mut set = std::collections::BTreeSet::<(Operation, AddressingMode)>::new();
 
-    for entry in DISASSEMBLY_TABLE
+    for (_, entry) in DISASSEMBLY_TABLE
         .iter()
-        .filter(|entry| **entry != (Operation::XXX, AddressingMode::Implied))
+        .enumerate()
+        .filter(|(opcode, _)| !DUPLICATES.contains(&(*opcode as u8)))
     {
         assert!(!set.contains(entry), "{:?} {:?} repeats", entry.0, entry.1);
         set.insert(*entry);
@@ -1,6 +1,5 @@
 use crate::EmulatorState;
 use polones_core::nes::Nes;
-use polones_core::ppu::PALLETTE;
 use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 use sdl2::rect::Rect;
@@ -99,6 +98,7 @@ impl SdlGraphicsDebugger {
     pub fn draw(&mut self, nes: &mut Nes) {
         let (_cpu, mut cpu_bus) = nes.split_into_cpu_and_bus();
         let (ppu, mut ppu_bus) = cpu_bus.split_into_ppu_and_bus();
+        let colors = ppu.palette.colors();
 
         if self.mode == 1 || self.mode == 2 {
             let nt = ppu.control_register.get_background_tile_select() as u16;
@@ -145,7 +145,7 @@ impl SdlGraphicsDebugger {
 
                                             let (r, g, b) = if self.mode == 1 {
                                                 if ((high >> 7 << 1) | low >> 7) == 0 {
-                                                    PALLETTE[(ppu_bus.read(0x3F00) & 0b00111111)
+                                                    colors[(ppu_bus.read(0x3F00) & 0b00111111)
                                                         as usize]
                                                 } else {
                                                     let b = ppu_bus.read(
@@ -154,7 +154,7 @@ impl SdlGraphicsDebugger {
                                                             + (((high as u16) >> 7 << 1)
                                                                 | low as u16 >> 7),
                                                     ) & 0b00111111;
-                                                    PALLETTE[b as usize]
+                                                    colors[b as usize]
                                                 }
                                             } else {
                                                 match (high >> 7 << 1) | (low >> 7) {
@@ -207,7 +207,7 @@ impl SdlGraphicsDebugger {
 
                                         let (r, g, b) = if self.mode == 3 {
                                             if ((high >> 7 << 1) | low >> 7) == 0 {
-                                                PALLETTE
+                                                colors
                                                     [(ppu_bus.read(0x3F00) & 0b00111111) as usize]
                                             } else {
                                                 let b = ppu_bus.read(
@@ -216,7 +216,7 @@ impl SdlGraphicsDebugger {
                                                         + (((high as u16) >> 7 << 1)
                                                             | low as u16 >> 7),
                                                 ) & 0b00111111;
-                                                PALLETTE[b as usize]
+                                                colors[b as usize]
                                             }
                                         } else {
                                             match (high >> 7 << 1) | (low >> 7) {
@@ -303,13 +303,13 @@ impl SdlGraphicsDebugger {
                                             }
                                         } else {
                                             if color == 0 {
-                                                PALLETTE
+                                                colors
                                                     [(ppu_bus.read(0x3F00) & 0b00111111) as usize]
                                             } else {
                                                 let b = ppu_bus.read(
                                                     0x3F10 + ((palette as u16) << 2) + color as u16,
                                                 ) & 0b00111111;
-                                                PALLETTE[b as usize]
+                                                colors[b as usize]
                                             }
                                         };
 
@@ -374,14 +374,14 @@ impl SdlGraphicsDebugger {
 
                                     for xf in 0..8usize {
                                         let (r, g, b) = if ((high >> 7 << 1) | low >> 7) == 0 {
-                                            PALLETTE[(ppu_bus.read(0x3F00) & 0b00111111) as usize]
+                                            colors[(ppu_bus.read(0x3F00) & 0b00111111) as usize]
                                         } else {
                                             let b = ppu_bus.read(
                                                 0x3F10
                                                     + ((palette as u16) << 2)
                                                     + (((high as u16) >> 7 << 1) | low as u16 >> 7),
                                             ) & 0b00111111;
-                                            PALLETTE[b as usize]
+                                            colors[b as usize]
                                         };
 
                                         let i = (256 * 512)
@@ -438,7 +438,7 @@ impl SdlGraphicsDebugger {
                         for yf in 0..8usize {
                             for xc in 0..4usize {
                                 let byte = ppu_bus.read(0x3F00 | ((yc as u16) << 2) | xc as u16);
-                                let (r, g, b) = PALLETTE[byte as usize & 0b00111111];
+                                let (r, g, b) = colors[byte as usize & 0b00111111];
                                 for xf in 0..8 {
                                     let i = (512 * 8)
                                         + (256 + 24)
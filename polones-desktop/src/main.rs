@@ -4,8 +4,11 @@ use cpu_debugger::SdlCpuDebugger;
 use graphics_debugger::SdlGraphicsDebugger;
 use mapper_debugger::SdlMapperDebugger;
 use memory_debugger::SdlMemoryDebugger;
+use polones_core::disk_file::DiskFile;
 use polones_core::game_file::GameFile;
-use polones_core::nes::{Frame, GamepadState, Nes, PortState};
+use polones_core::nes::{Frame, GamepadState, Nes, PortState, Region};
+use polones_core::nsf_file::{NsfFile, NsfTrack};
+use polones_core::palette::Palette;
 use ppu_debugger::SdlPpuDebugger;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::event::{Event, WindowEvent};
@@ -16,7 +19,8 @@ use sdl2::surface::Surface;
 use sdl2::video::WindowContext;
 use sdl_extensions::get_default_playback_device_name;
 use std::collections::VecDeque;
-use std::path::Component;
+use std::path::{Component, Path};
+use std::time::{Duration, Instant};
 
 mod apu_debugger;
 mod cpu_debugger;
@@ -35,13 +39,21 @@ struct SdlGameWindow {
     gamepad_2: GamepadState,
     frame: Box<Frame>,
     version: u32,
+    /// Name of the game, or title and artist of NSF music.
+    title: String,
+    /// Labels and lengths of NSF songs.
+    tracks: Vec<NsfTrack>,
+    /// NSF songs in the order they are played.
+    playlist: Vec<usize>,
+    /// CPU cycle the current NSF song started at.
+    song_start: u64,
 }
 
 impl SdlGameWindow {
     const WIDTH: u32 = 256;
     const HEIGHT: u32 = 240;
 
-    fn new(canvas: sdl2::render::WindowCanvas) -> Self {
+    fn new(canvas: sdl2::render::WindowCanvas, title: String) -> Self {
         let mut canvas = canvas;
         canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
         let texture_creator = canvas.texture_creator();
@@ -66,10 +78,65 @@ impl SdlGameWindow {
             gamepad_2: GamepadState::default(),
             frame: Box::new([[(0, 0, 0); 256]; 240]),
             version: 0,
+            title,
+            tracks: Vec::new(),
+            playlist: Vec::new(),
+            song_start: 0,
         }
     }
 
-    fn handle_event(&mut self, _nes: &mut Nes, event: Event, state: &mut EmulatorState) {
+    /// Shows the title in the window's title bar, with the song number and
+    /// label for NSF music.
+    fn update_title(&mut self, nes: &Nes) {
+        let label = nes
+            .song()
+            .and_then(|song| self.tracks.get(song)?.label.as_ref());
+        let title = match (nes.song(), label) {
+            (Some(song), Some(label)) => format!(
+                "{} ({}/{}: {}) - Polones",
+                self.title,
+                song + 1,
+                nes.song_count(),
+                label
+            ),
+            (Some(song), None) => format!(
+                "{} ({}/{}) - Polones",
+                self.title,
+                song + 1,
+                nes.song_count()
+            ),
+            (None, _) => format!("{} - Polones", self.title),
+        };
+        self.canvas.window_mut().set_title(&title).unwrap();
+    }
+
+    /// Moves by `offset` songs along the playlist.
+    fn change_song(&mut self, nes: &mut Nes, offset: isize) {
+        let current = nes.song().unwrap_or(0);
+        let position = self.playlist.iter().position(|&song| song == current);
+        let position = position.unwrap_or(0) as isize + offset;
+        let position = position.rem_euclid(self.playlist.len() as isize) as usize;
+        let song = self.playlist[position];
+        nes.select_song(song).unwrap();
+        self.song_start = nes.cpu.cycle;
+        self.update_title(nes);
+        eprintln!("Playing song {}", song + 1);
+    }
+
+    /// Returns true once the current NSF song played for its length and fade.
+    fn song_finished(&self, nes: &Nes) -> bool {
+        let Some(track) = nes.song().and_then(|song| self.tracks.get(song)) else {
+            return false;
+        };
+        let Some(length) = track.length else {
+            return false;
+        };
+        let milliseconds = (length + track.fade.unwrap_or(0)) as u64;
+        let cycles = milliseconds * nes.region().cpu_clock_rate() as u64 / 1000;
+        nes.cpu.cycle - self.song_start >= cycles
+    }
+
+    fn handle_event(&mut self, nes: &mut Nes, event: Event, state: &mut EmulatorState) {
         match event {
             Event::Window {
                 win_event: WindowEvent::Close,
@@ -86,6 +153,30 @@ impl SdlGameWindow {
             } => {
                 state.exit = true;
             }
+            Event::KeyDown {
+                keycode: _k @ Some(Keycode::Tab),
+                repeat: false,
+                ..
+            } if nes.disk_side_count() > 0 => {
+                let side_count = nes.disk_side_count();
+                let side = nes.disk_side().map_or(0, |side| (side + 1) % side_count);
+                nes.insert_disk_side(Some(side)).unwrap();
+                eprintln!("Inserting disk side {}", side + 1);
+            }
+            Event::KeyDown {
+                keycode: _k @ Some(Keycode::Left),
+                repeat: false,
+                ..
+            } if nes.song_count() > 0 => {
+                self.change_song(nes, -1);
+            }
+            Event::KeyDown {
+                keycode: _k @ Some(Keycode::Right),
+                repeat: false,
+                ..
+            } if nes.song_count() > 0 => {
+                self.change_song(nes, 1);
+            }
             Event::KeyDown {
                 keycode: _k @ Some(Keycode::W),
                 ..
@@ -280,6 +371,35 @@ struct Args {
 
     #[arg(long, default_value="3")]
     scale: u32,
+
+    /// Overrides the TV system from the ROM header.
+    #[arg(long, value_enum)]
+    region: Option<RegionArg>,
+
+    /// Name of a built-in palette (polones, 2c02) or path to a .pal file.
+    #[arg(long)]
+    palette: Option<String>,
+
+    /// Path to the Famicom Disk System BIOS, needed to play .fds images.
+    #[arg(long)]
+    fds_bios: Option<String>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum RegionArg {
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+impl From<RegionArg> for Region {
+    fn from(region: RegionArg) -> Self {
+        match region {
+            RegionArg::Ntsc => Region::Ntsc,
+            RegionArg::Pal => Region::Pal,
+            RegionArg::Dendy => Region::Dendy,
+        }
+    }
 }
 
 fn main() {
@@ -308,12 +428,68 @@ fn main() {
         }
     };
 
-    let game_file = match GameFile::read(args.rom.clone(), rom_data) {
-        Ok(game_file) => game_file,
-        Err(_error) => {
-            eprintln!("Could not parse ROM");
-            std::process::exit(1);
+    let extension = Path::new(&args.rom).extension();
+    let is_disk = rom_data.starts_with(b"FDS\x1A") || extension.is_some_and(|ext| ext == "fds");
+    let is_nsf = rom_data.starts_with(b"NESM\x1A")
+        || rom_data.starts_with(b"NSFE")
+        || extension.is_some_and(|ext| ext == "nsf" || ext == "nsfe");
+    let mut title = rom_filename.clone();
+    let mut tracks = Vec::new();
+    let mut playlist = Vec::new();
+    let mut nes = if is_nsf {
+        let nsf_file = match NsfFile::read(args.rom.clone(), rom_data) {
+            Ok(nsf_file) => nsf_file,
+            Err(error) => {
+                eprintln!("Could not parse NSF file: {error}");
+                std::process::exit(1);
+            }
+        };
+        if !nsf_file.title.is_empty() {
+            title = format!("{} - {}", nsf_file.title, nsf_file.artist);
+        }
+        eprintln!("Title: {}", nsf_file.title);
+        eprintln!("Artist: {}", nsf_file.artist);
+        eprintln!("Copyright: {}", nsf_file.copyright);
+        if !nsf_file.ripper.is_empty() {
+            eprintln!("Ripper: {}", nsf_file.ripper);
         }
+        eprintln!("Songs: {}", nsf_file.song_count);
+        let nes = Nes::new_nsf(&nsf_file).expect("Could not start the music");
+        playlist = match nsf_file.playlist {
+            Some(playlist) if !playlist.is_empty() => playlist,
+            _ => (0..nsf_file.song_count).collect(),
+        };
+        tracks = nsf_file.tracks;
+        nes
+    } else if is_disk {
+        let disk_file = match DiskFile::read(args.rom.clone(), rom_data) {
+            Ok(disk_file) => disk_file,
+            Err(error) => {
+                eprintln!("Could not parse disk image: {error}");
+                std::process::exit(1);
+            }
+        };
+        let Some(bios_path) = &args.fds_bios else {
+            eprintln!("Disk System games need the BIOS, pass it with --fds-bios");
+            std::process::exit(1);
+        };
+        let bios = match std::fs::read(bios_path) {
+            Ok(bios) => bios,
+            Err(error) => {
+                eprintln!("Could not read Disk System BIOS: {error}");
+                std::process::exit(1);
+            }
+        };
+        Nes::new_fds(&disk_file, &bios).expect("Could not start the game")
+    } else {
+        let game_file = match GameFile::read(args.rom.clone(), rom_data) {
+            Ok(game_file) => game_file,
+            Err(_error) => {
+                eprintln!("Could not parse ROM");
+                std::process::exit(1);
+            }
+        };
+        Nes::new(game_file).expect("Could not start the game")
     };
 
     let sdl_context = sdl2::init().unwrap();
@@ -481,8 +657,44 @@ fn main() {
         .build()
         .unwrap();
 
-    let mut game_window = SdlGameWindow::new(game_canvas);
-    let mut nes = Nes::new(game_file).expect("Could not start the game");
+    let mut game_window = SdlGameWindow::new(game_canvas, title);
+    game_window.tracks = tracks;
+    game_window.playlist = playlist;
+    if let Some(region) = args.region {
+        nes.set_region(region.into());
+        // NSF music picks its play rate when a song starts
+        if let Some(song) = nes.song() {
+            nes.select_song(song).unwrap();
+        }
+    }
+    game_window.song_start = nes.cpu.cycle;
+    game_window.update_title(&nes);
+    let cpu_clock_rate = nes.region().cpu_clock_rate() as i32;
+    if let Some(palette) = &args.palette {
+        match load_palette(palette) {
+            Ok(palette) => nes.set_palette(palette),
+            Err(error) => {
+                eprintln!("Could not load palette: {error}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Battery-backed memory is kept in a .sav file next to the ROM.
+    let save_path = Path::new(&args.rom).with_extension("sav");
+    if nes.battery_ram().is_some() {
+        match std::fs::read(&save_path) {
+            Ok(data) => {
+                if let Err(error) = nes.load_battery_ram(&data) {
+                    eprintln!("Could not load save file: {error}");
+                }
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => eprintln!("Could not read save file: {error}"),
+        }
+    }
+    let mut saved_battery_ram = nes.battery_ram().map(|ram| ram.to_vec());
+    let mut last_battery_ram_save = Instant::now();
 
     // On every write to $4016 we push port 1 and port 2 state to this vec.
     let mut inputs: Vec<u8> = Vec::new();
@@ -525,8 +737,8 @@ fn main() {
         .map(|mode| mode.refresh_rate)
         .unwrap_or(60);
 
-    // CPU cycles per frame = 29780.5*
-    let audio_samples_per_draw = 59561 * 30 / refresh_rate;
+    // one audio sample is generated per CPU cycle
+    let audio_samples_per_draw = cpu_clock_rate / refresh_rate;
 
     let default_playback_device_name = get_default_playback_device_name();
 
@@ -540,6 +752,7 @@ fn main() {
             },
             |_| AudioRunner {
                 source: audio_receiver,
+                cpu_clock_rate: cpu_clock_rate as usize,
                 samples: VecDeque::with_capacity(audio_samples_per_draw as usize * 5),
             },
         )
@@ -623,8 +836,7 @@ fn main() {
             .map(|mode| mode.refresh_rate)
             .unwrap_or(60);
 
-        // CPU cycles per frame = 29780.5*
-        let audio_samples_per_draw = 59561 * 30 / refresh_rate;
+        let audio_samples_per_draw = cpu_clock_rate / refresh_rate;
 
         if args.record_inputs {
             while inputs_version != nes.input.read_version {
@@ -708,6 +920,17 @@ fn main() {
             }
         }
 
+        // go to the next NSF song when the current one ends
+        if state.running && game_window.song_finished(&nes) {
+            game_window.change_song(&mut nes, 1);
+        }
+
+        // pause if the game jammed the CPU
+        if state.running && nes.cpu.halted() {
+            eprintln!("CPU halted at {:04X}, pausing.", nes.cpu.program_counter);
+            state.running = false;
+        }
+
         if game_window.version != nes.display.version {
             game_window.version = nes.display.version;
             std::mem::swap(&mut game_window.frame, &mut nes.display.frame);
@@ -733,8 +956,15 @@ fn main() {
             debugger.draw(&mut nes);
         }
         game_window.draw_and_wait(&mut nes);
+
+        if last_battery_ram_save.elapsed() >= BATTERY_RAM_SAVE_INTERVAL {
+            save_battery_ram(&nes, &save_path, &mut saved_battery_ram);
+            last_battery_ram_save = Instant::now();
+        }
     }
 
+    save_battery_ram(&nes, &save_path, &mut saved_battery_ram);
+
     if args.record_inputs {
         std::fs::write(format!("../inputs/{rom_filename}.bin"), inputs).unwrap_or_else(|e| {
             eprintln!("Could not save inputs: {e}");
@@ -743,8 +973,33 @@ fn main() {
     }
 }
 
+const BATTERY_RAM_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Writes battery-backed memory to the save file if it changed since the last write.
+fn save_battery_ram(nes: &Nes, path: &Path, saved: &mut Option<Vec<u8>>) {
+    let Some(ram) = nes.battery_ram() else {
+        return;
+    };
+    if saved.as_deref() == Some(ram) {
+        return;
+    }
+    match std::fs::write(path, ram) {
+        Ok(()) => *saved = Some(ram.to_vec()),
+        Err(error) => eprintln!("Could not write save file: {error}"),
+    }
+}
+
+fn load_palette(name_or_path: &str) -> Result<Palette, String> {
+    if let Some(palette) = Palette::builtin(name_or_path) {
+        return Ok(palette);
+    }
+    let data = std::fs::read(name_or_path).map_err(|error| error.to_string())?;
+    Palette::from_pal_file(&data).map_err(String::from)
+}
+
 struct AudioRunner {
     source: std::sync::mpsc::Receiver<Vec<u16>>,
+    cpu_clock_rate: usize,
     samples: VecDeque<u16>,
 }
 
@@ -759,7 +1014,7 @@ impl AudioCallback for AudioRunner {
             }
         }
 
-        let samples_standard = 1789772 * 256 / 44100;
+        let samples_standard = self.cpu_clock_rate * 256 / 44100;
         let samples_to_use = std::cmp::min(self.samples.len(), samples_standard);
 
         if !self.samples.is_empty() {
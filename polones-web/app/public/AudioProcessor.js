@@ -1,6 +1,7 @@
 class PolonesAudioProcessor extends AudioWorkletProcessor {
-  constructor(...args) {
-    super(...args);
+  constructor(options) {
+    super(options);
+    this.cpuClockRate = options.processorOptions.cpuClockRate;
     this.buffer = new Uint16RingBuffer(1_700_000 * 10);
     this.port.onmessage = (e) => {
       this.buffer.pushBack(e.data);
@@ -14,7 +15,7 @@ class PolonesAudioProcessor extends AudioWorkletProcessor {
         channel[i] = 0;
       }
     }
-    const inSamplesLength = Math.min(this.buffer.length, Math.floor(outSamplesLength * 60 * 29780.5 / 44_100));
+    const inSamplesLength = Math.min(this.buffer.length, Math.floor(outSamplesLength * this.cpuClockRate / 44_100));
     for (let i = 0; i < outSamplesLength; i++) {
       channel[i] = this.buffer.at(Math.floor(i / outSamplesLength * inSamplesLength)) / 65536;
     }
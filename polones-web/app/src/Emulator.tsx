@@ -40,6 +40,7 @@ export default function Emulator() {
   const audioContextRef = React.useRef<AudioContext | null>(null);
   const audioNodeRef = React.useRef<AudioWorkletNode | null>(null);
   const [audioBlocked, setAudioBlocked] = React.useState(false);
+  const [palette, setPalette] = React.useState<string>(window.localStorage.getItem('palette') ?? 'polones');
 
   function onresize(_event: UIEvent) {
     setViewportSize([
@@ -103,6 +104,7 @@ export default function Emulator() {
         .then(rom => {
           try {
             polones.polones_init(new Uint8Array(rom));
+            polones.polones_set_palette(palette);
             setError(null);
             setState('running');
             startAudio();
@@ -133,7 +135,9 @@ export default function Emulator() {
       };
 
       audioCtx.audioWorklet.addModule(window.location.href + (window.location.href.endsWith('/') ? '' : '/') + 'AudioProcessor.js').then(() => {
-        let audioNode = new AudioWorkletNode(audioCtx, "polones-audio-processor");
+        let audioNode = new AudioWorkletNode(audioCtx, "polones-audio-processor", {
+          processorOptions: { cpuClockRate: polones.polones_get_cpu_clock_rate() },
+        });
         audioNode.connect(audioCtx.destination);
         audioNode.onprocessorerror = (error) => {
           console.error("polones audio processor errored", error);
@@ -173,7 +177,7 @@ export default function Emulator() {
 
         polones.polones_set_input(port1, port2);
 
-        const ticksToRun = Math.floor(60 * 29780.5 / refreshRateRef.current);
+        const ticksToRun = Math.floor(polones.polones_get_cpu_clock_rate() / refreshRateRef.current);
         let ticksRun = 0;
 
         while (ticksRun < ticksToRun) {
@@ -211,7 +215,7 @@ export default function Emulator() {
     }
   }
 
-  function handleDragOver(event: DragEvent<HTMLDivElement>) {
+  function handleDragOver(event: DragEvent<HTMLElement>) {
     event.preventDefault();
   }
 
@@ -239,6 +243,27 @@ export default function Emulator() {
     }
   }
 
+  function handlePaletteClick(_event: MouseEvent<HTMLButtonElement>) {
+    const palettes = polones.polones_get_builtin_palettes();
+    const next = palettes[(palettes.indexOf(palette) + 1) % palettes.length];
+    polones.polones_set_palette(next);
+    setPalette(next);
+    window.localStorage.setItem('palette', next);
+  }
+
+  function handlePaletteDrop(event: DragEvent<HTMLCanvasElement>) {
+    event.preventDefault();
+    const file = event.dataTransfer.files[0];
+    if (file) {
+      file.arrayBuffer()
+        .then(data => {
+          polones.polones_load_palette(new Uint8Array(data));
+          setError(null);
+        })
+        .catch(error => setError(error));
+    }
+  }
+
   function handleInputScreenClick(_event: MouseEvent<HTMLButtonElement>) {
     if (state === 'running') {
       stopAudio();
@@ -297,7 +322,7 @@ export default function Emulator() {
           </div>
         )}
         {polones && (state !== 'rom') && (
-          <canvas ref={canvasRef} width={256} height={240} className="canvas" style={{ transform }}></canvas>
+          <canvas ref={canvasRef} width={256} height={240} className="canvas" style={{ transform }} onDrop={handlePaletteDrop} onDragOver={handleDragOver}></canvas>
         )}
         {error && (
           <div className="error">{error}</div>
@@ -316,6 +341,9 @@ export default function Emulator() {
           {audioBlocked && (
             <button type="button" onClick={handleUnblockAudio}>🔊</button>
           )}
+          {state !== 'rom' && (
+            <button type="button" title={`Palette: ${palette}`} onClick={handlePaletteClick}>🎨</button>
+          )}
           <button type="button" onClick={handleInputScreenClick}>🎮</button>
         </aside>
 
@@ -4,7 +4,8 @@ use serde::Deserialize;
 use wasm_bindgen::prelude::*;
 
 use polones_core::game_file::GameFile;
-use polones_core::nes::{GamepadState, Nes, PortState};
+use polones_core::nes::{GamepadState, Nes, PortState, Region};
+use polones_core::palette::{Palette, BUILTIN_PALETTES};
 use utils::set_panic_hook;
 
 static mut STATE: Option<State> = None;
@@ -49,6 +50,56 @@ pub fn polones_tick(times: u32) -> Result<(), String> {
     }
 }
 
+#[wasm_bindgen]
+pub fn polones_set_region(region: String) -> Result<(), String> {
+    if let Some(state) = unsafe { &mut STATE } {
+        state.nes.set_region(match region.as_str() {
+            "ntsc" => Region::Ntsc,
+            "pal" => Region::Pal,
+            "dendy" => Region::Dendy,
+            _ => return Err("unknown region".into()),
+        });
+        Ok(())
+    } else {
+        Err("NES not initialized".into())
+    }
+}
+
+#[wasm_bindgen]
+pub fn polones_get_builtin_palettes() -> Vec<String> {
+    BUILTIN_PALETTES.iter().map(|name| name.to_string()).collect()
+}
+
+#[wasm_bindgen]
+pub fn polones_set_palette(name: String) -> Result<(), String> {
+    if let Some(state) = unsafe { &mut STATE } {
+        let palette = Palette::builtin(&name).ok_or("unknown palette")?;
+        state.nes.set_palette(palette);
+        Ok(())
+    } else {
+        Err("NES not initialized".into())
+    }
+}
+
+#[wasm_bindgen]
+pub fn polones_load_palette(data: Vec<u8>) -> Result<(), String> {
+    if let Some(state) = unsafe { &mut STATE } {
+        state.nes.set_palette(Palette::from_pal_file(&data)?);
+        Ok(())
+    } else {
+        Err("NES not initialized".into())
+    }
+}
+
+#[wasm_bindgen]
+pub fn polones_get_cpu_clock_rate() -> Result<u32, String> {
+    if let Some(state) = unsafe { &mut STATE } {
+        Ok(state.nes.region().cpu_clock_rate())
+    } else {
+        Err("NES not initialized".into())
+    }
+}
+
 #[wasm_bindgen]
 pub fn polones_get_video_frame() -> Result<Option<Vec<u8>>, String> {
     if let Some(state) = unsafe { &mut STATE } {